@@ -0,0 +1,39 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use rscalc::Lexer;
+
+const SNIPPET: &str = r#"
+## A person, with a doc comment attached.
+Person :: struct {
+   name  :string; # plain comments are skipped
+   /* so are /* nested */ block comments */
+   age   :i32;
+}
+area :: (w, h) -> float {
+   return w * h / 2.0 + 0x1.8p3 - 1_000_000 // 3;
+}
+main :: () {
+   total := 0;
+   for i in 0..100 {
+      total += area(i, i ^ 2) >> 1;
+   }
+   greeting := "hello\tworld\n";
+   println(greeting, [[long
+string]], total);
+}
+"#;
+
+fn lexer_benchmark(c: &mut Criterion) {
+    let source = SNIPPET.repeat(64);
+    let mut group = c.benchmark_group("lexer");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    group.bench_function("tokens", |b| {
+        b.iter(|| Lexer::new(black_box(&source)).count())
+    });
+    group.bench_function("trivia", |b| {
+        b.iter(|| Lexer::new(black_box(&source)).emit_trivia(true).count())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, lexer_benchmark);
+criterion_main!(benches);
@@ -4,22 +4,23 @@ fn main() {
     let t = "4.20 * ((69 + 69_420) >> 69)";
 
     println!("Cmd: {t}");
-    let mut lexer = Lexer::new(t);
+    let mut lexer = Lexer::new(t).recover(true);
     println!("Lex: {lexer:?}");
 
-    'l: loop {
-        if let Some(lex) = lexer.next() {
-            match lex {
-                Ok(x) => {
-                    println!("{x:?},");
-                }
-                Err(err) => {
-                    eprintln!("{err}");
-                    break 'l;
-                }
-            }
-        } else {
-            break 'l;
-        }
+    for tok in lexer.by_ref().flatten() {
+        println!("{tok:?},");
+    }
+    for diag in lexer.diagnostics() {
+        eprint!("{}", diag.render(t, true));
+    }
+
+    match Parser::new(Lexer::new(t)).and_then(|mut p| p.parse_expression()) {
+        Ok(expr) => println!("Ast: {expr}\n{expr:#?}"),
+        Err(err) => eprint!("{}", err.render(t, true)),
+    }
+
+    match eval(t) {
+        Ok(value) => println!("Res: {value}"),
+        Err(err) => eprint!("{}", err.render(t, true)),
     }
 }
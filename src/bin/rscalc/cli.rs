@@ -0,0 +1,376 @@
+use std::io::{IsTerminal, Read};
+use std::process::ExitCode;
+
+use rscalc::{Context, Error, Format, Lexer, Parser, Value};
+
+use crate::config::Config;
+
+pub const USAGE: &str = "\
+Usage: rscalc [OPTIONS] [--] EXPR...
+       rscalc [OPTIONS] run FILE
+       rscalc [OPTIONS] [-]
+       rscalc [OPTIONS] -e PROGRAM [FILE]
+
+With no arguments an interactive prompt is opened, or the program is read
+from stdin when it is not a terminal. `-` always reads stdin.
+
+With `-e` the program is run for every line of FILE or stdin, with the
+whitespace or comma separated fields of the line bound to `$1`, `$2`...
+and the first one to `x` as well.
+
+Settings are read from `rscalc/config` in the user config directory, and
+`rscalc/prelude.rsc` there is run before anything else.
+
+Options:
+      --json       Print a JSON object with the span and value or error of
+                   every statement, a line of input at a time
+      --no-prelude Do not run the prelude
+  -h, --help       Print this help
+  -V, --version    Print the version
+
+Exit status:
+  0  success
+  1  runtime error
+  2  parse error
+  3  lex error
+  4  I/O error
+  64 invalid usage
+  65 invalid input line for `-e`
+  78 invalid config";
+
+/// Exit status for invalid command lines.
+pub const EXIT_USAGE: u8 = 64;
+/// Exit status for input lines with fields that are not numbers.
+pub const EXIT_DATA: u8 = 65;
+/// Exit status for invalid config files.
+pub const EXIT_CONFIG: u8 = 78;
+
+/// The parsed command line.
+#[derive(Debug, PartialEq)]
+pub struct Args {
+    pub command: Command,
+    pub prelude: bool,
+    pub json: bool,
+}
+
+impl Args {
+    /// Without a command, `stdin_is_terminal` picks between the prompt and
+    /// reading a program from stdin.
+    pub fn parse(
+        args: impl IntoIterator<Item = String>,
+        stdin_is_terminal: bool,
+    ) -> Result<Self, String> {
+        let mut args = args.into_iter().peekable();
+        let (mut prelude, mut json) = (true, false);
+        loop {
+            match args.peek().map(String::as_str) {
+                Some("--no-prelude") => prelude = false,
+                Some("--json") => json = true,
+                _ => break,
+            }
+            args.next();
+        }
+        let command = match Command::parse(args, stdin_is_terminal)? {
+            // JSON is for other programs, not for people at a prompt
+            Command::Repl if json => Command::Stdin,
+            Command::Filter { .. } if json => {
+                return Err("`--json` does not apply to `-e`".to_owned())
+            }
+            command => command,
+        };
+        Ok(Self {
+            command,
+            prelude,
+            json,
+        })
+    }
+}
+
+/// What the command line asks for.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Repl,
+    Help,
+    Version,
+    Stdin,
+    Eval(String),
+    Run(String),
+    /// `-e`, the program runs on each line of the file or stdin.
+    Filter {
+        program: String,
+        file: Option<String>,
+    },
+}
+
+impl Command {
+    pub fn parse(
+        args: impl IntoIterator<Item = String>,
+        stdin_is_terminal: bool,
+    ) -> Result<Self, String> {
+        let mut args = args.into_iter();
+        let Some(first) = args.next() else {
+            return Ok(if stdin_is_terminal {
+                Self::Repl
+            } else {
+                Self::Stdin
+            });
+        };
+        let command = match first.as_str() {
+            "-h" | "--help" => Self::Help,
+            "-V" | "--version" => Self::Version,
+            "-" => Self::Stdin,
+            "--" => Self::Eval(join(args.by_ref())),
+            "run" => Self::Run(args.next().ok_or("`run` expects a file")?),
+            "-e" => Self::Filter {
+                program: args.next().ok_or("`-e` expects a program")?,
+                file: args.next(),
+            },
+            // let negative numbers through
+            flag if flag.starts_with('-')
+                && !flag[1..].starts_with(|c: char| c.is_ascii_digit()) =>
+            {
+                return Err(format!("unknown option `{flag}`"))
+            }
+            _ => Self::Eval(join(std::iter::once(first).chain(args.by_ref()))),
+        };
+        match args.next() {
+            Some(arg) => Err(format!("unexpected argument `{arg}`")),
+            None => Ok(command),
+        }
+    }
+}
+
+/// Joins the words of an expression split up by the shell.
+fn join(args: impl Iterator<Item = String>) -> String {
+    args.collect::<Vec<_>>().join(" ")
+}
+
+/// What programs run in: the bindings and how to show results.
+pub struct Session {
+    pub ctx: Context,
+    pub format: Format,
+    pub color: bool,
+}
+
+impl Session {
+    /// A session set up as `config` says, with the prelude run unless
+    /// `prelude` is false. Errors in the prelude are reported, along with the
+    /// exit status they call for.
+    pub fn new(config: &Config, prelude: bool) -> Result<Self, u8> {
+        let mut session = Self {
+            ctx: Context::new(),
+            format: config.format,
+            color: config
+                .color
+                .unwrap_or_else(|| std::io::stderr().is_terminal()),
+        };
+        session.ctx.set_angle_unit(config.angle_unit);
+        session.ctx.set_precision(config.precision);
+        session.ctx.set_mode(config.mode);
+        let Some(path) = config.prelude.as_ref().filter(|_| prelude) else {
+            return Ok(session);
+        };
+        let path = path.to_string_lossy();
+        match std::fs::read_to_string(path.as_ref()) {
+            Ok(source) => {
+                if let Err(err) = exec(&source, Some(&path), &mut session.ctx, session.format) {
+                    eprint!("{}", err.render(&source, session.color));
+                    return Err(exit_code(&err));
+                }
+            }
+            // only a prelude named in the config has to be there
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound && !config.prelude_set => {}
+            Err(err) => {
+                eprintln!("rscalc: {path}: {err}");
+                return Err(exit_code(&err.into()));
+            }
+        }
+        Ok(session)
+    }
+
+    /// Goes back to the `initial` bindings, keeping the current mode and
+    /// precision.
+    pub fn reset(&mut self, initial: &Context) {
+        let (mode, precision) = (self.ctx.mode(), self.ctx.precision());
+        self.ctx = initial.clone();
+        self.ctx.set_precision(precision);
+        self.ctx.set_mode(mode);
+    }
+
+    /// Runs a whole program, printing its value, or the error and picking
+    /// the exit status from its kind.
+    pub fn run(&mut self, source: &str, path: Option<&str>) -> ExitCode {
+        match exec(source, path, &mut self.ctx, self.format) {
+            Ok(value) => {
+                if let Some(value) = value {
+                    println!("{value}");
+                }
+                ExitCode::SUCCESS
+            }
+            Err(err) => {
+                eprint!("{}", err.render(source, self.color));
+                ExitCode::from(exit_code(&err))
+            }
+        }
+    }
+}
+
+/// Runs a program in `ctx` and writes its value in the format given after
+/// `->`, or else in `format`. `None` when there is no value to show.
+pub fn exec<'s>(
+    source: &'s str,
+    path: Option<&'s str>,
+    ctx: &mut Context,
+    format: Format,
+) -> rscalc::Result<'s, Option<String>> {
+    let mut lexer = Lexer::new(source);
+    if let Some(path) = path {
+        lexer = lexer.set_source_path(path);
+    }
+    let program = Parser::new(lexer)?.parse_program()?;
+    let format = program.format().unwrap_or(format);
+    Ok(match ctx.exec(&program)? {
+        Value::Unit => None,
+        value => Some(format.value(&value)),
+    })
+}
+
+/// Whether `input` only fails because it stops too early, the parser is run
+/// on its own so that nothing gets evaluated twice.
+pub fn is_incomplete(input: &str) -> bool {
+    Parser::new(Lexer::new(input))
+        .and_then(|mut parser| parser.parse_program())
+        .is_err_and(|err| err.is_incomplete())
+}
+
+pub fn read_stdin() -> std::io::Result<String> {
+    let mut source = String::new();
+    std::io::stdin().read_to_string(&mut source)?;
+    Ok(source)
+}
+
+pub fn exit_code(err: &Error) -> u8 {
+    match err {
+        Error::Any(_) | Error::EvalError(_) => 1,
+        Error::ParsingError(_) => 2,
+        Error::LexingError(_) => 3,
+        Error::IoError(_) => 4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str, terminal: bool) -> Result<Args, String> {
+        Args::parse(line.split_whitespace().map(str::to_owned), terminal)
+    }
+
+    fn command(line: &str) -> Result<Command, String> {
+        args(line, true).map(|args| args.command)
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(command(""), Ok(Command::Repl));
+        assert_eq!(args("", false).unwrap().command, Command::Stdin);
+        assert_eq!(args("-", true).unwrap().command, Command::Stdin);
+        assert_eq!(command("-h"), Ok(Command::Help));
+        assert_eq!(command("--version"), Ok(Command::Version));
+        assert_eq!(command("1 + 2"), Ok(Command::Eval("1 + 2".to_owned())));
+        assert_eq!(command("-5 * 2"), Ok(Command::Eval("-5 * 2".to_owned())));
+        // everything after `--` is the expression, options included
+        assert_eq!(command("-- -x + 1"), Ok(Command::Eval("-x + 1".to_owned())));
+        assert_eq!(command("-- -h"), Ok(Command::Eval("-h".to_owned())));
+        assert_eq!(command("run a.rsc"), Ok(Command::Run("a.rsc".to_owned())));
+        assert_eq!(
+            command("-e x*2 data.csv"),
+            Ok(Command::Filter {
+                program: "x*2".to_owned(),
+                file: Some("data.csv".to_owned()),
+            })
+        );
+        assert_eq!(
+            command("-e x*2"),
+            Ok(Command::Filter {
+                program: "x*2".to_owned(),
+                file: None,
+            })
+        );
+    }
+
+    #[test]
+    fn options() {
+        let parsed = args("--no-prelude --json run a.rsc", true).unwrap();
+        assert_eq!(
+            parsed,
+            Args {
+                command: Command::Run("a.rsc".to_owned()),
+                prelude: false,
+                json: true,
+            }
+        );
+        // no prompt for JSON output
+        assert_eq!(args("--json", true).unwrap().command, Command::Stdin);
+        assert!(args("--json -e x", true).is_err());
+    }
+
+    #[test]
+    fn usage_errors() {
+        assert_eq!(command("run"), Err("`run` expects a file".to_owned()));
+        assert_eq!(command("-e"), Err("`-e` expects a program".to_owned()));
+        assert_eq!(
+            command("--frobnicate"),
+            Err("unknown option `--frobnicate`".to_owned())
+        );
+        assert_eq!(
+            command("run a.rsc b.rsc"),
+            Err("unexpected argument `b.rsc`".to_owned())
+        );
+        assert_eq!(command("- 1"), Err("unexpected argument `1`".to_owned()));
+        assert!(USAGE.contains(&format!("{EXIT_USAGE} invalid usage")));
+    }
+
+    #[test]
+    fn exit_codes() {
+        let mut session = Session::new(&Config::default(), false).unwrap();
+        session.color = false;
+        let mut run = |source: &str| session.run(source, Some("a.rsc"));
+        assert_eq!(run("x := 2; x * 3"), ExitCode::SUCCESS);
+        assert_eq!(run("1 / 0"), ExitCode::from(1));
+        assert_eq!(run("1 +"), ExitCode::from(2));
+        assert_eq!(run("0x"), ExitCode::from(3));
+        // the bindings carry over from one run to the next
+        assert_eq!(run("x"), ExitCode::SUCCESS);
+
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "missing");
+        assert_eq!(exit_code(&io.into()), 4);
+        for (code, text) in [
+            (1, "runtime error"),
+            (2, "parse error"),
+            (3, "lex error"),
+            (4, "I/O error"),
+            (EXIT_USAGE, "invalid usage"),
+            (EXIT_DATA, "invalid input line"),
+            (EXIT_CONFIG, "invalid config"),
+        ] {
+            assert!(USAGE.contains(&format!("  {code:<2} {text}")), "{code}");
+        }
+    }
+
+    #[test]
+    fn run_file() {
+        let mut ctx = Context::new();
+        let run = exec("y := 1", Some("a.rsc"), &mut ctx, Format::default());
+        assert_eq!(run.unwrap(), None);
+        let source = "y := 5;\ny +";
+        match exec(source, Some("a.rsc"), &mut ctx, Format::default()) {
+            Err(Error::ParsingError(err)) => assert_eq!(err.loc().to_string(), "a.rsc:2:4"),
+            other => panic!("expected a parse error, got {other:?}"),
+        }
+        assert_eq!(
+            exec("y * 2 -> hex", Some("a.rsc"), &mut ctx, Format::default()).unwrap(),
+            Some("0x2".to_owned())
+        );
+    }
+}
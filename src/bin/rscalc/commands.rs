@@ -0,0 +1,265 @@
+//! The `:name argument` meta commands of the REPL.
+
+use rscalc::{Block, Context, Expr, ExprKind, Lexer, Parser, Stmt, StmtKind};
+
+use crate::cli::{self, Session};
+
+/// A meta command, `args` describes its argument for `:help`.
+pub struct Meta {
+    pub name: &'static str,
+    pub args: &'static str,
+    pub help: &'static str,
+}
+
+pub const COMMANDS: &[Meta] = &[
+    Meta::new("tokens", "<source>", "print the tokens of the source"),
+    Meta::new("ast", "<source>", "print the syntax tree of the source"),
+    Meta::new(
+        "type",
+        "<source>",
+        "print the type of the value, without side effects",
+    ),
+    Meta::new("format", "[format]", "show or set how results are written"),
+    Meta::new(
+        "mode",
+        "[mode]",
+        "show or set the arithmetic, float, exact or decimal",
+    ),
+    Meta::new(
+        "decimal",
+        "[digits]",
+        "show or set the digits and rounding of decimals",
+    ),
+    Meta::new("vars", "", "list the global variables"),
+    Meta::new("funcs", "", "list the user functions, structs and builtins"),
+    Meta::new("clear", "", "reset the variables and functions"),
+    Meta::new("load", "<file>", "run a file in the session"),
+    Meta::new("save", "<file>", "write the session out as a program"),
+    Meta::new(
+        "restore",
+        "<file>",
+        "clear the session and load a saved one",
+    ),
+    Meta::new("help", "", "show this list"),
+    Meta::new("quit", "", "leave the REPL"),
+];
+
+impl Meta {
+    const fn new(name: &'static str, args: &'static str, help: &'static str) -> Self {
+        Self { name, args, help }
+    }
+}
+
+pub fn help() {
+    for meta in COMMANDS {
+        let usage = format!(":{} {}", meta.name, meta.args);
+        println!("  {usage:<18} {}", meta.help);
+    }
+}
+
+/// Prints every token with where it starts, comments included.
+pub fn tokens(source: &str, color: bool) {
+    let mut lexer = Lexer::new(source).emit_comments(true).recover(true);
+    for token in lexer.by_ref().flatten() {
+        println!("{:<8} {:?}", token.span.start.to_string(), token.value);
+    }
+    for diag in lexer.diagnostics() {
+        eprint!("{}", diag.render(source, color));
+    }
+}
+
+pub fn ast(source: &str, color: bool) {
+    match Parser::new(Lexer::new(source)).and_then(|mut parser| parser.parse_program()) {
+        Ok(program) => print_block(&program, 0),
+        Err(err) => eprint!("{}", err.render(source, color)),
+    }
+}
+
+/// Runs the source on a copy of the context, so that it cannot change any
+/// binding.
+pub fn type_of(session: &Session, source: &str) {
+    let color = session.color;
+    match rscalc::eval_with(source, &mut session.ctx.clone()) {
+        Ok(value) => println!("{}", value.type_name()),
+        Err(err) => eprint!("{}", err.render(source, color)),
+    }
+}
+
+/// Lists the globals as declarations.
+pub fn vars(ctx: &Context) {
+    for (name, value) in ctx.vars() {
+        let decl = if ctx.is_const(name) { "::" } else { ":=" };
+        println!("{name} {decl} {value}");
+    }
+}
+
+pub fn funcs(ctx: &Context) {
+    for decl in ctx.functions() {
+        println!("{decl}");
+    }
+    for decl in ctx.structs() {
+        println!("{decl}");
+    }
+    let builtins: Vec<_> = ctx
+        .builtins()
+        .map(|builtin| match builtin.max_args {
+            Some(max) if max == builtin.min_args => format!("{}/{max}", builtin.name),
+            Some(max) => format!("{}/{}-{max}", builtin.name, builtin.min_args),
+            None => format!("{}/{}+", builtin.name, builtin.min_args),
+        })
+        .collect();
+    if !builtins.is_empty() {
+        println!("builtins: {}", builtins.join(" "));
+    }
+}
+
+/// Runs the file in the session, its errors point into the file.
+pub fn load(session: &mut Session, path: &str) {
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) => return eprintln!("rscalc: {path}: {err}"),
+    };
+    match cli::exec(&source, Some(path), &mut session.ctx, session.format) {
+        Ok(value) => value.into_iter().for_each(|value| println!("{value}")),
+        Err(err) => eprint!("{}", err.render(&source, session.color)),
+    }
+}
+
+pub fn save(ctx: &Context, path: &str) {
+    if let Err(err) = std::fs::write(path, ctx.to_source()) {
+        eprintln!("rscalc: {path}: {err}");
+    }
+}
+
+fn print_block(block: &Block, depth: usize) {
+    for stmt in &block.stmts {
+        print_stmt(stmt, depth);
+    }
+    if let Some(tail) = &block.tail {
+        print_expr(tail, depth);
+    }
+}
+
+fn print_stmt(stmt: &Stmt, depth: usize) {
+    let indent = "  ".repeat(depth);
+    match &stmt.kind {
+        StmtKind::Expr(expr) => {
+            println!("{indent}Expr");
+            print_expr(expr, depth + 1);
+        }
+        StmtKind::Decl {
+            name,
+            ty,
+            value,
+            constant,
+        } => {
+            let decl = if *constant { "Const" } else { "Decl" };
+            match ty {
+                Some(ty) => println!("{indent}{decl} {name} :{ty}"),
+                None => println!("{indent}{decl} {name}"),
+            }
+            print_expr(value, depth + 1);
+        }
+        StmtKind::Assign { name, op, value } => {
+            let op = op.map(|op| op.to_string()).unwrap_or_default();
+            println!("{indent}Assign {name} {op}=");
+            print_expr(value, depth + 1);
+        }
+        StmtKind::Fn(decl) => {
+            let params: Vec<_> = decl.params.iter().map(|p| p.name.as_ref()).collect();
+            println!("{indent}Fn {}({})", decl.name, params.join(", "));
+            print_block(&decl.body, depth + 1);
+        }
+        StmtKind::Struct(decl) => {
+            let fields: Vec<_> = decl.fields.iter().map(|f| f.name.as_ref()).collect();
+            println!("{indent}Struct {} {{{}}}", decl.name, fields.join(", "));
+        }
+        StmtKind::While(cond, body) => {
+            println!("{indent}While");
+            print_expr(cond, depth + 1);
+            print_block(body, depth + 1);
+        }
+        StmtKind::For {
+            var,
+            start,
+            end,
+            inclusive,
+            body,
+        } => {
+            let range = if *inclusive { "..=" } else { ".." };
+            println!("{indent}For {var} {range}");
+            print_expr(start, depth + 1);
+            print_expr(end, depth + 1);
+            print_block(body, depth + 1);
+        }
+        StmtKind::Return(value) => {
+            println!("{indent}Return");
+            if let Some(value) = value {
+                print_expr(value, depth + 1);
+            }
+        }
+        StmtKind::Break => println!("{indent}Break"),
+        StmtKind::Continue => println!("{indent}Continue"),
+    }
+}
+
+fn print_expr(expr: &Expr, depth: usize) {
+    let indent = "  ".repeat(depth);
+    match &expr.kind {
+        ExprKind::Int(i) => println!("{indent}Int {i}"),
+        ExprKind::BigInt(i) => println!("{indent}Int {i}"),
+        ExprKind::Float(x) => println!("{indent}Float {x:?}"),
+        ExprKind::Decimal(..) => println!("{indent}Decimal {expr}"),
+        ExprKind::Imag(x) => println!("{indent}Imag {x:?}"),
+        ExprKind::Quantity(value, units) => {
+            let units: Vec<_> = units
+                .iter()
+                .map(|(u, e)| format!("{}^{e}", u.value))
+                .collect();
+            println!("{indent}Quantity {}", units.join(" "));
+            print_expr(value, depth + 1);
+        }
+        ExprKind::Bool(b) => println!("{indent}Bool {b}"),
+        ExprKind::Ident(name) => println!("{indent}Ident {name}"),
+        ExprKind::Unary(op, operand) => {
+            println!("{indent}Unary {op}");
+            print_expr(operand, depth + 1);
+        }
+        ExprKind::Binary(op, lhs, rhs) => {
+            println!("{indent}Binary {op}");
+            print_expr(lhs, depth + 1);
+            print_expr(rhs, depth + 1);
+        }
+        ExprKind::Call(callee, args) => {
+            println!("{indent}Call");
+            print_expr(callee, depth + 1);
+            for arg in args {
+                print_expr(arg, depth + 1);
+            }
+        }
+        ExprKind::Field(target, name) => {
+            println!("{indent}Field {name}");
+            print_expr(target, depth + 1);
+        }
+        ExprKind::Block(block) => {
+            println!("{indent}Block");
+            print_block(block, depth + 1);
+        }
+        ExprKind::Format(expr, format) => {
+            println!("{indent}Format {format}");
+            print_expr(expr, depth + 1);
+        }
+        ExprKind::If(branches, otherwise) => {
+            for (idx, (cond, block)) in branches.iter().enumerate() {
+                println!("{indent}{}", if idx == 0 { "If" } else { "Elif" });
+                print_expr(cond, depth + 1);
+                println!("{indent}Then");
+                print_block(block, depth + 1);
+            }
+            if let Some(block) = otherwise {
+                println!("{indent}Else");
+                print_block(block, depth + 1);
+            }
+        }
+    }
+}
@@ -0,0 +1,281 @@
+//! The user configuration, read from `<config dir>/rscalc/config`.
+//!
+//! The file has one `key = value` setting per line and `#` comments:
+//!
+//! ```text
+//! format = sci        # any `:format` words
+//! precision = 6       # significant digits
+//! angle = deg         # rad, deg or grad
+//! mode = exact        # float, exact or decimal arithmetic
+//! decimal = 50        # digits and rounding of decimals
+//! prompt = "calc> "
+//! color = auto        # auto, on or off
+//! history = 1000      # lines kept in the history file
+//! prelude = ~/team/prelude.rsc
+//! ```
+//!
+//! The prelude defaults to `prelude.rsc` next to the config file.
+
+use std::path::{Path, PathBuf};
+
+use rscalc::{AngleUnit, Format, Mode, Precision, PRECISION_HELP};
+
+const PROMPT: &str = ">> ";
+const HISTORY_SIZE: usize = 1000;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub format: Format,
+    pub angle_unit: AngleUnit,
+    pub mode: Mode,
+    pub precision: Precision,
+    pub prompt: String,
+    /// `None` to color output only on terminals.
+    pub color: Option<bool>,
+    pub history_size: usize,
+    pub prelude: Option<PathBuf>,
+    /// Whether the prelude was set in the file, and so has to exist.
+    pub prelude_set: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            format: Format::default(),
+            angle_unit: AngleUnit::default(),
+            mode: Mode::default(),
+            precision: Precision::default(),
+            prompt: PROMPT.to_owned(),
+            color: None,
+            history_size: HISTORY_SIZE,
+            prelude: config_dir().map(|dir| dir.join("prelude.rsc")),
+            prelude_set: false,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the config file, the defaults apply when there is none.
+    pub fn load() -> Result<Self, String> {
+        let Some(path) = config_dir().map(|dir| dir.join("config")) else {
+            return Ok(Self::default());
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text, &path),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(format!("{}: {err}", path.display())),
+        }
+    }
+
+    /// Parses the settings in `text`, `path` is for error messages.
+    pub fn parse(text: &str, path: &Path) -> Result<Self, String> {
+        let mut config = Self::default();
+        let mut precision = None;
+        for (idx, line) in text.lines().enumerate() {
+            let error = |msg: String| format!("{}:{}: {msg}", path.display(), idx + 1);
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(error(format!("expected `key = value`, got `{line}`")));
+            };
+            let value = unquote(value.trim());
+            match key.trim() {
+                "format" => {
+                    config.format = value
+                        .parse()
+                        .map_err(|word| error(format!("unknown format `{word}`")))?
+                }
+                "precision" => {
+                    precision = match value.parse() {
+                        Ok(digits @ 1..=rscalc::MAX_DIGITS) => Some(digits),
+                        _ => return Err(error(format!("invalid precision `{value}`"))),
+                    }
+                }
+                "angle" => {
+                    config.angle_unit = value.parse().map_err(|unit| {
+                        error(format!(
+                            "unknown angle unit `{unit}`, expected rad, deg or grad"
+                        ))
+                    })?
+                }
+                "mode" => {
+                    config.mode = value.parse().map_err(|mode| {
+                        error(format!(
+                            "unknown mode `{mode}`, expected float, exact or decimal"
+                        ))
+                    })?
+                }
+                "decimal" => {
+                    config.precision = value.parse().map_err(|word| {
+                        error(format!("invalid precision `{word}`, {PRECISION_HELP}"))
+                    })?
+                }
+                "prompt" => config.prompt = value.to_owned(),
+                "color" => {
+                    config.color = match value {
+                        "auto" => None,
+                        "on" | "true" => Some(true),
+                        "off" | "false" => Some(false),
+                        _ => {
+                            return Err(error(format!(
+                                "invalid color `{value}`, expected auto, on or off"
+                            )))
+                        }
+                    }
+                }
+                "history" => {
+                    config.history_size = value
+                        .parse()
+                        .map_err(|_| error(format!("invalid history size `{value}`")))?
+                }
+                "prelude" => {
+                    config.prelude = Some(expand_home(value));
+                    config.prelude_set = true;
+                }
+                key => return Err(error(format!("unknown setting `{key}`"))),
+            }
+        }
+        if precision.is_some() {
+            config.format = config.format.digits(precision);
+        }
+        Ok(config)
+    }
+}
+
+fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("rscalc"))
+}
+
+/// Drops a `#` comment, unless it is inside quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (idx, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..idx],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Quotes keep the spaces around a value, as in `prompt = "> "`.
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rscalc::{Notation, Rounding};
+
+    fn parse(text: &str) -> Result<Config, String> {
+        Config::parse(text, Path::new("config"))
+    }
+
+    #[test]
+    fn settings() {
+        let config = parse(
+            "# a comment line\n\
+             format = sci\n\
+             \n\
+             precision = 6   # trailing comment\n\
+             angle = deg\n\
+             mode = exact\n\
+             decimal = 50 half-up\n\
+             prompt = \"calc> \"\n\
+             color = off\n\
+             history = 20\n\
+             prelude = /etc/rscalc/prelude.rsc\n",
+        )
+        .unwrap();
+        assert_eq!(config.format, Format::new(Notation::Sci).digits(Some(6)));
+        assert_eq!(config.angle_unit, AngleUnit::Deg);
+        assert_eq!(config.mode, Mode::Exact);
+        assert_eq!(config.precision, Precision::new(50, Rounding::HalfUp));
+        assert_eq!(config.prompt, "calc> ");
+        assert_eq!(config.color, Some(false));
+        assert_eq!(config.history_size, 20);
+        assert_eq!(
+            config.prelude.as_deref(),
+            Some(Path::new("/etc/rscalc/prelude.rsc"))
+        );
+        assert!(config.prelude_set);
+
+        let config = parse("color = auto\nmode=float").unwrap();
+        assert_eq!(config.color, None);
+        assert_eq!(config.mode, Mode::Float);
+        assert!(!config.prelude_set);
+        assert_eq!(parse("color = on").unwrap().color, Some(true));
+    }
+
+    #[test]
+    fn defaults() {
+        let config = parse("").unwrap();
+        assert_eq!(config.format, Format::default());
+        assert_eq!(config.mode, Mode::Decimal);
+        assert_eq!(config.prompt, PROMPT);
+        assert_eq!(config.history_size, HISTORY_SIZE);
+    }
+
+    #[test]
+    fn quotes() {
+        // `#` in quotes is part of the value
+        assert_eq!(parse("prompt = \"# \" # not this").unwrap().prompt, "# ");
+        assert_eq!(parse("prompt = \"a=b\"").unwrap().prompt, "a=b");
+        assert_eq!(parse("prompt = >>").unwrap().prompt, ">>");
+        assert_eq!(parse("prompt = \"\"").unwrap().prompt, "");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            parse("mode = exact\ncolour = on").unwrap_err(),
+            "config:2: unknown setting `colour`"
+        );
+        assert_eq!(
+            parse("just words").unwrap_err(),
+            "config:1: expected `key = value`, got `just words`"
+        );
+        assert_eq!(
+            parse("mode = fast").unwrap_err(),
+            "config:1: unknown mode `fast`, expected float, exact or decimal"
+        );
+        assert_eq!(
+            parse("angle = turns").unwrap_err(),
+            "config:1: unknown angle unit `turns`, expected rad, deg or grad"
+        );
+        assert_eq!(
+            parse("color = maybe").unwrap_err(),
+            "config:1: invalid color `maybe`, expected auto, on or off"
+        );
+        assert_eq!(
+            parse("history = -1").unwrap_err(),
+            "config:1: invalid history size `-1`"
+        );
+        for precision in ["0", "many", &(rscalc::MAX_DIGITS + 1).to_string()] {
+            assert_eq!(
+                parse(&format!("precision = {precision}")).unwrap_err(),
+                format!("config:1: invalid precision `{precision}`")
+            );
+        }
+        assert!(parse("format = fancy")
+            .unwrap_err()
+            .starts_with("config:1: unknown format `fancy`"));
+        assert!(parse("decimal = 0")
+            .unwrap_err()
+            .starts_with("config:1: invalid precision `0`"));
+    }
+}
@@ -0,0 +1,136 @@
+//! The `-e` filter mode, one program run for every line of input.
+
+use std::process::ExitCode;
+
+use bigdecimal::BigDecimal;
+use num_bigint::BigInt;
+use rscalc::{Context, Lexer, Mode, Parser, Value};
+
+use crate::cli::{self, Session};
+
+/// Parses `source` once and runs it on each non blank line of `input`,
+/// printing the values. Errors name the line of `name` they come from, the
+/// exit status is that of the first one.
+pub fn run(session: &mut Session, source: &str, input: &str, name: &str) -> ExitCode {
+    let program =
+        match Parser::new(Lexer::new(source)).and_then(|mut parser| parser.parse_program()) {
+            Ok(program) => program,
+            Err(err) => {
+                eprint!("{}", err.render(source, session.color));
+                return ExitCode::from(cli::exit_code(&err));
+            }
+        };
+    let format = program.format().unwrap_or(session.format);
+    let mut status = 0;
+    for (idx, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        // a fresh context, so that no line sees the fields of another
+        let mut ctx = session.ctx.clone();
+        let code = match bind_fields(&mut ctx, line) {
+            Err(field) => {
+                eprintln!("rscalc: {name}:{}: `{field}` is not a number", idx + 1);
+                cli::EXIT_DATA
+            }
+            Ok(()) => match ctx.exec(&program) {
+                Ok(Value::Unit) => continue,
+                Ok(value) => {
+                    println!("{}", format.value(&value));
+                    continue;
+                }
+                Err(err) => {
+                    eprint!("{name}:{}: {}", idx + 1, err.render(source, session.color));
+                    cli::exit_code(&err)
+                }
+            },
+        };
+        if status == 0 {
+            status = code;
+        }
+    }
+    ExitCode::from(status)
+}
+
+/// Binds the fields of `line` to `$1`, `$2`... and the first one to `x`.
+/// Fields with a point are decimals in the decimal mode. The error is the
+/// first field that is not a number, `inf` and `nan` included.
+fn bind_fields(ctx: &mut Context, line: &str) -> Result<(), String> {
+    let decimal = ctx.mode() == Mode::Decimal;
+    let fields = line
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|field| !field.is_empty());
+    for (idx, field) in fields.enumerate() {
+        let value = if let Ok(i) = field.parse::<BigInt>() {
+            Value::from(i)
+        } else if let Some(d) = decimal.then(|| field.parse::<BigDecimal>().ok()).flatten() {
+            Value::from(d)
+        } else {
+            match field.parse::<f64>() {
+                Ok(x) if x.is_finite() => Value::Float(x),
+                _ => return Err(field.to_owned()),
+            }
+        };
+        if idx == 0 {
+            ctx.set_var("x", value.clone());
+        }
+        ctx.set_var(format!("${}", idx + 1), value);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bind(mode: Mode, line: &str) -> Result<Context, String> {
+        let mut ctx = Context::new();
+        ctx.set_mode(mode);
+        bind_fields(&mut ctx, line)?;
+        Ok(ctx)
+    }
+
+    fn field(ctx: &Context, name: &str) -> String {
+        let value = ctx.get_var(name).unwrap();
+        format!("{}: {value}", value.type_name())
+    }
+
+    #[test]
+    fn splits() {
+        let ctx = bind(Mode::Decimal, "1,2, 3\t4   5").unwrap();
+        let fields: Vec<_> = (1..=5).map(|idx| field(&ctx, &format!("${idx}"))).collect();
+        assert_eq!(fields, ["int: 1", "int: 2", "int: 3", "int: 4", "int: 5"]);
+        assert_eq!(field(&ctx, "x"), "int: 1");
+        assert!(ctx.get_var("$6").is_none());
+        // empty fields between commas are skipped
+        let ctx = bind(Mode::Decimal, ",7,,8,").unwrap();
+        assert_eq!(field(&ctx, "$1"), "int: 7");
+        assert_eq!(field(&ctx, "$2"), "int: 8");
+        assert!(ctx.get_var("$3").is_none());
+    }
+
+    #[test]
+    fn numbers() {
+        let ctx = bind(Mode::Decimal, "123456789012345678901234567890 -0.1 1e3").unwrap();
+        assert_eq!(field(&ctx, "$1"), "int: 123456789012345678901234567890");
+        assert!(matches!(ctx.get_var("$1"), Some(Value::BigInt(_))));
+        assert_eq!(field(&ctx, "$2"), "decimal: -0.1");
+        assert_eq!(field(&ctx, "$3"), "decimal: 1000.0");
+        let ctx = bind(Mode::Float, "-0.1 1e3").unwrap();
+        assert_eq!(field(&ctx, "$1"), "float: -0.1");
+        assert_eq!(field(&ctx, "$2"), "float: 1000.0");
+        let ctx = bind(Mode::Exact, "0.25").unwrap();
+        assert_eq!(field(&ctx, "$1"), "float: 0.25");
+    }
+
+    #[test]
+    fn not_numbers() {
+        assert_eq!(bind(Mode::Decimal, "1 two 3").err().as_deref(), Some("two"));
+        assert_eq!(bind(Mode::Float, "1.2.3").err().as_deref(), Some("1.2.3"));
+        for field in ["inf", "-infinity", "NaN"] {
+            for mode in [Mode::Float, Mode::Exact, Mode::Decimal] {
+                assert_eq!(bind(mode, field).err().as_deref(), Some(field));
+            }
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use rscalc::Context;
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+
+use crate::commands::COMMANDS;
+
+/// Tab completion for the REPL, from a copy of the session bindings that is
+/// refreshed after every input.
+pub struct Helper {
+    pub ctx: Context,
+}
+
+impl Completer for Helper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let command = line
+            .get(..pos)
+            .and_then(|before| before.strip_prefix(':'))
+            .filter(|name| !name.contains(char::is_whitespace));
+        if let Some(name) = command {
+            let names = COMMANDS
+                .iter()
+                .filter(|meta| meta.name.starts_with(name))
+                .map(|meta| meta.name.to_owned());
+            return Ok((1, names.collect()));
+        }
+        Ok(rscalc::complete(line, pos, &self.ctx))
+    }
+}
+
+impl Hinter for Helper {
+    type Hint = String;
+}
+
+impl Highlighter for Helper {}
+
+impl Validator for Helper {}
+
+impl rustyline::Helper for Helper {}
@@ -0,0 +1,405 @@
+//! The `--json` batch mode, one JSON object per line for every statement.
+//!
+//! Input is run a line at a time, a line that leaves a statement unfinished
+//! is joined with the following ones. Results and errors look like
+//!
+//! ```text
+//! {"span":{"start":{..},"end":{..}},"type":"int","value":6,"text":"0x6"}
+//! {"span":{..},"error":{"stage":"eval","kind":"DivisionByZero","message":"division by zero","loc":{"line":2,"col":5,"offset":12}}}
+//! ```
+//!
+//! An error skips the rest of its line, the following lines still run.
+
+use std::fmt::{Debug, Write};
+use std::process::ExitCode;
+
+use rscalc::{Error, Expr, ExprKind, Format, Lexer, Loc, Parser, Span, StmtKind, Value};
+
+use crate::cli::{self, Session};
+
+/// Where a chunk of the input starts, the locations inside it are relative
+/// to that.
+#[derive(Debug, Clone, Copy, Default)]
+struct Origin {
+    line: u32,
+    offset: usize,
+}
+
+/// Runs `source` chunk by chunk. The exit status is that of the first error.
+pub fn run(session: &mut Session, source: &str, path: Option<&str>) -> ExitCode {
+    let mut status = 0;
+    let mut out = Vec::new();
+    for (origin, chunk) in chunks(source) {
+        let code = run_chunk(session, chunk, path, origin, &mut out);
+        out.drain(..).for_each(|line| println!("{line}"));
+        if let Some(code) = code {
+            if status == 0 {
+                status = code;
+            }
+        }
+    }
+    ExitCode::from(status)
+}
+
+/// Splits the source into lines, keeping unfinished statements together.
+fn chunks(source: &str) -> Vec<(Origin, &str)> {
+    let mut chunks = Vec::new();
+    let mut origin = Origin::default();
+    let mut end = 0;
+    let mut lines = 0;
+    for line in source.split_inclusive('\n') {
+        end += line.len();
+        lines += 1;
+        let chunk = &source[origin.offset..end];
+        if end == source.len() || !cli::is_incomplete(chunk) {
+            chunks.push((origin, chunk));
+            origin = Origin {
+                line: origin.line + lines,
+                offset: end,
+            };
+            lines = 0;
+        }
+    }
+    chunks
+}
+
+/// Runs the statements of a chunk and adds their reports to `out`, the exit
+/// status of its error if there is one.
+fn run_chunk(
+    session: &mut Session,
+    chunk: &str,
+    path: Option<&str>,
+    origin: Origin,
+    out: &mut Vec<String>,
+) -> Option<u8> {
+    let mut lexer = Lexer::new(chunk);
+    if let Some(path) = path {
+        lexer = lexer.set_source_path(path);
+    }
+    let program = match Parser::new(lexer).and_then(|mut parser| parser.parse_program()) {
+        Ok(program) => program,
+        // nothing ran, the whole chunk is to blame
+        Err(err) => {
+            let span = chunk_span(chunk, path);
+            return emit(out, &span, Err(err), session.format, origin);
+        }
+    };
+    for stmt in &program.stmts {
+        let format = match &stmt.kind {
+            StmtKind::Expr(expr) => format_of(expr, session.format),
+            _ => session.format,
+        };
+        let status = emit(out, &stmt.span, session.ctx.run_stmt(stmt), format, origin);
+        if status.is_some() {
+            return status;
+        }
+    }
+    let tail = program.tail.as_deref()?;
+    let format = format_of(tail, session.format);
+    emit(out, &tail.span, session.ctx.eval_expr(tail), format, origin)
+}
+
+fn format_of(expr: &Expr, default: Format) -> Format {
+    match expr.kind {
+        ExprKind::Format(_, format) => format,
+        _ => default,
+    }
+}
+
+/// The span of the trimmed chunk.
+fn chunk_span<'s>(chunk: &str, path: Option<&'s str>) -> Span<'s> {
+    let chunk = chunk.trim_end();
+    let last_line = chunk.rsplit('\n').next().unwrap_or_default();
+    let line = chunk.matches('\n').count() as u32 + 1;
+    let mut start = Loc::new(1, 1);
+    let mut end = Loc::new(line, last_line.chars().count() as u32 + 1).with_offset(chunk.len());
+    if let Some(path) = path {
+        start = start.set_source(path);
+        end = end.set_source(path);
+    }
+    Span::new(start, end)
+}
+
+/// Adds the report of the result of a statement to `lines`, the exit status
+/// it calls for when it is an error.
+fn emit(
+    lines: &mut Vec<String>,
+    span: &Span,
+    result: rscalc::Result<Value>,
+    format: Format,
+    origin: Origin,
+) -> Option<u8> {
+    let mut out = String::from("{\"span\":");
+    write_span(&mut out, span, origin);
+    let status = match result {
+        Ok(value) => {
+            out.push_str(",\"type\":");
+            write_str(&mut out, value.type_name());
+            out.push_str(",\"value\":");
+            write_value(&mut out, &value);
+            out.push_str(",\"text\":");
+            write_str(&mut out, &format.value(&value));
+            None
+        }
+        Err(err) => {
+            out.push_str(",\"error\":");
+            write_error(&mut out, &err, origin);
+            Some(cli::exit_code(&err))
+        }
+    };
+    out.push('}');
+    lines.push(out);
+    status
+}
+
+/// A JSON number where there is one, an object of the fields for structs,
+/// `null` for unit, complex numbers, infinities and NaN.
+fn write_value(out: &mut String, value: &Value) {
+    match *value {
+        Value::Int(i) => {
+            let _ = write!(out, "{i}");
+        }
+        // JSON numbers have no size limit, whether readers cope is up to them
+        Value::BigInt(ref i) => {
+            let _ = write!(out, "{i}");
+        }
+        // the nearest float, `text` has the fraction
+        Value::Ratio(_) => write_value(out, &Value::Float(value.as_f64().unwrap_or(f64::NAN))),
+        Value::Decimal(_) => {
+            let _ = write!(out, "{value}");
+        }
+        Value::Float(x) if x.is_finite() => {
+            let _ = write!(out, "{x:?}");
+        }
+        Value::Bool(b) => {
+            let _ = write!(out, "{b}");
+        }
+        Value::Struct(ref s) => {
+            out.push('{');
+            for (idx, (name, value)) in s.fields().enumerate() {
+                if idx > 0 {
+                    out.push(',');
+                }
+                write_str(out, name);
+                out.push(':');
+                write_value(out, value);
+            }
+            out.push('}');
+        }
+        Value::Float(_) | Value::Complex(_) | Value::Quantity(_) | Value::Unit => {
+            out.push_str("null")
+        }
+    }
+}
+
+fn write_error(out: &mut String, err: &Error, origin: Origin) {
+    let (stage, kind, message, loc, suggestions) = match err {
+        Error::LexingError(err) => (
+            "lex",
+            kind_name(err.kind()),
+            err.kind().to_string(),
+            Some(err.loc()),
+            &[][..],
+        ),
+        Error::ParsingError(err) => (
+            "parse",
+            kind_name(err.kind()),
+            err.kind().to_string(),
+            Some(err.loc()),
+            err.suggestions(),
+        ),
+        Error::EvalError(err) => (
+            "eval",
+            kind_name(err.kind()),
+            err.kind().to_string(),
+            Some(err.loc()),
+            err.suggestions(),
+        ),
+        Error::IoError(err) => ("io", kind_name(&err.kind()), err.to_string(), None, &[][..]),
+        Error::Any(msg) => ("other", "Any".to_owned(), msg.clone(), None, &[][..]),
+    };
+    out.push_str("{\"stage\":");
+    write_str(out, stage);
+    out.push_str(",\"kind\":");
+    write_str(out, &kind);
+    out.push_str(",\"message\":");
+    write_str(out, &message);
+    if let Some(loc) = loc {
+        out.push_str(",\"loc\":");
+        write_loc(out, loc, origin);
+    }
+    if !suggestions.is_empty() {
+        out.push_str(",\"suggestions\":[");
+        for (idx, name) in suggestions.iter().enumerate() {
+            if idx > 0 {
+                out.push(',');
+            }
+            write_str(out, name);
+        }
+        out.push(']');
+    }
+    out.push('}');
+}
+
+/// The variant name of an error kind, `UnexpectedCharacter` for
+/// `UnexpectedCharacter('$')`.
+fn kind_name(kind: &impl Debug) -> String {
+    let debug = format!("{kind:?}");
+    let end = debug
+        .find(|c: char| !c.is_alphanumeric())
+        .unwrap_or(debug.len());
+    debug[..end].to_owned()
+}
+
+fn write_span(out: &mut String, span: &Span, origin: Origin) {
+    out.push_str("{\"start\":");
+    write_loc(out, &span.start, origin);
+    out.push_str(",\"end\":");
+    write_loc(out, &span.end, origin);
+    out.push('}');
+}
+
+fn write_loc(out: &mut String, loc: &Loc, origin: Origin) {
+    out.push('{');
+    if let Some(source) = loc.source() {
+        out.push_str("\"source\":");
+        write_str(out, source);
+        out.push(',');
+    }
+    let _ = write!(
+        out,
+        "\"line\":{},\"col\":{},\"offset\":{}}}",
+        loc.line() + origin.line,
+        loc.col(),
+        loc.offset() + origin.offset
+    );
+}
+
+fn write_str(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    /// The reports of running `source` in a fresh session, with its status.
+    fn report(source: &str, path: Option<&str>) -> (Vec<String>, u8) {
+        let mut session = Session::new(&Config::default(), false).unwrap();
+        let mut out = Vec::new();
+        let mut status = 0;
+        for (origin, chunk) in chunks(source) {
+            if let Some(code) = run_chunk(&mut session, chunk, path, origin, &mut out) {
+                status = status.max(code);
+            }
+        }
+        (out, status)
+    }
+
+    fn json_str(s: &str) -> String {
+        let mut out = String::new();
+        write_str(&mut out, s);
+        out
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(json_str("plain"), r#""plain""#);
+        assert_eq!(json_str(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(json_str(r"a\b"), r#""a\\b""#);
+        assert_eq!(json_str("a\nb\tc"), r#""a\nb\tc""#);
+        assert_eq!(json_str("\u{1}\r\u{7f}"), r#""\u0001\u000d\u007f""#);
+        assert_eq!(json_str("∠ é"), "\"∠ é\"");
+    }
+
+    #[test]
+    fn values() {
+        let (out, status) = report(
+            "2 * 3 -> hex\n1 / 4\ntrue\nx := 1\nP :: struct { a; b } P(1, 2.5f64)",
+            None,
+        );
+        assert_eq!(status, 0);
+        assert!(
+            out[0].ends_with(r#""type":"int","value":6,"text":"0x6"}"#),
+            "{}",
+            out[0]
+        );
+        assert!(out[1].ends_with(r#""type":"decimal","value":0.25,"text":"0.25"}"#));
+        assert!(out[2].ends_with(r#""type":"bool","value":true,"text":"true"}"#));
+        assert!(out[3].ends_with(r#""type":"unit","value":null,"text":"()"}"#));
+        assert!(out[5].ends_with(r#""value":{"a":1,"b":2.5},"text":"P { a: 1, b: 2.5 }"}"#));
+        let (out, _) = report("1e999f64; 1 + 2i", None);
+        assert!(out[0].contains(r#""value":null"#) && out[1].contains(r#""value":null"#));
+    }
+
+    #[test]
+    fn chunks_span_lines() {
+        let source = "x := 1\nf :: (a) {\n  a * 2\n}\ny := f(\n  x)\n";
+        let chunks: Vec<_> = chunks(source)
+            .into_iter()
+            .map(|(origin, chunk)| (origin.line, origin.offset, chunk))
+            .collect();
+        assert_eq!(
+            chunks,
+            [
+                (0, 0, "x := 1\n"),
+                (1, 7, "f :: (a) {\n  a * 2\n}\n"),
+                (4, 28, "y := f(\n  x)\n"),
+            ]
+        );
+        // an unfinished statement at the end is a chunk of its own
+        assert_eq!(chunks_of("1\n2 +\n"), ["1\n", "2 +\n"]);
+    }
+
+    fn chunks_of(source: &str) -> Vec<&str> {
+        chunks(source).into_iter().map(|(_, chunk)| chunk).collect()
+    }
+
+    #[test]
+    fn locations_after_the_first_chunk() {
+        let source = "x := 1\ny :=\n  2\nx / 0; 4\n(y +\n  *)\n";
+        let (out, status) = report(source, Some("in.rsc"));
+        assert_eq!(status, 2);
+        // the multi-line declaration spans the lines of the source
+        assert!(out[1].starts_with(
+            r#"{"span":{"start":{"source":"in.rsc","line":2,"col":1,"offset":7},"end":{"source":"in.rsc","line":3,"col":4,"offset":15}}"#
+        ), "{}", out[1]);
+        assert!(out[2].contains(
+            r#""error":{"stage":"eval","kind":"DivisionByZero","message":"division by zero","loc":{"source":"in.rsc","line":4,"col":1,"offset":16}}"#
+        ), "{}", out[2]);
+        // the rest of the line with the error is skipped
+        assert_eq!(out.len(), 4);
+        assert!(out[3].contains(r#""stage":"parse""#), "{}", out[3]);
+        assert!(
+            out[3].contains(r#""loc":{"source":"in.rsc","line":6,"col":3,"offset":32}"#),
+            "{}",
+            out[3]
+        );
+    }
+
+    #[test]
+    fn suggestions() {
+        let (out, status) = report("count := 1\ncuont + 1\n", None);
+        assert_eq!(status, 1);
+        assert!(out[1].contains(r#""kind":"UndefinedVariable""#));
+        assert!(
+            out[1].ends_with(r#""suggestions":["count"]}}"#),
+            "{}",
+            out[1]
+        );
+    }
+}
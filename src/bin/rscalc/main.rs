@@ -0,0 +1,94 @@
+//! The `rscalc` command line calculator.
+
+mod cli;
+mod commands;
+mod config;
+mod filter;
+mod helper;
+mod json;
+mod repl;
+
+use std::io::IsTerminal;
+use std::process::ExitCode;
+
+use cli::{Args, Command, Session};
+use config::Config;
+use repl::Repl;
+
+fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1), std::io::stdin().is_terminal()) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("rscalc: {err}\n\n{}", cli::USAGE);
+            return ExitCode::from(cli::EXIT_USAGE);
+        }
+    };
+    match args.command {
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Command::Version => {
+            println!("rscalc {}", env!("CARGO_PKG_VERSION"));
+            return ExitCode::SUCCESS;
+        }
+        _ => {}
+    }
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("rscalc: {err}");
+            return ExitCode::from(cli::EXIT_CONFIG);
+        }
+    };
+    let mut session = match Session::new(&config, args.prelude) {
+        Ok(session) => session,
+        Err(code) => return ExitCode::from(code),
+    };
+    match args.command {
+        Command::Repl => match Repl::new(&config, session) {
+            Ok(mut repl) => {
+                repl.run();
+                ExitCode::SUCCESS
+            }
+            Err(err) => {
+                eprintln!("rscalc: {err}");
+                ExitCode::FAILURE
+            }
+        },
+        Command::Help | Command::Version => unreachable!("handled before the config"),
+        Command::Eval(expr) => run(&mut session, &expr, None, args.json),
+        Command::Stdin => match cli::read_stdin() {
+            Ok(source) => run(&mut session, &source, None, args.json),
+            Err(err) => io_error("<stdin>", err),
+        },
+        Command::Run(path) => match std::fs::read_to_string(&path) {
+            Ok(source) => run(&mut session, &source, Some(&path), args.json),
+            Err(err) => io_error(&path, err),
+        },
+        Command::Filter { program, file } => {
+            let name = file.as_deref().unwrap_or("<stdin>");
+            let input = match &file {
+                Some(path) => std::fs::read_to_string(path),
+                None => cli::read_stdin(),
+            };
+            match input {
+                Ok(input) => filter::run(&mut session, &program, &input, name),
+                Err(err) => io_error(name, err),
+            }
+        }
+    }
+}
+
+fn run(session: &mut Session, source: &str, path: Option<&str>, json: bool) -> ExitCode {
+    if json {
+        json::run(session, source, path)
+    } else {
+        session.run(source, path)
+    }
+}
+
+fn io_error(path: &str, err: std::io::Error) -> ExitCode {
+    eprintln!("rscalc: {path}: {err}");
+    ExitCode::from(cli::exit_code(&err.into()))
+}
@@ -0,0 +1,186 @@
+use std::path::PathBuf;
+
+use rscalc::{closest_words, Context, FORMAT_HELP, PRECISION_HELP};
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::{CompletionType, Editor};
+
+use crate::cli::{self, Session};
+use crate::commands::{self, COMMANDS};
+use crate::config::Config;
+use crate::helper::Helper;
+
+const CONTINUATION_PROMPT: &str = ".. ";
+
+/// An interactive session, bindings made by one input stay visible to the
+/// following ones.
+pub struct Repl {
+    editor: Editor<Helper, DefaultHistory>,
+    session: Session,
+    /// What `:clear` goes back to, the context right after the prelude.
+    initial: Context,
+    prompt: String,
+    history: Option<PathBuf>,
+}
+
+impl Repl {
+    pub fn new(config: &Config, session: Session) -> rustyline::Result<Self> {
+        let editor_config = rustyline::Config::builder()
+            .completion_type(CompletionType::List)
+            .max_history_size(config.history_size)?
+            .build();
+        let mut editor = Editor::with_config(editor_config)?;
+        editor.set_helper(Some(Helper {
+            ctx: session.ctx.clone(),
+        }));
+        let history = history_path();
+        if let Some(path) = &history {
+            // there is no history yet on the first run
+            let _ = editor.load_history(path);
+        }
+        Ok(Self {
+            editor,
+            initial: session.ctx.clone(),
+            session,
+            prompt: config.prompt.clone(),
+            history,
+        })
+    }
+
+    /// Reads and evaluates inputs until end of file.
+    pub fn run(&mut self) {
+        while let Some(input) = self.read_input() {
+            if input.trim().is_empty() {
+                continue;
+            }
+            let _ = self.editor.add_history_entry(input.as_str());
+            match input.trim_start().strip_prefix(':') {
+                Some(command) => {
+                    if !self.command(command) {
+                        break;
+                    }
+                }
+                None => self.eval(&input),
+            }
+            if let Some(helper) = self.editor.helper_mut() {
+                helper.ctx = self.session.ctx.clone();
+            }
+        }
+        self.save_history();
+    }
+
+    /// Reads lines until they form a complete input. `None` on end of file,
+    /// an interrupt drops what was typed so far.
+    fn read_input(&mut self) -> Option<String> {
+        let mut input = String::new();
+        loop {
+            let prompt = if input.is_empty() {
+                &self.prompt
+            } else {
+                CONTINUATION_PROMPT
+            };
+            match self.editor.readline(prompt) {
+                Ok(line) => {
+                    let blank = line.trim().is_empty();
+                    if !input.is_empty() {
+                        input.push('\n');
+                    }
+                    input.push_str(&line);
+                    // a blank line forces evaluation of an unfinished input,
+                    // meta commands are a single line
+                    if blank || input.starts_with(':') || !cli::is_incomplete(&input) {
+                        return Some(input);
+                    }
+                }
+                Err(ReadlineError::Interrupted) => input.clear(),
+                Err(ReadlineError::Eof) if !input.is_empty() => return Some(input),
+                Err(ReadlineError::Eof) => return None,
+                Err(err) => {
+                    eprintln!("rscalc: {err}");
+                    return None;
+                }
+            }
+        }
+    }
+
+    fn eval(&mut self, input: &str) {
+        let Session { ctx, format, color } = &mut self.session;
+        match cli::exec(input, None, ctx, *format) {
+            Ok(value) => value.into_iter().for_each(|value| println!("{value}")),
+            Err(err) => eprint!("{}", err.render(input, *color)),
+        }
+    }
+
+    /// Runs a meta command, `false` when it asks to quit.
+    fn command(&mut self, line: &str) -> bool {
+        let (name, arg) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let arg = arg.trim();
+        let session = &mut self.session;
+        match name {
+            "tokens" => commands::tokens(arg, session.color),
+            "ast" => commands::ast(arg, session.color),
+            "type" => commands::type_of(session, arg),
+            "vars" => commands::vars(&session.ctx),
+            "funcs" => commands::funcs(&session.ctx),
+            "clear" => session.reset(&self.initial),
+            "load" | "save" | "restore" if arg.is_empty() => {
+                eprintln!("rscalc: `:{name}` expects a file")
+            }
+            "load" => commands::load(session, arg),
+            "save" => commands::save(&session.ctx, arg),
+            "restore" => {
+                session.reset(&self.initial);
+                commands::load(session, arg);
+            }
+            "format" if arg.is_empty() => println!("{}", session.format),
+            "format" => match arg.parse() {
+                Ok(format) => session.format = format,
+                Err(word) => eprintln!("rscalc: unknown format `{word}`, {FORMAT_HELP}"),
+            },
+            "mode" if arg.is_empty() => println!("{}", session.ctx.mode().name()),
+            "mode" => match arg.parse() {
+                Ok(mode) => session.ctx.set_mode(mode),
+                Err(word) => {
+                    eprintln!("rscalc: unknown mode `{word}`, expected float, exact or decimal")
+                }
+            },
+            "decimal" if arg.is_empty() => println!("{}", session.ctx.precision()),
+            "decimal" => match arg.parse() {
+                Ok(precision) => session.ctx.set_precision(precision),
+                Err(word) => eprintln!("rscalc: invalid precision `{word}`, {PRECISION_HELP}"),
+            },
+            "help" => commands::help(),
+            "quit" | "q" => return false,
+            _ => {
+                let names: Vec<_> = COMMANDS.iter().map(|meta| meta.name).collect();
+                eprint!("rscalc: unknown command `:{name}`");
+                match closest_words(name, &names).first() {
+                    Some(close) => eprintln!(", did you mean `:{close}`?"),
+                    None => eprintln!(", see `:help`"),
+                }
+            }
+        }
+        true
+    }
+
+    fn save_history(&mut self) {
+        let Some(path) = &self.history else {
+            return;
+        };
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        if let Err(err) = self.editor.save_history(path) {
+            eprintln!(
+                "rscalc: could not save history to {}: {err}",
+                path.display()
+            );
+        }
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    dirs::data_dir()
+        .map(|dir| dir.join("rscalc").join("history"))
+        .or_else(|| dirs::home_dir().map(|dir| dir.join(".rscalc_history")))
+}
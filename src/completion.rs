@@ -0,0 +1,208 @@
+use crate::eval::{context::Context, value::Value};
+use crate::lev::closest_words;
+use crate::lexer::{
+    token::{Token, KEYWORDS},
+    Lexer,
+};
+
+/// Completions for the word at byte offset `pos` of `line`, along with the
+/// offset the word starts at. The candidates replace `line[start..pos]`.
+///
+/// Words are completed to keywords, variables in scope and functions, or to
+/// struct fields after a `.`, names starting with the word first and else
+/// the ones that look like it.
+pub fn complete(line: &str, pos: usize, ctx: &Context) -> (usize, Vec<String>) {
+    let Some(before) = line.get(..pos) else {
+        return (pos, Vec::new());
+    };
+    // the token ending at the cursor is the word to complete, the ones before
+    // it tell what kind of name can go there
+    let mut seen = Vec::new();
+    let mut word = None;
+    let tokens = Lexer::new(line).emit_comments(true).recover(true);
+    for token in tokens.flatten() {
+        let range = token.span.range();
+        if range.start >= pos {
+            break;
+        }
+        if range.end >= pos {
+            word = Some((token.value, range.start));
+            break;
+        }
+        seen.push(token.value);
+    }
+    let (start, prefix) = match word {
+        Some((Token::Ident(_), start)) => (start, &before[start..]),
+        Some((_, start)) if KEYWORDS.contains(&&before[start..]) => (start, &before[start..]),
+        Some((
+            Token::Int(_)
+            | Token::Float(_)
+            | Token::Decimal(..)
+            | Token::Imag(_)
+            | Token::Str(_)
+            | Token::Comment(_)
+            | Token::DocComment(_)
+            | Token::Illegal,
+            _,
+        )) => return (pos, Vec::new()),
+        // a new name starts right after an operator
+        Some((token, _)) => {
+            seen.push(token);
+            (pos, "")
+        }
+        None => (pos, ""),
+    };
+    let mut names: Vec<&str> = match seen.last() {
+        Some(Token::Dot) => fields(&seen, ctx),
+        _ => {
+            let mut names = KEYWORDS.to_vec();
+            names.extend(ctx.var_names());
+            names.extend(ctx.fn_names());
+            names
+        }
+    };
+    names.sort_unstable();
+    names.dedup();
+    let matches: Vec<String> = names
+        .iter()
+        .filter(|name| name.starts_with(prefix))
+        .map(|name| name.to_string())
+        .collect();
+    if !matches.is_empty() || prefix.is_empty() {
+        return (start, matches);
+    }
+    (start, closest_words(prefix, &names))
+}
+
+/// The fields that can follow `tokens`, which end with a `.`. Those of the
+/// value of a path like `a.b.` when it is a struct, or else of every struct
+/// when the value is not known without evaluating.
+fn fields<'c>(tokens: &[Token<'_>], ctx: &'c Context) -> Vec<&'c str> {
+    let mut path = Vec::new();
+    let mut rest = &tokens[..tokens.len() - 1];
+    while let [head @ .., Token::Ident(name)] = rest {
+        path.push(name.as_ref());
+        match head {
+            [head @ .., Token::Dot] => rest = head,
+            _ => break,
+        }
+    }
+    let mut path = path.into_iter().rev();
+    let value = path
+        .next()
+        .and_then(|var| ctx.get_var(var))
+        .and_then(|var| {
+            path.try_fold(var, |value, name| match value {
+                Value::Struct(s) => s.field(name),
+                _ => None,
+            })
+        });
+    match value {
+        Some(Value::Struct(s)) => s.fields().map(|(name, _)| name).collect(),
+        Some(_) => Vec::new(),
+        None => ctx
+            .structs()
+            .flat_map(|decl| decl.fields.iter().map(|field| field.name.as_ref()))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx() -> Context {
+        let mut ctx = Context::new();
+        ctx.set_const("CONSTANT_VAR", 1);
+        ctx.set_var("count", 2);
+        ctx.set_var("counter", 3);
+        ctx
+    }
+
+    #[test]
+    fn prefixes() {
+        let ctx = ctx();
+        assert_eq!(
+            complete("1 + CONS", 8, &ctx),
+            (4, vec!["CONSTANT_VAR".to_owned()])
+        );
+        assert_eq!(
+            complete("coun", 4, &ctx),
+            (0, vec!["count".to_owned(), "counter".to_owned()])
+        );
+        assert_eq!(complete("sq", 2, &ctx), (0, vec!["sqrt".to_owned()]));
+        assert_eq!(complete("whi", 3, &ctx), (0, vec!["while".to_owned()]));
+        // only what is left of the cursor counts
+        assert_eq!(
+            complete("sqrt(cos)", 8, &ctx),
+            (5, vec!["cos".to_owned(), "cosh".to_owned()])
+        );
+        assert_eq!(
+            complete("sqrt(cos)", 7, &ctx),
+            (
+                5,
+                vec![
+                    "conj".to_owned(),
+                    "continue".to_owned(),
+                    "cos".to_owned(),
+                    "cosh".to_owned(),
+                    "count".to_owned(),
+                    "counter".to_owned()
+                ]
+            )
+        );
+        assert_eq!(complete("for", 3, &ctx), (0, vec!["for".to_owned()]));
+    }
+
+    #[test]
+    fn fuzzy() {
+        let ctx = ctx();
+        assert_eq!(
+            complete("cuont", 5, &ctx),
+            (0, vec!["conj".to_owned(), "count".to_owned()])
+        );
+        assert_eq!(complete("zzzz", 4, &ctx), (0, Vec::<String>::new()));
+    }
+
+    #[test]
+    fn contexts() {
+        let ctx = ctx();
+        let all = complete("1 + ", 4, &ctx);
+        assert_eq!(all.0, 4);
+        assert!(all.1.contains(&"count".to_owned()) && all.1.contains(&"while".to_owned()));
+        assert_eq!(complete("12", 2, &ctx), (2, Vec::<String>::new()));
+        assert_eq!(complete("x.co", 4, &ctx), (2, Vec::<String>::new()));
+        assert_eq!(complete("'co", 3, &ctx), (3, Vec::<String>::new()));
+        assert_eq!(complete("1 # co", 6, &ctx), (6, Vec::<String>::new()));
+        let after_op = complete("2*", 2, &ctx);
+        assert_eq!(after_op.0, 2);
+        assert!(after_op.1.contains(&"CONSTANT_VAR".to_owned()));
+        assert_eq!(complete("co", 7, &ctx), (7, Vec::<String>::new()));
+    }
+
+    #[test]
+    fn fields() {
+        let mut ctx = ctx();
+        crate::eval_with(
+            "Point :: struct { x; y } Line :: struct { start; end; width }
+            line := Line(Point(0, 0), Point(1, 2), 1)",
+            &mut ctx,
+        )
+        .unwrap();
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        assert_eq!(
+            complete("line.", 5, &ctx),
+            (5, names(&["end", "start", "width"]))
+        );
+        assert_eq!(complete("2 * line.st", 11, &ctx), (9, names(&["start"])));
+        assert_eq!(complete("line.end.", 9, &ctx), (9, names(&["x", "y"])));
+        assert_eq!(complete("line.widht", 10, &ctx), (5, names(&["width"])));
+        // no fields on numbers, the fields of all structs when it takes a call
+        assert_eq!(complete("count.", 6, &ctx), (6, Vec::new()));
+        assert_eq!(complete("line.width.", 11, &ctx), (11, Vec::new()));
+        assert_eq!(
+            complete("Point(1, 2).", 12, &ctx),
+            (12, names(&["end", "start", "width", "x", "y"]))
+        );
+    }
+}
@@ -0,0 +1,428 @@
+//! Rustc style rendering of errors along with the offending source lines.
+//!
+//! ```text
+//! error: unexpected token ')', expected expression
+//!  --> script.rsc:2:9
+//!   |
+//! 2 |     1 + )
+//!   |         ^ expected expression
+//! ```
+
+use derive_more::Display;
+use std::fmt::Write;
+
+use crate::error::Error;
+use crate::eval::error::{EvalError, EvalErrorKind};
+use crate::eval::{decimal::MAX_EXPONENT, value::MAX_INT_BITS};
+use crate::format::FORMAT_HELP;
+use crate::lexer::error::{LexError, LexErrorKind};
+use crate::location::{Loc, Span};
+use crate::parser::error::{ParseError, ParseErrorKind};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const GUTTER: &str = "\x1b[1;34m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum Severity {
+    #[display(fmt = "error")]
+    Error,
+    #[display(fmt = "warning")]
+    Warning,
+    #[display(fmt = "note")]
+    Note,
+    #[display(fmt = "help")]
+    Help,
+}
+
+impl Severity {
+    const fn color(self) -> &'static str {
+        match self {
+            Self::Error => "\x1b[1;31m",
+            Self::Warning => "\x1b[1;33m",
+            Self::Note => "\x1b[1;32m",
+            Self::Help => "\x1b[1;36m",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label<'a> {
+    pub span: Span<'a>,
+    pub message: Option<String>,
+    /// Primary labels are underlined with `^`, secondary ones with `-`.
+    pub primary: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic<'a> {
+    pub severity: Severity,
+    pub message: String,
+    pub labels: Vec<Label<'a>>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+}
+
+impl<'a> Diagnostic<'a> {
+    pub fn new(severity: Severity, message: impl ToString) -> Self {
+        Self {
+            severity,
+            message: message.to_string(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn error(message: impl ToString) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    #[inline]
+    pub fn warning(message: impl ToString) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn with_label(mut self, span: Span<'a>, message: Option<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message,
+            primary: true,
+        });
+        self
+    }
+
+    pub fn with_secondary_label(mut self, span: Span<'a>, message: Option<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message,
+            primary: false,
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl ToString) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl ToString) -> Self {
+        self.help.push(help.to_string());
+        self
+    }
+
+    /// Adds a "did you mean" help listing `names`, if there are any.
+    pub fn with_suggestions(self, names: &[String]) -> Self {
+        let quoted: Vec<_> = names.iter().map(|name| format!("`{name}`")).collect();
+        match quoted.as_slice() {
+            [] => self,
+            [name] => self.with_help(format!("did you mean {name}?")),
+            [names @ .., last] => {
+                self.with_help(format!("did you mean {} or {last}?", names.join(", ")))
+            }
+        }
+    }
+
+    /// Renders the diagnostic against `source`, the text the spans point
+    /// into. `color` wraps the output in ANSI escape codes.
+    pub fn render(&self, source: &str, color: bool) -> String {
+        let paint = |code: &'static str| if color { code } else { "" };
+        let reset = paint(RESET);
+        let gutter_color = paint(GUTTER);
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{}{}{reset}{}: {}{reset}",
+            paint(self.severity.color()),
+            self.severity,
+            paint(BOLD),
+            self.message
+        );
+
+        let mut labels: Vec<_> = self
+            .labels
+            .iter()
+            .filter(|label| label.span.start.line() > 0)
+            .collect();
+        labels.sort_by_key(|label| label.span.start.offset());
+
+        let width = labels
+            .iter()
+            .map(|label| label.span.start.line().to_string().len())
+            .max()
+            .unwrap_or(0);
+        let blank = " ".repeat(width);
+
+        let primary = labels.iter().find(|label| label.primary).or(labels.first());
+        if let Some(primary) = primary {
+            let _ = writeln!(
+                out,
+                "{blank}{gutter_color}-->{reset} {}",
+                primary.span.start
+            );
+            let _ = writeln!(out, "{blank} {gutter_color}|{reset}");
+        }
+
+        let mut last_line = None;
+        for label in &labels {
+            let (line, col, len) = snippet(source, &label.span);
+            let line_no = label.span.start.line();
+            if last_line != Some(line_no) {
+                let _ = writeln!(
+                    out,
+                    "{gutter_color}{line_no:>width$} |{reset} {}",
+                    line.replace('\t', " ")
+                );
+                last_line = Some(line_no);
+            }
+            let (mark, mark_color) = if label.primary {
+                ('^', paint(self.severity.color()))
+            } else {
+                ('-', gutter_color)
+            };
+            let _ = write!(
+                out,
+                "{blank} {gutter_color}|{reset} {}{mark_color}{}",
+                " ".repeat(col),
+                mark.to_string().repeat(len)
+            );
+            if let Some(message) = &label.message {
+                let _ = write!(out, " {message}");
+            }
+            let _ = writeln!(out, "{reset}");
+        }
+
+        let has_footer = !self.notes.is_empty() || !self.help.is_empty();
+        if !labels.is_empty() && has_footer {
+            let _ = writeln!(out, "{blank} {gutter_color}|{reset}");
+        }
+        for (kind, msgs) in [(Severity::Note, &self.notes), (Severity::Help, &self.help)] {
+            for msg in msgs {
+                let _ = writeln!(
+                    out,
+                    "{blank} {gutter_color}={reset} {}{kind}{reset}: {msg}",
+                    paint(BOLD)
+                );
+            }
+        }
+        out
+    }
+}
+
+/// Returns the source line the span starts on, the char column the span
+/// starts at and how many chars of that line it covers (at least one).
+fn snippet<'s>(source: &'s str, span: &Span<'_>) -> (&'s str, usize, usize) {
+    let start = floor_char_boundary(source, span.start.offset());
+    let end = floor_char_boundary(source, span.end.offset().max(start));
+
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |i| start + i);
+    let line = source[line_start..line_end].trim_end_matches('\r');
+
+    let col = source[line_start..start].chars().count();
+    let len = source[start..end.min(line_end)].chars().count().max(1);
+    (line, col, len)
+}
+
+fn floor_char_boundary(source: &str, mut idx: usize) -> usize {
+    idx = idx.min(source.len());
+    while !source.is_char_boundary(idx) {
+        idx -= 1;
+    }
+    idx
+}
+
+/// A lexer error only knows where it happened, it covers a single char.
+fn point_span<'a>(loc: &Loc<'a>) -> Span<'a> {
+    let mut end = loc.clone();
+    end.inc_col();
+    end.inc_offset(1);
+    Span::new(loc.clone(), end)
+}
+
+impl<'a> From<&LexError<'a>> for Diagnostic<'a> {
+    fn from(err: &LexError<'a>) -> Self {
+        let diag = Diagnostic::error(err.kind()).with_label(point_span(err.loc()), None);
+        match err.kind() {
+            LexErrorKind::EscapeUnicodeStart
+            | LexErrorKind::EscapeUnicodeEnd
+            | LexErrorKind::EscapeUnicodeInvalid => {
+                diag.with_help("unicode escapes are written as `\\u{XXXX}`, up to 10FFFF")
+            }
+            LexErrorKind::EscapeDecimalTooLarge => {
+                diag.with_help("use `\\xNN` or `\\u{XXXX}` for characters above 255")
+            }
+            _ => diag,
+        }
+    }
+}
+
+impl<'a> From<&ParseError<'a>> for Diagnostic<'a> {
+    fn from(err: &ParseError<'a>) -> Self {
+        let label = match err.kind() {
+            ParseErrorKind::UnexpectedToken { expected, .. }
+            | ParseErrorKind::UnexpectedEof(expected) => Some(format!("expected {expected}")),
+            ParseErrorKind::InvalidAssignTarget => Some("cannot be assigned to".to_owned()),
+            ParseErrorKind::DuplicateField(_) => Some("declared again here".to_owned()),
+            ParseErrorKind::Any(_) | ParseErrorKind::UnknownFormat(_) => None,
+        };
+        let diag = Diagnostic::error(err.kind())
+            .with_label(err.span().clone(), label)
+            .with_suggestions(err.suggestions());
+        match err.kind() {
+            ParseErrorKind::UnknownFormat(_) => diag.with_help(FORMAT_HELP),
+            _ => diag,
+        }
+    }
+}
+
+impl<'a> From<&EvalError<'a>> for Diagnostic<'a> {
+    fn from(err: &EvalError<'a>) -> Self {
+        let diag = Diagnostic::error(err.kind())
+            .with_label(err.span().clone(), None)
+            .with_suggestions(err.suggestions());
+        match err.kind() {
+            EvalErrorKind::IntegerOverflow => diag.with_note(format!(
+                "integers are limited to {MAX_INT_BITS} bits, or to the range of their type"
+            )),
+            EvalErrorKind::DecimalOverflow => diag.with_note(format!(
+                "decimals are limited to powers of ten up to {MAX_EXPONENT} either way"
+            )),
+            EvalErrorKind::InvalidShift(_) => diag.with_note("shift amounts cannot be negative"),
+            EvalErrorKind::IncompatibleUnits { .. } => {
+                diag.with_note("only quantities of the same dimension add up or compare")
+            }
+            EvalErrorKind::UnitPower(_) => diag.with_note("units only have whole powers up to 127"),
+            EvalErrorKind::IntegerFormat(_) => {
+                diag.with_help("`hex` writes fractions as hexadecimal floats")
+            }
+            EvalErrorKind::AmbiguousUnit(name) => diag.with_help(format!(
+                "write `* {name}` to multiply by the variable, or rename the variable"
+            )),
+            _ => diag,
+        }
+    }
+}
+
+impl<'a> From<&Error<'a>> for Diagnostic<'a> {
+    fn from(err: &Error<'a>) -> Self {
+        match err {
+            Error::LexingError(err) => err.into(),
+            Error::ParsingError(err) => err.as_ref().into(),
+            Error::EvalError(err) => err.as_ref().into(),
+            err @ (Error::Any(_) | Error::IoError(_)) => Diagnostic::error(err),
+        }
+    }
+}
+
+impl<'a> Error<'a> {
+    /// Renders the error with the source snippet it points to, see
+    /// [`Diagnostic::render`].
+    pub fn render(&self, source: &str, color: bool) -> String {
+        Diagnostic::from(self).render(source, color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{eval, Lexer, Parser};
+
+    #[test]
+    fn parse_error() {
+        let source = "1 +\n    1 + )";
+        let err = Parser::new(Lexer::new(source).set_source_path("script.rsc"))
+            .and_then(|mut p| p.parse_expression())
+            .unwrap_err();
+        assert_eq!(
+            err.render(source, false),
+            "\
+error: unexpected token ')', expected expression
+ --> script.rsc:2:9
+  |
+2 |     1 + )
+  |         ^ expected expression
+"
+        );
+    }
+
+    #[test]
+    fn eval_error() {
+        let source = "2 * (1 << -70)";
+        let err = eval(source).unwrap_err();
+        assert_eq!(
+            err.render(source, false),
+            "\
+error: invalid shift amount -70
+ --> 1:5
+  |
+1 | 2 * (1 << -70)
+  |     ^^^^^^^^^^
+  |
+  = note: shift amounts cannot be negative
+"
+        );
+    }
+
+    #[test]
+    fn suggestions() {
+        let source = "tan(pie / 4)";
+        let err = eval(source).unwrap_err();
+        assert!(err
+            .render(source, false)
+            .ends_with("  = help: did you mean `pi`?\n"));
+
+        let diag =
+            Diagnostic::error("oops").with_suggestions(&["a".into(), "b".into(), "c".into()]);
+        assert_eq!(diag.help, ["did you mean `a`, `b` or `c`?"]);
+        assert!(Diagnostic::error("oops")
+            .with_suggestions(&[])
+            .help
+            .is_empty());
+    }
+
+    #[test]
+    fn lex_error() {
+        let source = "1 + $";
+        let err = eval(source).unwrap_err();
+        let rendered = err.render(source, false);
+        assert!(rendered.starts_with("error: unexpected character: $\n --> 1:5\n"));
+        assert!(rendered.ends_with("1 | 1 + $\n  |     ^\n"));
+    }
+
+    #[test]
+    fn labels_and_help() {
+        let source = "a := 1;\nb := a ++ 2;";
+        let loc = |line, col, offset| Loc::new(line, col).with_offset(offset);
+        let diag = Diagnostic::warning("suspicious increment")
+            .with_secondary_label(
+                Span::new(loc(1, 1, 0), loc(1, 2, 1)),
+                Some("declared here".into()),
+            )
+            .with_label(
+                Span::new(loc(2, 8, 15), loc(2, 10, 17)),
+                Some("did you mean `+`?".into()),
+            )
+            .with_help("there is no increment operator");
+        assert_eq!(
+            diag.render(source, false),
+            "\
+warning: suspicious increment
+ --> 2:8
+  |
+1 | a := 1;
+  | - declared here
+2 | b := a ++ 2;
+  |        ^^ did you mean `+`?
+  |
+  = help: there is no increment operator
+"
+        );
+        let colored = diag.render(source, true);
+        assert!(colored.contains("\x1b[1;33mwarning\x1b[0m"));
+    }
+}
@@ -2,16 +2,20 @@ use std::result::Result as StdResult;
 
 use thiserror::Error;
 
-use crate::lexer::error::LexError;
+use crate::eval::error::EvalError;
+use crate::lexer::error::{LexError, LexErrorKind};
+use crate::parser::error::{ParseError, ParseErrorKind};
 
 #[derive(Debug, Error)]
 pub enum Error<'a> {
     #[error("{0}")]
     Any(String),
     #[error("{0} - ParsingError")]
-    ParsingError(String),
+    ParsingError(Box<ParseError<'a>>),
     #[error("{0} - LexingError")]
     LexingError(LexError<'a>),
+    #[error("{0} - EvalError")]
+    EvalError(Box<EvalError<'a>>),
 
     #[error("IoError - {0}")]
     IoError(#[from] std::io::Error),
@@ -21,12 +25,28 @@ impl<'err> Error<'err> {
     pub fn any(s: impl ToString) -> Self {
         Self::Any(s.to_string())
     }
-    pub fn parsing_error(s: impl ToString) -> Self {
-        Self::ParsingError(s.to_string())
+    pub fn parsing_error(s: impl Into<ParseError<'err>>) -> Self {
+        Self::ParsingError(Box::new(s.into()))
     }
     pub fn lexing_error(s: impl Into<LexError<'err>>) -> Self {
         Self::LexingError(s.into())
     }
+    pub fn eval_error(s: impl Into<EvalError<'err>>) -> Self {
+        Self::EvalError(Box::new(s.into()))
+    }
+
+    /// Whether the input ended in the middle of something, so that reading
+    /// more of it may fix the error.
+    pub fn is_incomplete(&self) -> bool {
+        match self {
+            Self::ParsingError(err) => matches!(err.kind(), ParseErrorKind::UnexpectedEof(_)),
+            Self::LexingError(err) => matches!(
+                err.kind(),
+                LexErrorKind::UnfinishedLongString | LexErrorKind::UnfinishedBlockComment
+            ),
+            _ => false,
+        }
+    }
 }
 
 pub type Result<'err, T> = StdResult<T, Error<'err>>;
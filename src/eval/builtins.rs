@@ -0,0 +1,328 @@
+use std::f64::consts;
+
+use bigdecimal::BigDecimal;
+use num_bigint::BigInt;
+use num_complex::Complex64;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, One, Signed, ToPrimitive, Zero};
+
+use super::context::{AngleUnit, Builtin, Mode};
+use super::decimal::{self, Precision, Rounding};
+use super::error::EvalErrorKind;
+use super::value::Value;
+use crate::parser::ast::BinOp;
+
+type FnResult = std::result::Result<Value, EvalErrorKind>;
+type DecimalResult = std::result::Result<BigDecimal, EvalErrorKind>;
+
+/// `pi`, `tau` and `e`, as decimals in the decimal mode.
+pub(crate) fn constants(mode: Mode, precision: Precision) -> [(&'static str, Value); 3] {
+    match mode {
+        Mode::Decimal => [
+            ("pi", decimal::pi_times(1, precision)),
+            ("tau", decimal::pi_times(2, precision)),
+            ("e", decimal::exp(&BigDecimal::one(), precision)),
+        ]
+        .map(|(name, x)| (name, Value::from(x.unwrap_or_default()))),
+        _ => [("pi", consts::PI), ("tau", consts::TAU), ("e", consts::E)]
+            .map(|(name, x)| (name, Value::Float(x))),
+    }
+}
+
+macro_rules! float_fn {
+    ($($name:literal => $f:expr $(, $decimal:expr)?);* $(;)?) => {
+        &[$(Builtin::new($name, 1, Some(1), |args| {
+            let x = float_arg(&args[0])?;
+            Ok(Value::Float(($f)(x)))
+        })$(.with_decimal(|args, precision| {
+            let x = args[0].to_decimal(precision)?;
+            ($decimal)(&x, precision).map(Value::from)
+        }))?,)*]
+    };
+}
+
+const FLOAT_FNS: &[Builtin] = float_fn! {
+    "cbrt" => f64::cbrt;
+    "exp" => f64::exp, decimal::exp;
+    "ln" => f64::ln, decimal::ln;
+    "log2" => f64::log2;
+    "log10" => f64::log10;
+    "sinh" => f64::sinh;
+    "cosh" => f64::cosh;
+    "tanh" => f64::tanh;
+};
+
+/// Trigonometric functions taking and giving angles in a unit, converted
+/// from and to radians by the two functions.
+macro_rules! trig_fns {
+    ($unit:expr, $to_rad:expr, $from_rad:expr) => {
+        &[
+            Builtin::new("sin", 1, Some(1), |args| {
+                Ok(Value::Float(($to_rad)(float_arg(&args[0])?).sin()))
+            })
+            .with_decimal(|args, precision| decimal_trig(args, precision, $unit, decimal::sin)),
+            Builtin::new("cos", 1, Some(1), |args| {
+                Ok(Value::Float(($to_rad)(float_arg(&args[0])?).cos()))
+            })
+            .with_decimal(|args, precision| decimal_trig(args, precision, $unit, decimal::cos)),
+            Builtin::new("tan", 1, Some(1), |args| {
+                Ok(Value::Float(($to_rad)(float_arg(&args[0])?).tan()))
+            })
+            .with_decimal(|args, precision| decimal_trig(args, precision, $unit, decimal::tan)),
+            Builtin::new("asin", 1, Some(1), |args| {
+                Ok(Value::Float(($from_rad)(float_arg(&args[0])?.asin())))
+            }),
+            Builtin::new("acos", 1, Some(1), |args| {
+                Ok(Value::Float(($from_rad)(float_arg(&args[0])?.acos())))
+            }),
+            Builtin::new("atan", 1, Some(1), |args| {
+                Ok(Value::Float(($from_rad)(float_arg(&args[0])?.atan())))
+            }),
+            Builtin::new("atan2", 2, Some(2), |args| {
+                let (y, x) = (float_arg(&args[0])?, float_arg(&args[1])?);
+                Ok(Value::Float(($from_rad)(y.atan2(x))))
+            }),
+            Builtin::new("arg", 1, Some(1), |args| {
+                Ok(Value::Float(($from_rad)(complex_arg(&args[0])?.arg())))
+            }),
+        ]
+    };
+}
+
+const RAD_FNS: &[Builtin] = trig_fns!(AngleUnit::Rad, |x: f64| x, |x: f64| x);
+const DEG_FNS: &[Builtin] = trig_fns!(AngleUnit::Deg, f64::to_radians, f64::to_degrees);
+const GRAD_FNS: &[Builtin] = trig_fns!(
+    AngleUnit::Grad,
+    |x: f64| x * consts::PI / 200.0,
+    |x: f64| x * 200.0 / consts::PI
+);
+
+/// The trigonometric builtins for angles in `unit`.
+pub(crate) fn trig_fns(unit: AngleUnit) -> &'static [Builtin] {
+    match unit {
+        AngleUnit::Rad => RAD_FNS,
+        AngleUnit::Deg => DEG_FNS,
+        AngleUnit::Grad => GRAD_FNS,
+    }
+}
+
+const OTHER_FNS: &[Builtin] = &[
+    Builtin::new("sqrt", 1, Some(1), sqrt).with_decimal(|args, precision| {
+        match args[0].to_decimal(precision)? {
+            x if x.is_negative() => sqrt(args),
+            x => decimal::sqrt(&x, precision).map(Value::from),
+        }
+    }),
+    Builtin::new("abs", 1, Some(1), abs),
+    Builtin::new("conj", 1, Some(1), |args| match args[0] {
+        Value::Complex(ref c) => Ok(Value::from(c.conj())),
+        ref v => float_arg(v).map(|_| v.clone()),
+    }),
+    Builtin::new("re", 1, Some(1), |args| match args[0] {
+        Value::Complex(ref c) => Ok(Value::Float(c.re)),
+        ref v => float_arg(v).map(|_| v.clone()),
+    }),
+    Builtin::new("im", 1, Some(1), |args| match args[0] {
+        Value::Complex(ref c) => Ok(Value::Float(c.im)),
+        ref v => float_arg(v).map(|_| Value::Int(0)),
+    }),
+    Builtin::new("floor", 1, Some(1), |args| {
+        round_with(args, f64::floor, BigRational::floor, Rounding::Floor)
+    }),
+    Builtin::new("ceil", 1, Some(1), |args| {
+        round_with(args, f64::ceil, BigRational::ceil, Rounding::Ceiling)
+    }),
+    Builtin::new("round", 1, Some(1), |args| {
+        round_with(args, f64::round, BigRational::round, Rounding::HalfUp)
+    }),
+    Builtin::new("trunc", 1, Some(1), |args| {
+        round_with(args, f64::trunc, BigRational::trunc, Rounding::Down)
+    }),
+    Builtin::new("log", 1, Some(2), log).with_decimal(|args, precision| {
+        let x = args[0].to_decimal(precision)?;
+        let base = args.get(1).map(|b| b.to_decimal(precision)).transpose()?;
+        decimal::log(&x, base.as_ref(), precision).map(Value::from)
+    }),
+    Builtin::new("hypot", 2, Some(2), |args| {
+        Ok(Value::Float(
+            float_arg(&args[0])?.hypot(float_arg(&args[1])?),
+        ))
+    }),
+    Builtin::new("min", 1, None, |args| extremum(args, true)),
+    Builtin::new("max", 1, None, |args| extremum(args, false)),
+    Builtin::new("int", 1, Some(1), int),
+    Builtin::new("float", 1, Some(1), |args| {
+        Ok(Value::Float(match args[0] {
+            Value::Bool(b) => b as i64 as f64,
+            ref v => float_arg(v)?,
+        }))
+    }),
+    // the exact numbers of any mode, as written out by `Context::to_source`
+    Builtin::new("rational", 2, Some(2), |args| {
+        let (numer, denom) = (int_arg(&args[0])?, int_arg(&args[1])?);
+        if denom.is_zero() {
+            return Err(EvalErrorKind::DivisionByZero);
+        }
+        Ok(Value::from(BigRational::new(numer, denom)))
+    }),
+    Builtin::new("decimal", 2, Some(2), |args| {
+        let scale = int_arg(&args[1])?
+            .to_i64()
+            .and_then(i64::checked_neg)
+            .ok_or(EvalErrorKind::IntegerOverflow)?;
+        Ok(Value::from(BigDecimal::new(int_arg(&args[0])?, scale)))
+    }),
+];
+
+const POW: Builtin = Builtin::new("pow", 2, Some(2), |args| {
+    args[0].clone().binary(BinOp::Pow, args[1].clone())
+})
+.with_decimal(|args, precision| {
+    args[0]
+        .clone()
+        .binary_decimal(BinOp::Pow, args[1].clone(), precision)
+});
+const EXACT_POW: Builtin = Builtin::new("pow", 2, Some(2), |args| {
+    args[0].clone().binary_exact(BinOp::Pow, args[1].clone())
+});
+
+/// `pow` for the arithmetic of `mode`.
+pub(crate) fn pow_fn(mode: Mode) -> Builtin {
+    match mode {
+        Mode::Float | Mode::Decimal => POW,
+        Mode::Exact => EXACT_POW,
+    }
+}
+
+pub(crate) fn builtins() -> impl Iterator<Item = &'static Builtin> {
+    FLOAT_FNS
+        .iter()
+        .chain(RAD_FNS)
+        .chain(OTHER_FNS)
+        .chain([&POW])
+}
+
+fn float_arg(v: &Value) -> std::result::Result<f64, EvalErrorKind> {
+    let expected = match v {
+        Value::Complex(_) => "real number",
+        _ => "number",
+    };
+    v.as_f64()
+        .ok_or_else(|| EvalErrorKind::type_mismatch(expected, v.type_name()))
+}
+
+fn int_arg(v: &Value) -> std::result::Result<BigInt, EvalErrorKind> {
+    match v {
+        Value::Int(i) => Ok(BigInt::from(*i)),
+        Value::BigInt(i) => Ok(i.clone()),
+        v => Err(EvalErrorKind::type_mismatch("int", v.type_name())),
+    }
+}
+
+fn complex_arg(v: &Value) -> std::result::Result<Complex64, EvalErrorKind> {
+    v.as_complex()
+        .ok_or_else(|| EvalErrorKind::type_mismatch("number", v.type_name()))
+}
+
+/// The square roots of negative numbers are imaginary, those of quantities
+/// halve the powers of their units.
+fn sqrt(args: &[Value]) -> FnResult {
+    match args[0] {
+        Value::Complex(ref c) => Ok(Value::from(c.sqrt())),
+        Value::Quantity(ref q) => q.as_ref().clone().pow(0.5).map(Value::from),
+        ref v => match float_arg(v)? {
+            x if x < 0.0 => Ok(Value::from(Complex64::new(0.0, (-x).sqrt()))),
+            x => Ok(Value::Float(x.sqrt())),
+        },
+    }
+}
+
+fn abs(args: &[Value]) -> FnResult {
+    match args[0] {
+        Value::Int(i) => Ok(i
+            .checked_abs()
+            .map_or_else(|| Value::from(BigInt::from(i).abs()), Value::Int)),
+        Value::BigInt(ref i) => Ok(Value::from(i.abs())),
+        Value::Ratio(ref r) => Ok(Value::from(r.abs())),
+        Value::Decimal(ref d) => Ok(Value::from(d.abs())),
+        Value::Complex(ref c) => Ok(Value::Float(c.norm())),
+        Value::Quantity(ref q) => {
+            let abs = q.value().abs();
+            Ok(Value::from(q.as_ref().clone().with_value(abs)))
+        }
+        ref v => Ok(Value::Float(float_arg(v)?.abs())),
+    }
+}
+
+/// The decimal version of a trigonometric function, for angles in `unit`.
+fn decimal_trig(
+    args: &[Value],
+    precision: Precision,
+    unit: AngleUnit,
+    f: fn(&BigDecimal, Precision) -> DecimalResult,
+) -> FnResult {
+    let x = args[0].to_decimal(precision)?;
+    let x = match unit {
+        AngleUnit::Rad => x,
+        AngleUnit::Deg => decimal::to_radians(&x, 180, precision)?,
+        AngleUnit::Grad => decimal::to_radians(&x, 200, precision)?,
+    };
+    f(&x, precision).map(Value::from)
+}
+
+/// Rounding functions keep integers as they are and round rationals and
+/// decimals to integers.
+fn round_with(
+    args: &[Value],
+    f: fn(f64) -> f64,
+    exact: fn(&BigRational) -> BigRational,
+    rounding: Rounding,
+) -> FnResult {
+    match args[0] {
+        ref i @ (Value::Int(_) | Value::BigInt(_)) => Ok(i.clone()),
+        Value::Ratio(ref r) => Ok(Value::from(exact(r))),
+        Value::Decimal(ref d) => decimal::to_int(d, rounding).map(Value::from),
+        ref v => Ok(Value::Float(f(float_arg(v)?))),
+    }
+}
+
+/// `log(x)` is the natural logarithm, `log(x, base)` any other base.
+fn log(args: &[Value]) -> FnResult {
+    let x = float_arg(&args[0])?;
+    match args.get(1) {
+        Some(base) => Ok(Value::Float(x.log(float_arg(base)?))),
+        None => Ok(Value::Float(x.ln())),
+    }
+}
+
+fn extremum(args: &[Value], min: bool) -> FnResult {
+    let mut best = &args[0];
+    // quantities compare with quantities of the same dimension only
+    if !matches!(best, Value::Quantity(_)) {
+        float_arg(best)?;
+    }
+    for v in &args[1..] {
+        let op = if min { BinOp::Lt } else { BinOp::Gt };
+        if v.clone().binary(op, best.clone())? == Value::Bool(true) {
+            best = v;
+        }
+    }
+    Ok(best.clone())
+}
+
+/// Truncates towards zero, failing for infinities and NaN.
+fn int(args: &[Value]) -> FnResult {
+    match args[0] {
+        ref i @ (Value::Int(_) | Value::BigInt(_)) => Ok(i.clone()),
+        Value::Ratio(ref r) => Ok(Value::from(r.trunc())),
+        Value::Decimal(ref d) => decimal::to_int(d, Rounding::Down).map(Value::from),
+        Value::Bool(b) => Ok(Value::Int(b as i64)),
+        Value::Float(x) => BigInt::from_f64(x.trunc())
+            .map(Value::from)
+            .ok_or(EvalErrorKind::IntegerOverflow),
+        Value::Complex(_) => Err(EvalErrorKind::type_mismatch("real number", "complex")),
+        Value::Quantity(_) => Err(EvalErrorKind::type_mismatch("number", "quantity")),
+        Value::Struct(_) => Err(EvalErrorKind::type_mismatch("number", "struct")),
+        Value::Unit => Err(EvalErrorKind::type_mismatch("number", "unit")),
+    }
+}
@@ -0,0 +1,467 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::rc::Rc;
+
+use super::builtins;
+use super::decimal::Precision;
+use super::error::EvalErrorKind;
+use super::value::Value;
+use crate::parser::ast::{FnDecl, StructDecl};
+use crate::units::Unit;
+
+pub type BuiltinFn = fn(&[Value]) -> std::result::Result<Value, EvalErrorKind>;
+/// A builtin on decimals, computed to a precision.
+pub type DecimalFn = fn(&[Value], Precision) -> std::result::Result<Value, EvalErrorKind>;
+
+/// A native function callable from expressions.
+#[derive(Debug, Clone, Copy)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub min_args: usize,
+    /// `None` for variadic functions.
+    pub max_args: Option<usize>,
+    pub func: BuiltinFn,
+    /// Used instead of `func` in the decimal mode.
+    pub decimal: Option<DecimalFn>,
+}
+
+impl Builtin {
+    pub const fn new(
+        name: &'static str,
+        min_args: usize,
+        max_args: Option<usize>,
+        func: BuiltinFn,
+    ) -> Self {
+        Self {
+            name,
+            min_args,
+            max_args,
+            func,
+            decimal: None,
+        }
+    }
+
+    pub const fn with_decimal(mut self, decimal: DecimalFn) -> Self {
+        self.decimal = Some(decimal);
+        self
+    }
+
+    pub fn call(&self, args: &[Value]) -> std::result::Result<Value, EvalErrorKind> {
+        self.check_args(args.len())?;
+        (self.func)(args)
+    }
+
+    /// Calls the decimal version when there is one and the arguments are all
+    /// decimals or exact numbers.
+    pub fn call_decimal(
+        &self,
+        args: &[Value],
+        precision: Precision,
+    ) -> std::result::Result<Value, EvalErrorKind> {
+        match self.decimal {
+            Some(f) if args.iter().all(Value::is_rational) => {
+                self.check_args(args.len())?;
+                f(args, precision)
+            }
+            _ => self.call(args),
+        }
+    }
+
+    fn check_args(&self, n: usize) -> Result<(), EvalErrorKind> {
+        if n < self.min_args || self.max_args.is_some_and(|max| n > max) {
+            let expected = match self.max_args {
+                Some(max) if max == self.min_args => max.to_string(),
+                Some(max) => format!("{} to {max}", self.min_args),
+                None => format!("at least {}", self.min_args),
+            };
+            return Err(EvalErrorKind::ArgumentCount {
+                name: self.name.to_owned(),
+                expected,
+                found: n,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The unit of the angles trigonometric builtins take and return.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AngleUnit {
+    #[default]
+    Rad,
+    Deg,
+    /// A right angle is 100 gradians.
+    Grad,
+}
+
+impl AngleUnit {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Rad => "rad",
+            Self::Deg => "deg",
+            Self::Grad => "grad",
+        }
+    }
+}
+
+impl std::str::FromStr for AngleUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rad" => Ok(Self::Rad),
+            "deg" => Ok(Self::Deg),
+            "grad" => Ok(Self::Grad),
+            _ => Err(s.to_owned()),
+        }
+    }
+}
+
+/// What dividing integers gives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// Floats, `1 / 3` is `0.3333333333333333`.
+    Float,
+    /// Exact rationals, `1 / 3` stays `1/3`. So do negative powers.
+    Exact,
+    /// Decimals rounded to the [`Precision`], for literals with a point too,
+    /// so that `0.1 + 0.2 == 0.3`.
+    #[default]
+    Decimal,
+}
+
+impl Mode {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Float => "float",
+            Self::Exact => "exact",
+            Self::Decimal => "decimal",
+        }
+    }
+}
+
+impl std::str::FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "float" => Ok(Self::Float),
+            "exact" => Ok(Self::Exact),
+            "decimal" => Ok(Self::Decimal),
+            _ => Err(s.to_owned()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Binding {
+    value: Value,
+    constant: bool,
+}
+
+impl Binding {
+    const fn new(value: Value, constant: bool) -> Self {
+        Self { value, constant }
+    }
+}
+
+type Scope = BTreeMap<String, Binding>;
+
+/// Variable bindings and functions visible to the evaluator.
+///
+/// Globals live as long as the context, blocks and calls push scopes on top
+/// of them. A call only sees its own scopes, the ones below `frame` belong to
+/// its callers.
+#[derive(Debug, Clone)]
+pub struct Context {
+    vars: Scope,
+    scopes: Vec<Scope>,
+    frame: usize,
+    pub(super) depth: usize,
+    functions: BTreeMap<String, Rc<FnDecl<'static>>>,
+    structs: BTreeMap<String, Rc<StructDecl<'static>>>,
+    builtins: BTreeMap<&'static str, Builtin>,
+    angle_unit: AngleUnit,
+    mode: Mode,
+    precision: Precision,
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Context {
+    /// A context with the builtin functions and constants (`pi`, `e`, `tau`).
+    pub fn new() -> Self {
+        let mut s = Self::empty();
+        for b in builtins::builtins() {
+            s.register(*b);
+        }
+        s.register(builtins::pow_fn(s.mode));
+        for (name, value) in builtins::constants(s.mode, s.precision) {
+            s.set_const(name, value);
+        }
+        s
+    }
+
+    /// A context without any bindings.
+    pub fn empty() -> Self {
+        Self {
+            vars: Scope::new(),
+            scopes: Vec::new(),
+            frame: 0,
+            depth: 0,
+            functions: BTreeMap::new(),
+            structs: BTreeMap::new(),
+            builtins: BTreeMap::new(),
+            angle_unit: AngleUnit::Rad,
+            mode: Mode::default(),
+            precision: Precision::default(),
+        }
+    }
+
+    /// Binds a global variable.
+    #[inline]
+    pub fn set_var(&mut self, name: impl Into<String>, value: impl Into<Value>) {
+        self.vars
+            .insert(name.into(), Binding::new(value.into(), false));
+    }
+
+    /// Binds a global constant, which cannot be assigned to.
+    #[inline]
+    pub fn set_const(&mut self, name: impl Into<String>, value: impl Into<Value>) {
+        self.vars
+            .insert(name.into(), Binding::new(value.into(), true));
+    }
+
+    /// Looks a variable up from the innermost scope out to the globals.
+    pub fn get_var(&self, name: &str) -> Option<&Value> {
+        self.binding(name).map(|b| &b.value)
+    }
+
+    #[inline]
+    pub fn is_const(&self, name: &str) -> bool {
+        self.binding(name).is_some_and(|b| b.constant)
+    }
+
+    /// The global variables.
+    #[inline]
+    pub fn vars(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.vars.iter().map(|(k, b)| (k.as_str(), &b.value))
+    }
+
+    /// Names of all the variables visible from the current scope.
+    pub fn var_names(&self) -> impl Iterator<Item = &str> {
+        self.scopes[self.frame..]
+            .iter()
+            .chain(std::iter::once(&self.vars))
+            .flat_map(|scope| scope.keys().map(String::as_str))
+    }
+
+    /// The globals, functions and structs that a fresh context does not
+    /// have, as a program declaring them again. The values read back the
+    /// same whatever the mode and precision, see [`Value::literal`], while
+    /// the functions use the settings of the context that runs the program.
+    pub fn to_source(&self) -> String {
+        let mut source = String::new();
+        // before the variables holding their values
+        for decl in self.structs.values() {
+            let _ = writeln!(source, "{decl}");
+        }
+        let constants = builtins::constants(self.mode, self.precision);
+        let (mut quantities, others): (Vec<_>, Vec<_>) = self
+            .vars
+            .iter()
+            .filter(|(name, binding)| {
+                let builtin = constants.iter().find(|(c, _)| c == *name);
+                !builtin.is_some_and(|(_, x)| binding.constant && binding.value == *x)
+            })
+            .partition(|(_, binding)| binding.value.has_quantity());
+        // the units of quantities would be ambiguous after variables of the
+        // same name, `h := 2` with `5.0f64 km/h`
+        quantities.sort_by_key(|(name, _)| Unit::lookup(name).is_some());
+        for (name, binding) in quantities.into_iter().chain(others) {
+            let decl = if binding.constant { "::" } else { ":=" };
+            let _ = writeln!(source, "{name} {decl} {};", binding.value.literal());
+        }
+        for decl in self.functions.values() {
+            let _ = writeln!(source, "{decl}");
+        }
+        source
+    }
+
+    /// Declares a variable in the innermost scope, shadowing any other
+    /// binding of the name.
+    pub fn declare(&mut self, name: impl Into<String>, value: Value, constant: bool) {
+        let scope = match self.scopes[self.frame..].last_mut() {
+            Some(scope) => scope,
+            None => &mut self.vars,
+        };
+        scope.insert(name.into(), Binding::new(value, constant));
+    }
+
+    /// Assigns to an existing variable.
+    pub fn assign(&mut self, name: &str, value: Value) -> Result<(), EvalErrorKind> {
+        let frame = self.frame;
+        let binding = self.scopes[frame..]
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+            .or_else(|| self.vars.get_mut(name))
+            .ok_or_else(|| EvalErrorKind::UndefinedVariable(name.to_owned()))?;
+        if binding.constant {
+            return Err(EvalErrorKind::AssignToConstant(name.to_owned()));
+        }
+        binding.value = value;
+        Ok(())
+    }
+
+    fn binding(&self, name: &str) -> Option<&Binding> {
+        self.scopes[self.frame..]
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .or_else(|| self.vars.get(name))
+    }
+
+    /// Runs `f` in a new scope, which holds `vars` to begin with.
+    pub(super) fn scoped<T>(
+        &mut self,
+        vars: impl IntoIterator<Item = (String, Value)>,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        let scope = vars
+            .into_iter()
+            .map(|(name, value)| (name, Binding::new(value, false)))
+            .collect();
+        self.scopes.push(scope);
+        let result = f(self);
+        self.scopes.pop();
+        result
+    }
+
+    /// Runs `f` in a new call frame, hiding the scopes of the caller.
+    pub(super) fn framed<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let frame = std::mem::replace(&mut self.frame, self.scopes.len());
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        self.frame = frame;
+        result
+    }
+
+    /// Defines a function, replacing any other function or struct with the
+    /// same name. User functions take precedence over builtins.
+    #[inline]
+    pub fn define_fn(&mut self, decl: Rc<FnDecl<'static>>) {
+        self.structs.remove(decl.name.as_ref());
+        self.functions.insert(decl.name.to_string(), decl);
+    }
+
+    #[inline]
+    pub fn get_fn(&self, name: &str) -> Option<&Rc<FnDecl<'static>>> {
+        self.functions.get(name)
+    }
+
+    #[inline]
+    pub fn functions(&self) -> impl Iterator<Item = &Rc<FnDecl<'static>>> {
+        self.functions.values()
+    }
+
+    /// Declares a struct, replacing any other struct or function with the
+    /// same name. Its name called with the fields makes a value.
+    #[inline]
+    pub fn define_struct(&mut self, decl: Rc<StructDecl<'static>>) {
+        self.functions.remove(decl.name.as_ref());
+        self.structs.insert(decl.name.to_string(), decl);
+    }
+
+    #[inline]
+    pub fn get_struct(&self, name: &str) -> Option<&Rc<StructDecl<'static>>> {
+        self.structs.get(name)
+    }
+
+    #[inline]
+    pub fn structs(&self) -> impl Iterator<Item = &Rc<StructDecl<'static>>> {
+        self.structs.values()
+    }
+
+    #[inline]
+    pub fn register(&mut self, builtin: Builtin) {
+        self.builtins.insert(builtin.name, builtin);
+    }
+
+    #[inline]
+    pub fn get_builtin(&self, name: &str) -> Option<&Builtin> {
+        self.builtins.get(name)
+    }
+
+    #[inline]
+    pub fn builtins(&self) -> impl Iterator<Item = &Builtin> {
+        self.builtins.values()
+    }
+
+    #[inline]
+    pub fn angle_unit(&self) -> AngleUnit {
+        self.angle_unit
+    }
+
+    /// Switches the trigonometric builtins to angles in `unit`.
+    pub fn set_angle_unit(&mut self, unit: AngleUnit) {
+        self.angle_unit = unit;
+        for builtin in builtins::trig_fns(unit) {
+            self.register(*builtin);
+        }
+    }
+
+    #[inline]
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Switches operators and builtins to exact, float or decimal arithmetic.
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.register(builtins::pow_fn(mode));
+        self.update_constants(|ctx| ctx.mode = mode);
+    }
+
+    #[inline]
+    pub fn precision(&self) -> Precision {
+        self.precision
+    }
+
+    /// Sets the precision of the decimal mode.
+    pub fn set_precision(&mut self, precision: Precision) {
+        self.update_constants(|ctx| ctx.precision = precision);
+    }
+
+    /// Runs `f` and then swaps the builtin constants for those of the new
+    /// mode and precision, unless they were rebound.
+    fn update_constants(&mut self, f: impl FnOnce(&mut Self)) {
+        let old = builtins::constants(self.mode, self.precision);
+        f(self);
+        let new = builtins::constants(self.mode, self.precision);
+        for ((name, old), (_, new)) in old.into_iter().zip(new) {
+            if self
+                .vars
+                .get(name)
+                .is_some_and(|b| b.constant && b.value == old)
+            {
+                self.set_const(name, new);
+            }
+        }
+    }
+
+    /// Names of the user functions, structs and builtins that can be called.
+    pub fn fn_names(&self) -> impl Iterator<Item = &str> {
+        let builtins = self.builtins.keys().copied();
+        let structs = self.structs.keys().map(String::as_str);
+        self.functions
+            .keys()
+            .map(String::as_str)
+            .chain(structs)
+            .chain(builtins)
+    }
+}
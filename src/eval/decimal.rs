@@ -0,0 +1,544 @@
+//! Decimal arithmetic rounded to a working precision, and the elementary
+//! functions on decimals.
+//!
+//! The series behind the functions run on fixed point integers, `x * 10^wp`
+//! for a working precision `wp` a few digits above the one asked for.
+
+use std::fmt;
+use std::num::NonZeroU64;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, RoundingMode};
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use super::error::EvalErrorKind;
+use super::value::MAX_INT_BITS;
+use crate::parser::ast::BinOp;
+
+type DecimalResult = std::result::Result<BigDecimal, EvalErrorKind>;
+
+/// The default precision, that of IEEE 754 decimal128.
+pub const DEFAULT_PRECISION: u64 = 34;
+/// Precisions above this make the elementary functions too slow.
+pub const MAX_PRECISION: u64 = 1000;
+/// Decimals with a larger power of ten fail to compute, rather than taking
+/// up all memory.
+pub const MAX_EXPONENT: i64 = 1 << 20;
+
+/// Digits computed beyond the precision, so that results round right.
+const GUARD_DIGITS: u64 = 10;
+
+/// How decimal results are rounded to the precision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rounding {
+    /// To the nearest, ties to the even neighbour.
+    #[default]
+    HalfEven,
+    /// To the nearest, ties away from zero.
+    HalfUp,
+    /// To the nearest, ties towards zero.
+    HalfDown,
+    /// Away from zero.
+    Up,
+    /// Towards zero.
+    Down,
+    Ceiling,
+    Floor,
+}
+
+impl Rounding {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::HalfEven => "half-even",
+            Self::HalfUp => "half-up",
+            Self::HalfDown => "half-down",
+            Self::Up => "up",
+            Self::Down => "down",
+            Self::Ceiling => "ceiling",
+            Self::Floor => "floor",
+        }
+    }
+
+    const fn mode(self) -> RoundingMode {
+        match self {
+            Self::HalfEven => RoundingMode::HalfEven,
+            Self::HalfUp => RoundingMode::HalfUp,
+            Self::HalfDown => RoundingMode::HalfDown,
+            Self::Up => RoundingMode::Up,
+            Self::Down => RoundingMode::Down,
+            Self::Ceiling => RoundingMode::Ceiling,
+            Self::Floor => RoundingMode::Floor,
+        }
+    }
+}
+
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "half-even" => Ok(Self::HalfEven),
+            "half-up" => Ok(Self::HalfUp),
+            "half-down" => Ok(Self::HalfDown),
+            "up" => Ok(Self::Up),
+            "down" => Ok(Self::Down),
+            "ceiling" => Ok(Self::Ceiling),
+            "floor" => Ok(Self::Floor),
+            _ => Err(s.to_owned()),
+        }
+    }
+}
+
+/// The working precision of decimals in significant digits, and how results
+/// are rounded to it.
+///
+/// It is written as words, a number of digits and a [`Rounding`] name, as in
+/// `50 half-up`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Precision {
+    digits: u64,
+    rounding: Rounding,
+}
+
+impl Default for Precision {
+    fn default() -> Self {
+        Self::new(DEFAULT_PRECISION, Rounding::default())
+    }
+}
+
+/// What precision words there are.
+pub const PRECISION_HELP: &str = "precisions are a number of digits up to 1000 and a rounding, half-even, half-up, half-down, up, down, ceiling or floor";
+
+impl Precision {
+    /// `digits` is clamped to `1..=MAX_PRECISION`.
+    pub const fn new(digits: u64, rounding: Rounding) -> Self {
+        let digits = if digits == 0 {
+            1
+        } else if digits > MAX_PRECISION {
+            MAX_PRECISION
+        } else {
+            digits
+        };
+        Self { digits, rounding }
+    }
+
+    pub fn digits(&self) -> u64 {
+        self.digits
+    }
+
+    pub fn rounding(&self) -> Rounding {
+        self.rounding
+    }
+
+    /// The precision with `extra` more digits, for intermediate results.
+    fn extended(self, extra: u64) -> Self {
+        Self {
+            digits: self.digits + extra,
+            ..self
+        }
+    }
+
+    fn non_zero(self) -> NonZeroU64 {
+        NonZeroU64::new(self.digits).unwrap_or(NonZeroU64::MIN)
+    }
+}
+
+impl fmt::Display for Precision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.digits, self.rounding.name())
+    }
+}
+
+/// Words left out keep their default, the error is the first unknown word.
+impl FromStr for Precision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut precision = Self::default();
+        for word in s.split_whitespace() {
+            match word.parse::<u64>() {
+                Ok(digits @ 1..=MAX_PRECISION) => precision.digits = digits,
+                Ok(_) => return Err(word.to_owned()),
+                Err(_) => precision.rounding = word.parse()?,
+            }
+        }
+        Ok(precision)
+    }
+}
+
+/// The shortest literal for `x`: plain digits for moderate exponents and
+/// `d.ddde<exp>` otherwise. There is always a point or an exponent, so that
+/// it reads back as a decimal rather than an integer.
+pub(crate) fn to_string(x: &BigDecimal) -> String {
+    if x.is_zero() {
+        return "0.0".to_owned();
+    }
+    let (digits, scale) = x.as_bigint_and_exponent();
+    let sign = if digits.is_negative() { "-" } else { "" };
+    let digits = digits.magnitude().to_string();
+    let len = digits.len() as i64;
+    let exp = len - 1 - scale;
+    if !(-7..21).contains(&exp) {
+        return match digits.split_at(1) {
+            (lead, "") => format!("{sign}{lead}e{exp}"),
+            (lead, rest) => format!("{sign}{lead}.{rest}e{exp}"),
+        };
+    }
+    if scale <= 0 {
+        let zeros = "0".repeat(-scale as usize);
+        format!("{sign}{digits}{zeros}.0")
+    } else if scale >= len {
+        let zeros = "0".repeat((scale - len) as usize);
+        format!("{sign}0.{zeros}{digits}")
+    } else {
+        let (int, frac) = digits.split_at((len - scale) as usize);
+        format!("{sign}{int}.{frac}")
+    }
+}
+
+/// Rounds to the precision, failing for exponents out of range. Shorter
+/// numbers are kept as they are, rather than padded with zeros.
+pub(crate) fn round(x: BigDecimal, precision: Precision) -> DecimalResult {
+    let x = match x.digits() > precision.digits {
+        true => x.with_precision_round(precision.non_zero(), precision.rounding.mode()),
+        false => x,
+    };
+    if !x.is_zero() && x.order_of_magnitude().abs() > MAX_EXPONENT {
+        return Err(EvalErrorKind::DecimalOverflow);
+    }
+    Ok(x)
+}
+
+/// Rounds to an integer, failing for those over [`MAX_INT_BITS`] bits.
+pub(crate) fn to_int(x: &BigDecimal, rounding: Rounding) -> Result<BigInt, EvalErrorKind> {
+    // a digit is worth a little over 3.3 bits
+    if !x.is_zero() && x.order_of_magnitude() > (MAX_INT_BITS * 3 / 10) as i64 {
+        return Err(EvalErrorKind::IntegerOverflow);
+    }
+    let int = x.with_scale_round(0, rounding.mode());
+    Ok(int.as_bigint_and_exponent().0)
+}
+
+/// The arithmetic operators on decimals.
+pub(crate) fn binary(
+    op: BinOp,
+    a: &BigDecimal,
+    b: &BigDecimal,
+    precision: Precision,
+) -> DecimalResult {
+    match op {
+        BinOp::Add => add(a, b, precision),
+        BinOp::Sub => add(a, &-b, precision),
+        BinOp::Mul => round(a * b, precision),
+        BinOp::Div => div(a, b, precision),
+        BinOp::IDiv | BinOp::Rem => {
+            if b.is_zero() {
+                return Err(EvalErrorKind::DivisionByZero);
+            }
+            let (a, b, scale) = aligned(a, b);
+            match op {
+                BinOp::IDiv => round(BigDecimal::from(a.div_floor(&b)), precision),
+                _ => round(BigDecimal::new(a.mod_floor(&b), scale), precision),
+            }
+        }
+        BinOp::Pow => pow(a, b, precision),
+        _ => unreachable!("`{op}` is not a decimal operator"),
+    }
+}
+
+/// `a + b`. An operand too small to reach the last digit of the other only
+/// counts for the rounding, it is swapped for a smaller number with a single
+/// digit rather than lined up digit for digit.
+fn add(a: &BigDecimal, b: &BigDecimal, precision: Precision) -> DecimalResult {
+    if a.is_zero() || b.is_zero() {
+        return round(a + b, precision);
+    }
+    let (big, small) = if a.abs() >= b.abs() { (a, b) } else { (b, a) };
+    let last = big.as_bigint_and_exponent().1;
+    // below both the last digit of `big` and the rounding digits
+    let stand_in = (big.order_of_magnitude() - precision.digits as i64 - 2).min(-last - 1);
+    if small.order_of_magnitude() < stand_in {
+        let digit = if small.is_negative() { -1 } else { 1 };
+        return round(big + BigDecimal::new(digit.into(), -stand_in), precision);
+    }
+    round(a + b, precision)
+}
+
+/// `a / b`, with a sticky last digit so that the quotient rounds as the
+/// exact one would.
+fn div(a: &BigDecimal, b: &BigDecimal, precision: Precision) -> DecimalResult {
+    if b.is_zero() {
+        return Err(EvalErrorKind::DivisionByZero);
+    }
+    let (a_int, a_scale) = a.as_bigint_and_exponent();
+    let (b_int, b_scale) = b.as_bigint_and_exponent();
+    let shift = (precision.digits as i64 + 2 + b.digits() as i64 - a.digits() as i64).max(0) as u64;
+    let sign = a_int.signum() * b_int.signum();
+    let (mut q, r) = (a_int * ten_pow(shift)).div_rem(&b_int);
+    let (ideal, mut scale) = (a_scale - b_scale, a_scale - b_scale + shift as i64);
+    if !r.is_zero() {
+        // a last digit that is not a zero rounds ties the right way
+        return round(BigDecimal::new(q * 10 + sign, scale + 1), precision);
+    }
+    // exact quotients keep no more zeros than the operands call for
+    while scale > ideal && (&q % 10u8).is_zero() {
+        q /= 10u8;
+        scale -= 1;
+    }
+    round(BigDecimal::new(q, scale), precision)
+}
+
+/// The integers of `a` and `b` over a common power of ten, and its scale.
+fn aligned(a: &BigDecimal, b: &BigDecimal) -> (BigInt, BigInt, i64) {
+    let (a, a_scale) = a.as_bigint_and_exponent();
+    let (b, b_scale) = b.as_bigint_and_exponent();
+    let scale = a_scale.max(b_scale);
+    (
+        a * ten_pow((scale - a_scale) as u64),
+        b * ten_pow((scale - b_scale) as u64),
+        scale,
+    )
+}
+
+/// `base ^ exp`, through `exp(exp * ln(base))` for exponents that are not
+/// whole.
+fn pow(base: &BigDecimal, exp: &BigDecimal, precision: Precision) -> DecimalResult {
+    if exp.is_integer() {
+        return powi(base, to_int(exp, Rounding::Down)?, precision);
+    }
+    if base.is_zero() {
+        return match exp.is_positive() {
+            true => Ok(BigDecimal::zero()),
+            false => Err(EvalErrorKind::DivisionByZero),
+        };
+    }
+    if base.is_negative() {
+        return Err(EvalErrorKind::Domain("^"));
+    }
+    // exponents over 10^7 overflow anyway, so 7 more digits keep the product
+    // as precise as the result needs
+    let wide = precision.extended(GUARD_DIGITS + 7);
+    let y = round(exp * ln(base, wide)?, wide)?;
+    self::exp(&y, precision)
+}
+
+/// Squares and multiplies, with enough more digits to cover the roundings.
+fn powi(base: &BigDecimal, exp: BigInt, precision: Precision) -> DecimalResult {
+    if exp.is_negative() {
+        if base.is_zero() {
+            return Err(EvalErrorKind::DivisionByZero);
+        }
+        let wide = precision.extended(GUARD_DIGITS);
+        return div(&BigDecimal::one(), &powi(base, -exp, wide)?, precision);
+    }
+    let wide = precision.extended(GUARD_DIGITS + exp.to_string().len() as u64);
+    let mut result = BigDecimal::one();
+    let mut square = base.clone();
+    let bits = exp.bits();
+    for bit in 0..bits {
+        if exp.bit(bit) {
+            result = round(&result * &square, wide)?;
+        }
+        if bit + 1 < bits {
+            square = round(&square * &square, wide)?;
+        }
+    }
+    round(result, precision)
+}
+
+pub(crate) fn sqrt(x: &BigDecimal, precision: Precision) -> DecimalResult {
+    let ctx = bigdecimal::Context::new(precision.non_zero(), precision.rounding.mode());
+    x.sqrt_with_context(&ctx)
+        .map(|root| root.normalized())
+        .ok_or(EvalErrorKind::Domain("sqrt"))
+}
+
+/// `e^x` as `e^r * 2^n` with `x = r + n ln(2)` and `|r| <= ln(2) / 2`.
+pub(crate) fn exp(x: &BigDecimal, precision: Precision) -> DecimalResult {
+    let limit = MAX_EXPONENT as f64 * std::f64::consts::LN_10;
+    let n = match x.abs().to_f64() {
+        Some(x) if x <= limit => (x / std::f64::consts::LN_2).round() as u64,
+        _ => return Err(EvalErrorKind::DecimalOverflow),
+    };
+    let wp = precision.digits + GUARD_DIGITS + n.to_string().len() as u64;
+    let one = ten_pow(wp);
+    let r = to_fixed(&x.abs(), wp) - ln2(&one) * n;
+    let mut sum = one.clone();
+    let mut term = one.clone();
+    for k in 1u32.. {
+        term = term * &r / &one / k;
+        if term.is_zero() {
+            break;
+        }
+        sum += &term;
+    }
+    let y = BigDecimal::new(sum << n, wp as i64);
+    let y = match x.is_negative() {
+        true => div(&BigDecimal::one(), &y, precision)?,
+        false => round(y, precision)?,
+    };
+    Ok(y.normalized())
+}
+
+/// `ln(x)` with `x = y * 2^j * 10^e` and `0.75 <= y < 1.5`, where the series
+/// of `ln(y) = 2 atanh((y - 1) / (y + 1))` is quick.
+pub(crate) fn ln(x: &BigDecimal, precision: Precision) -> DecimalResult {
+    if !x.is_positive() {
+        return Err(EvalErrorKind::Domain("ln"));
+    }
+    let e = x.order_of_magnitude();
+    let (digits, scale) = x.as_bigint_and_exponent();
+    let m = BigDecimal::new(digits, scale + e);
+    let j = (m.to_f64().unwrap_or(1.0) / 0.75).log2().floor().max(0.0) as usize;
+    // close to 1 the result is small, and needs a digit more for each
+    // leading zero
+    let near_one = match &m - BigDecimal::one() {
+        d if e == 0 && j == 0 && !d.is_zero() => (-d.order_of_magnitude()).max(0) as u64,
+        _ => 0,
+    };
+    let wp =
+        precision.digits + GUARD_DIGITS + e.to_string().len() as u64 + near_one.min(MAX_PRECISION);
+    let one = ten_pow(wp);
+    let y = to_fixed(&m, wp) >> j;
+    let z = (&y - &one) * &one / (&y + &one);
+    let ln2 = ln2(&one);
+    let mut sum = atanh(&z, &one) * 2 + &ln2 * j;
+    if e != 0 {
+        // ln(10) = ln(10 / 8) + 3 ln(2)
+        let ln10 = atanh(&(&one / 9), &one) * 2 + ln2 * 3;
+        sum += ln10 * e;
+    }
+    Ok(round(BigDecimal::new(sum, wp as i64), precision)?.normalized())
+}
+
+/// `ln(x)`, or `ln(x) / ln(base)` with a base.
+pub(crate) fn log(
+    x: &BigDecimal,
+    base: Option<&BigDecimal>,
+    precision: Precision,
+) -> DecimalResult {
+    let Some(base) = base else {
+        return ln(x, precision);
+    };
+    let wide = precision.extended(GUARD_DIGITS);
+    let log = div(&ln(x, wide)?, &ln(base, wide)?, precision)?;
+    Ok(log.normalized())
+}
+
+pub(crate) fn sin(x: &BigDecimal, precision: Precision) -> DecimalResult {
+    trig(x, precision, true)
+}
+
+pub(crate) fn cos(x: &BigDecimal, precision: Precision) -> DecimalResult {
+    trig(x, precision, false)
+}
+
+pub(crate) fn tan(x: &BigDecimal, precision: Precision) -> DecimalResult {
+    let wide = precision.extended(GUARD_DIGITS);
+    let tan = div(&sin(x, wide)?, &cos(x, wide)?, precision)?;
+    Ok(tan.normalized())
+}
+
+/// The sine or cosine series, after taking `x` down to `[-pi, pi)`.
+fn trig(x: &BigDecimal, precision: Precision, sine: bool) -> DecimalResult {
+    // each digit of the integer part is one more digit of pi to subtract
+    let int_digits = match x.is_zero() {
+        true => 0,
+        false => x.order_of_magnitude().max(0) as u64,
+    };
+    if int_digits > MAX_PRECISION {
+        return Err(EvalErrorKind::Domain(if sine { "sin" } else { "cos" }));
+    }
+    let wp = precision.digits + GUARD_DIGITS + int_digits;
+    let one = ten_pow(wp);
+    let two_pi = pi(&one) * 2;
+    let mut r = to_fixed(x, wp);
+    let turns = (&r + &pi(&one)).div_floor(&two_pi);
+    r -= turns * &two_pi;
+    let r2 = &r * &r / &one;
+    let (mut term, mut n) = match sine {
+        true => (r, 2u64),
+        false => (one.clone(), 1u64),
+    };
+    let mut sum = term.clone();
+    loop {
+        term = -(term * &r2 / &one) / (n * (n + 1));
+        if term.is_zero() {
+            break;
+        }
+        sum += &term;
+        n += 2;
+    }
+    Ok(round(BigDecimal::new(sum, wp as i64), precision)?.normalized())
+}
+
+/// `k` times pi to the precision.
+pub(crate) fn pi_times(k: u32, precision: Precision) -> DecimalResult {
+    let wp = precision.digits + GUARD_DIGITS;
+    round(BigDecimal::new(pi(&ten_pow(wp)) * k, wp as i64), precision)
+}
+
+/// An angle in radians, from one where a half turn is `half_turn`.
+pub(crate) fn to_radians(x: &BigDecimal, half_turn: u32, precision: Precision) -> DecimalResult {
+    let wide = precision.extended(GUARD_DIGITS);
+    let turned = round(x * pi_times(1, wide)?, wide)?;
+    div(&turned, &BigDecimal::from(half_turn), precision)
+}
+
+/// Machin's formula, `pi = 16 atan(1/5) - 4 atan(1/239)`.
+fn pi(one: &BigInt) -> BigInt {
+    atan_inv(5, one) * 16 - atan_inv(239, one) * 4
+}
+
+/// `atan(1/n)` in fixed point.
+fn atan_inv(n: u32, one: &BigInt) -> BigInt {
+    let n2 = n * n;
+    let mut power = one / n;
+    let mut sum = power.clone();
+    for k in (3u32..).step_by(2) {
+        power /= n2;
+        let term = &power / k;
+        if term.is_zero() {
+            break;
+        }
+        if k % 4 == 3 {
+            sum -= term;
+        } else {
+            sum += term;
+        }
+    }
+    sum
+}
+
+/// `atanh(x)` in fixed point, for `|x| <= 1/3`.
+fn atanh(x: &BigInt, one: &BigInt) -> BigInt {
+    let x2 = x * x / one;
+    let mut power = x.clone();
+    let mut sum = x.clone();
+    for k in (3u32..).step_by(2) {
+        power = power * &x2 / one;
+        let term = &power / k;
+        if term.is_zero() {
+            break;
+        }
+        sum += term;
+    }
+    sum
+}
+
+/// `ln(2) = 2 atanh(1/3)` in fixed point.
+fn ln2(one: &BigInt) -> BigInt {
+    atanh(&(one / 3), one) * 2
+}
+
+fn ten_pow(digits: u64) -> BigInt {
+    num_traits::pow(BigInt::from(10), digits as usize)
+}
+
+/// `x` as a fixed point integer with `wp` decimals.
+fn to_fixed(x: &BigDecimal, wp: u64) -> BigInt {
+    let fixed = x.with_scale_round(wp as i64, RoundingMode::HalfEven);
+    fixed.as_bigint_and_exponent().0
+}
@@ -0,0 +1,142 @@
+use crate::location::{Loc, Span};
+
+use derive_more::Display;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum EvalErrorKind {
+    #[error("{0}")]
+    Any(String),
+    #[error("undefined variable `{0}`")]
+    UndefinedVariable(String),
+    #[error("undefined function `{0}`")]
+    UndefinedFunction(String),
+    #[error("`{name}` takes {expected} argument(s), got {found}")]
+    ArgumentCount {
+        name: String,
+        expected: String,
+        found: usize,
+    },
+    #[error("unsupported operand type {ty} for `{op}`")]
+    InvalidOperand { op: String, ty: &'static str },
+    #[error("unsupported operand types {lhs} and {rhs} for `{op}`")]
+    InvalidOperands {
+        op: String,
+        lhs: &'static str,
+        rhs: &'static str,
+    },
+    #[error("expected {expected}, got {found}")]
+    TypeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    #[error("expression is not callable")]
+    NotCallable,
+    #[error("no field `{name}` on type {ty}")]
+    NoField { name: String, ty: String },
+    #[error("division by zero")]
+    DivisionByZero,
+    #[error("integer overflow")]
+    IntegerOverflow,
+    #[error("incompatible units `{lhs}` and `{rhs}` for `{op}`")]
+    IncompatibleUnits {
+        op: String,
+        lhs: String,
+        rhs: String,
+    },
+    #[error("unknown unit `{0}`")]
+    UnknownUnit(String),
+    #[error("`{0}` is both a unit and a variable")]
+    AmbiguousUnit(String),
+    #[error("units of `{0}` would have fractional or too large powers")]
+    UnitPower(String),
+    #[error("the `{0}` format needs an integer value")]
+    IntegerFormat(&'static str),
+    #[error("decimal exponent out of range")]
+    DecimalOverflow,
+    #[error("argument outside the domain of `{0}`")]
+    Domain(&'static str),
+    #[error("invalid shift amount {0}")]
+    InvalidShift(String),
+    #[error("cannot assign to constant `{0}`")]
+    AssignToConstant(String),
+    #[error("unknown type `{0}`")]
+    UnknownType(String),
+    #[error("`{0}` outside of a loop")]
+    OutsideLoop(&'static str),
+    #[error("`return` outside of a function")]
+    ReturnOutsideFunction,
+    #[error("recursion limit of {0} nested calls reached")]
+    RecursionLimit(usize),
+}
+
+impl EvalErrorKind {
+    pub fn invalid_operand(op: impl ToString, ty: &'static str) -> Self {
+        Self::InvalidOperand {
+            op: op.to_string(),
+            ty,
+        }
+    }
+
+    pub fn invalid_operands(op: impl ToString, lhs: &'static str, rhs: &'static str) -> Self {
+        Self::InvalidOperands {
+            op: op.to_string(),
+            lhs,
+            rhs,
+        }
+    }
+
+    pub fn type_mismatch(expected: &'static str, found: &'static str) -> Self {
+        Self::TypeMismatch { expected, found }
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "{span}: ERROR - {kind}")]
+pub struct EvalError<'loc> {
+    kind: EvalErrorKind,
+    span: Span<'loc>,
+    suggestions: Vec<String>,
+}
+
+impl<'loc> From<EvalErrorKind> for EvalError<'loc> {
+    fn from(kind: EvalErrorKind) -> Self {
+        Self {
+            kind,
+            span: Default::default(),
+            suggestions: Vec::new(),
+        }
+    }
+}
+
+impl<'loc> EvalError<'loc> {
+    pub fn new(kind: impl Into<EvalErrorKind>, span: Span<'loc>) -> Self {
+        Self {
+            kind: kind.into(),
+            span,
+            suggestions: Vec::new(),
+        }
+    }
+    pub fn set_span(mut self, span: Span<'loc>) -> Self {
+        self.span = span;
+        self
+    }
+    /// Names that were likely meant instead of the offending one.
+    pub fn with_suggestions(mut self, suggestions: Vec<String>) -> Self {
+        self.suggestions = suggestions;
+        self
+    }
+
+    pub fn kind(&self) -> &EvalErrorKind {
+        &self.kind
+    }
+    pub fn loc(&self) -> &Loc<'loc> {
+        &self.span.start
+    }
+    pub fn span(&self) -> &Span<'loc> {
+        &self.span
+    }
+    pub fn suggestions(&self) -> &[String] {
+        &self.suggestions
+    }
+}
@@ -0,0 +1,80 @@
+//! Values of the types declared with `name :: struct { .. }`.
+
+use std::fmt;
+use std::rc::Rc;
+
+use super::error::EvalErrorKind;
+use super::value::Value;
+use crate::parser::ast::StructDecl;
+
+/// A value of a struct, `Point { x: 1, y: 2 }`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instance {
+    decl: Rc<StructDecl<'static>>,
+    /// In the order of the fields of `decl`.
+    values: Vec<Value>,
+}
+
+impl Instance {
+    /// A value with one of `values` for each field, converted to the type
+    /// of the field when it has one.
+    pub fn new(
+        decl: Rc<StructDecl<'static>>,
+        values: Vec<Value>,
+    ) -> std::result::Result<Self, EvalErrorKind> {
+        if values.len() != decl.fields.len() {
+            return Err(EvalErrorKind::ArgumentCount {
+                name: decl.name.to_string(),
+                expected: decl.fields.len().to_string(),
+                found: values.len(),
+            });
+        }
+        let values = decl
+            .fields
+            .iter()
+            .zip(values)
+            .map(|(field, value)| match &field.ty {
+                Some(ty) => value.coerce(ty),
+                None => Ok(value),
+            })
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(Self { decl, values })
+    }
+
+    /// The name of the struct.
+    pub fn name(&self) -> &str {
+        &self.decl.name
+    }
+
+    pub fn decl(&self) -> &StructDecl<'static> {
+        &self.decl
+    }
+
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        self.decl.field(name).map(|idx| &self.values[idx])
+    }
+
+    /// The names of the fields with their values, in declaration order.
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &Value)> {
+        let names = self.decl.fields.iter().map(|field| field.name.as_ref());
+        names.zip(&self.values)
+    }
+
+    /// `Point { x: 1, y: 2 }`, the values written with `show`.
+    pub(crate) fn show(&self, show: impl Fn(&Value) -> String) -> String {
+        let fields: Vec<_> = self
+            .fields()
+            .map(|(name, value)| format!("{name}: {}", show(value)))
+            .collect();
+        match fields.len() {
+            0 => format!("{} {{}}", self.name()),
+            _ => format!("{} {{ {} }}", self.name(), fields.join(", ")),
+        }
+    }
+}
+
+impl fmt::Display for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.show(Value::to_string))
+    }
+}
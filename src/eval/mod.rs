@@ -0,0 +1,404 @@
+pub mod builtins;
+pub mod context;
+pub mod decimal;
+pub mod error;
+pub mod instance;
+pub mod quantity;
+pub mod value;
+
+#[cfg(test)]
+mod tests;
+
+use std::rc::Rc;
+
+use num_bigint::BigInt;
+use num_complex::Complex64;
+
+use crate::error::{Error, Result};
+use crate::lev::closest_words;
+use crate::lexer::token::KEYWORDS;
+use crate::location::Span;
+use crate::parser::ast::{BinOp, Block, Expr, ExprKind, FnDecl, Stmt, StmtKind};
+use crate::units::Unit;
+
+use context::{Context, Mode};
+use error::{EvalError, EvalErrorKind};
+use instance::Instance;
+use quantity::Quantity;
+use value::Value;
+
+/// Calls nested deeper than this fail instead of overflowing the stack.
+pub const MAX_CALL_DEPTH: usize = 128;
+
+/// Why evaluation stopped early: an error, or a `break`, `continue` or
+/// `return` on its way to the loop or call it belongs to.
+enum Unwind<'a> {
+    Error(Error<'a>),
+    Break(Span<'a>),
+    Continue(Span<'a>),
+    Return(Value, Span<'a>),
+}
+
+impl<'a> From<Error<'a>> for Unwind<'a> {
+    fn from(err: Error<'a>) -> Self {
+        Self::Error(err)
+    }
+}
+
+impl<'a> Unwind<'a> {
+    /// Control flow that escaped its loop or function is an error.
+    fn into_error(self) -> Error<'a> {
+        let (kind, span) = match self {
+            Self::Error(err) => return err,
+            Self::Break(span) => (EvalErrorKind::OutsideLoop("break"), span),
+            Self::Continue(span) => (EvalErrorKind::OutsideLoop("continue"), span),
+            Self::Return(_, span) => (EvalErrorKind::ReturnOutsideFunction, span),
+        };
+        Error::eval_error(EvalError::new(kind, span))
+    }
+}
+
+type Exec<'a, T> = std::result::Result<T, Unwind<'a>>;
+
+fn error<'a>(kind: EvalErrorKind, span: &Span<'a>) -> Unwind<'a> {
+    Unwind::Error(Error::eval_error(EvalError::new(kind, span.clone())))
+}
+
+impl Context {
+    /// Evaluates an expression tree against the bindings of this context.
+    pub fn eval_expr<'a>(&mut self, expr: &Expr<'a>) -> Result<'a, Value> {
+        self.eval(expr).map_err(Unwind::into_error)
+    }
+
+    /// Runs a program in the global scope, so its bindings outlive it. The
+    /// value is that of the trailing expression, [`Value::Unit`] without one.
+    pub fn exec<'a>(&mut self, program: &Block<'a>) -> Result<'a, Value> {
+        self.exec_stmts(program).map_err(Unwind::into_error)
+    }
+
+    /// Runs a single statement of a program in the global scope. The value
+    /// is that of an expression statement, [`Value::Unit`] for the others.
+    pub fn run_stmt<'a>(&mut self, stmt: &Stmt<'a>) -> Result<'a, Value> {
+        match &stmt.kind {
+            StmtKind::Expr(expr) => self.eval_expr(expr),
+            _ => self
+                .exec_stmt(stmt)
+                .map(|()| Value::Unit)
+                .map_err(Unwind::into_error),
+        }
+    }
+
+    fn exec_stmts<'a>(&mut self, block: &Block<'a>) -> Exec<'a, Value> {
+        for stmt in &block.stmts {
+            self.exec_stmt(stmt)?;
+        }
+        match &block.tail {
+            Some(tail) => self.eval(tail),
+            None => Ok(Value::Unit),
+        }
+    }
+
+    fn exec_block<'a>(&mut self, block: &Block<'a>) -> Exec<'a, Value> {
+        self.scoped([], |ctx| ctx.exec_stmts(block))
+    }
+
+    fn exec_stmt<'a>(&mut self, stmt: &Stmt<'a>) -> Exec<'a, ()> {
+        match &stmt.kind {
+            StmtKind::Expr(expr) => {
+                self.eval(expr)?;
+            }
+            StmtKind::Decl {
+                name,
+                ty,
+                value,
+                constant,
+            } => {
+                let mut value = self.eval(value)?;
+                if let Some(ty) = ty {
+                    value = value.coerce(ty).map_err(|kind| error(kind, &stmt.span))?;
+                }
+                self.declare(name.as_ref(), value, *constant);
+            }
+            StmtKind::Assign { name, op, value } => {
+                let mut value = self.eval(value)?;
+                if let Some(op) = op {
+                    let current = self.get_var(name).cloned().ok_or_else(|| {
+                        self.undefined(
+                            EvalErrorKind::UndefinedVariable(name.to_string()),
+                            &stmt.span,
+                        )
+                    })?;
+                    value = self
+                        .binary(current, *op, value)
+                        .map_err(|kind| error(kind, &stmt.span))?;
+                }
+                self.assign(name, value)
+                    .map_err(|kind| self.undefined(kind, &stmt.span))?;
+            }
+            StmtKind::Fn(decl) => self.define_fn(Rc::new(decl.as_ref().clone().into_owned())),
+            StmtKind::Struct(decl) => self.define_struct(Rc::new(decl.clone().into_owned())),
+            StmtKind::While(cond, body) => {
+                while self.eval_cond(cond)? {
+                    match self.exec_block(body) {
+                        Ok(_) | Err(Unwind::Continue(_)) => {}
+                        Err(Unwind::Break(_)) => break,
+                        Err(unwind) => return Err(unwind),
+                    }
+                }
+            }
+            StmtKind::For {
+                var,
+                start,
+                end,
+                inclusive,
+                body,
+            } => {
+                let start = self.eval_int(start)?;
+                let end = self.eval_int(end)?;
+                // nothing is below the smallest integer
+                let last = if *inclusive {
+                    Some(end)
+                } else {
+                    end.checked_sub(1)
+                };
+                for i in last.into_iter().flat_map(|last| start..=last) {
+                    let var = [(var.to_string(), Value::Int(i))];
+                    match self.scoped(var, |ctx| ctx.exec_block(body)) {
+                        Ok(_) | Err(Unwind::Continue(_)) => {}
+                        Err(Unwind::Break(_)) => break,
+                        Err(unwind) => return Err(unwind),
+                    }
+                }
+            }
+            StmtKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.eval(value)?,
+                    None => Value::Unit,
+                };
+                return Err(Unwind::Return(value, stmt.span.clone()));
+            }
+            StmtKind::Break => return Err(Unwind::Break(stmt.span.clone())),
+            StmtKind::Continue => return Err(Unwind::Continue(stmt.span.clone())),
+        }
+        Ok(())
+    }
+
+    fn eval_cond<'a>(&mut self, cond: &Expr<'a>) -> Exec<'a, bool> {
+        let value = self.eval(cond)?;
+        value.as_bool().ok_or_else(|| {
+            error(
+                EvalErrorKind::type_mismatch("bool", value.type_name()),
+                &cond.span,
+            )
+        })
+    }
+
+    fn eval_int<'a>(&mut self, expr: &Expr<'a>) -> Exec<'a, i64> {
+        match self.eval(expr)? {
+            Value::Int(i) => Ok(i),
+            Value::BigInt(_) => Err(error(EvalErrorKind::IntegerOverflow, &expr.span)),
+            v => Err(error(
+                EvalErrorKind::type_mismatch("int", v.type_name()),
+                &expr.span,
+            )),
+        }
+    }
+
+    fn call_fn<'a>(
+        &mut self,
+        decl: &FnDecl<'static>,
+        args: Vec<Value>,
+        span: &Span<'a>,
+    ) -> Exec<'a, Value> {
+        if args.len() != decl.params.len() {
+            let kind = EvalErrorKind::ArgumentCount {
+                name: decl.name.to_string(),
+                expected: decl.params.len().to_string(),
+                found: args.len(),
+            };
+            return Err(error(kind, span));
+        }
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(error(EvalErrorKind::RecursionLimit(MAX_CALL_DEPTH), span));
+        }
+        let mut params = Vec::with_capacity(args.len());
+        for (param, arg) in decl.params.iter().zip(args) {
+            let arg = match &param.ty {
+                Some(ty) => arg.coerce(ty).map_err(|kind| error(kind, span))?,
+                None => arg,
+            };
+            params.push((param.name.to_string(), arg));
+        }
+
+        let result = self.framed(|ctx| ctx.scoped(params, |ctx| ctx.exec_stmts(&decl.body)));
+        let value = match result {
+            Ok(value) | Err(Unwind::Return(value, _)) => value,
+            Err(unwind) => return Err(Unwind::Error(unwind.into_error())),
+        };
+        match &decl.ret {
+            Some(ty) => value.coerce(ty).map_err(|kind| error(kind, span)),
+            None => Ok(value),
+        }
+    }
+
+    fn eval<'a>(&mut self, expr: &Expr<'a>) -> Exec<'a, Value> {
+        let err = |kind: EvalErrorKind| error(kind, &expr.span);
+
+        match &expr.kind {
+            ExprKind::Int(i) => Ok(Value::from(*i)),
+            ExprKind::BigInt(i) => Ok(Value::from(BigInt::from(i.clone()))),
+            ExprKind::Float(x) => Ok(Value::Float(*x)),
+            ExprKind::Decimal(x, d) => match self.mode() {
+                Mode::Decimal => decimal::round(d.clone(), self.precision())
+                    .map(Value::from)
+                    .map_err(err),
+                _ => Ok(Value::Float(*x)),
+            },
+            ExprKind::Imag(x) => Ok(Value::from(Complex64::new(0.0, *x))),
+            ExprKind::Quantity(value, units) => {
+                let number = self.eval(value)?;
+                let number = number.as_f64().ok_or_else(|| {
+                    let kind = EvalErrorKind::type_mismatch("real number", number.type_name());
+                    error(kind, &value.span)
+                })?;
+                let units = units
+                    .iter()
+                    .map(|(unit, exp)| {
+                        let name = unit.value.to_string();
+                        // `h := 2; 5 h` could mean either
+                        if self.get_var(&name).is_some() {
+                            return Err(error(EvalErrorKind::AmbiguousUnit(name), &unit.span));
+                        }
+                        match Unit::lookup(&name) {
+                            Some(found) => Ok((found, *exp)),
+                            None => Err(error(EvalErrorKind::UnknownUnit(name), &unit.span)),
+                        }
+                    })
+                    .collect::<Exec<Vec<_>>>()?;
+                let quantity =
+                    Quantity::new(number, units).map_err(|kind| error(kind, &expr.span))?;
+                Ok(Value::from(quantity))
+            }
+            ExprKind::Bool(b) => Ok(Value::Bool(*b)),
+            ExprKind::Ident(name) => self.get_var(name).cloned().ok_or_else(|| {
+                self.undefined(
+                    EvalErrorKind::UndefinedVariable(name.to_string()),
+                    &expr.span,
+                )
+            }),
+            ExprKind::Unary(op, operand) => {
+                let v = self.eval(operand)?;
+                v.unary(*op).map_err(err)
+            }
+            ExprKind::Binary(op @ (BinOp::LogicAnd | BinOp::LogicOr), lhs, rhs) => {
+                let l = self.eval(lhs)?;
+                match (op, &l) {
+                    (BinOp::LogicAnd, Value::Bool(false)) => Ok(l),
+                    (BinOp::LogicOr, Value::Bool(true)) => Ok(l),
+                    _ => {
+                        let r = self.eval(rhs)?;
+                        l.binary(*op, r).map_err(err)
+                    }
+                }
+            }
+            ExprKind::Binary(op, lhs, rhs) => {
+                let l = self.eval(lhs)?;
+                let r = self.eval(rhs)?;
+                self.binary(l, *op, r).map_err(err)
+            }
+            ExprKind::Call(callee, args) => {
+                let ExprKind::Ident(name) = &callee.kind else {
+                    return Err(err(EvalErrorKind::NotCallable));
+                };
+                if let Some(decl) = self.get_fn(name).cloned() {
+                    let args = self.eval_args(args)?;
+                    return self.call_fn(&decl, args, &expr.span);
+                }
+                if let Some(decl) = self.get_struct(name).cloned() {
+                    let args = self.eval_args(args)?;
+                    return Instance::new(decl, args).map(Value::from).map_err(err);
+                }
+                let builtin = *self.get_builtin(name).ok_or_else(|| {
+                    self.undefined(
+                        EvalErrorKind::UndefinedFunction(name.to_string()),
+                        &expr.span,
+                    )
+                })?;
+                let args = self.eval_args(args)?;
+                match self.mode() {
+                    Mode::Decimal => builtin.call_decimal(&args, self.precision()),
+                    _ => builtin.call(&args),
+                }
+                .map_err(err)
+            }
+            ExprKind::Field(target, name) => match self.eval(target)? {
+                Value::Struct(s) => s.field(name).cloned().ok_or_else(|| {
+                    let kind = EvalErrorKind::NoField {
+                        name: name.to_string(),
+                        ty: s.name().to_owned(),
+                    };
+                    let fields: Vec<_> = s.fields().map(|(field, _)| field).collect();
+                    let suggestions = closest_words(name, &fields);
+                    let err = EvalError::new(kind, expr.span.clone()).with_suggestions(suggestions);
+                    Unwind::Error(Error::eval_error(err))
+                }),
+                v => Err(err(EvalErrorKind::NoField {
+                    name: name.to_string(),
+                    ty: v.type_name().to_owned(),
+                })),
+            },
+            ExprKind::Block(block) => self.exec_block(block),
+            ExprKind::Format(inner, format) => {
+                let value = self.eval(inner)?;
+                if !format.supports(&value) {
+                    let name = format.notation().name();
+                    return Err(err(EvalErrorKind::IntegerFormat(name)));
+                }
+                Ok(value)
+            }
+            ExprKind::If(branches, otherwise) => {
+                for (cond, block) in branches {
+                    if self.eval_cond(cond)? {
+                        return self.exec_block(block);
+                    }
+                }
+                match otherwise {
+                    Some(block) => self.exec_block(block),
+                    None => Ok(Value::Unit),
+                }
+            }
+        }
+    }
+
+    /// A binary operator in the current [`Mode`].
+    fn binary(
+        &self,
+        lhs: Value,
+        op: BinOp,
+        rhs: Value,
+    ) -> std::result::Result<Value, EvalErrorKind> {
+        match self.mode() {
+            Mode::Float => lhs.binary(op, rhs),
+            Mode::Exact => lhs.binary_exact(op, rhs),
+            Mode::Decimal => lhs.binary_decimal(op, rhs, self.precision()),
+        }
+    }
+
+    /// The error for an unknown name, suggesting the names in reach that
+    /// look like it. Other errors are passed through.
+    fn undefined<'a>(&self, kind: EvalErrorKind, span: &Span<'a>) -> Unwind<'a> {
+        let (name, mut candidates): (_, Vec<_>) = match &kind {
+            EvalErrorKind::UndefinedVariable(name) => (name, self.var_names().collect()),
+            EvalErrorKind::UndefinedFunction(name) => (name, self.fn_names().collect()),
+            _ => return error(kind, span),
+        };
+        candidates.extend(KEYWORDS);
+        let suggestions = closest_words(name, &candidates);
+        let err = EvalError::new(kind, span.clone()).with_suggestions(suggestions);
+        Unwind::Error(Error::eval_error(err))
+    }
+
+    fn eval_args<'a>(&mut self, args: &[Expr<'a>]) -> Exec<'a, Vec<Value>> {
+        args.iter().map(|arg| self.eval(arg)).collect()
+    }
+}
@@ -0,0 +1,236 @@
+//! Quantities: floats with physical units.
+//!
+//! A [`Quantity`] is a float and a product of units from [`crate::units`]
+//! with exponents, `9.81 m*s^-2`. Arithmetic keeps the units the operands
+//! were written in where it can, and [`Quantity::simplify`] cancels and
+//! merges them.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use super::error::EvalErrorKind;
+use crate::units::{Dimension, Unit};
+
+type UnitResult = std::result::Result<Quantity, EvalErrorKind>;
+
+/// A number of units, `5 km/h`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantity {
+    value: f64,
+    /// Distinct units with exponents that are not zero.
+    units: Vec<(Unit, i32)>,
+}
+
+impl Quantity {
+    /// `value` of the product of `units`, simplified. Fails when the powers
+    /// of the units or of their dimension leave the range of `i8`.
+    pub fn new(value: f64, units: impl IntoIterator<Item = (Unit, i32)>) -> UnitResult {
+        let mut q = Self {
+            value,
+            units: Vec::new(),
+        };
+        for (unit, exp) in units {
+            q.push(unit, exp)?;
+        }
+        q.checked()
+    }
+
+    /// The number of units.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// The dimension of the units.
+    pub fn dim(&self) -> Dimension {
+        self.checked_dim()
+            .expect("quantities are checked to have a dimension")
+    }
+
+    /// The dimension of the units, `None` when a power is out of range.
+    fn checked_dim(&self) -> Option<Dimension> {
+        self.units
+            .iter()
+            .try_fold(Dimension::default(), |dim, (u, e)| {
+                Dimension::exponent(*e)?;
+                dim.mul(u.dim().pow(*e)?)
+            })
+    }
+
+    /// Simplified, or the error for powers out of range.
+    fn checked(mut self) -> UnitResult {
+        self.simplify();
+        match self.checked_dim() {
+            Some(_) => Ok(self),
+            None => Err(EvalErrorKind::UnitPower(self.unit())),
+        }
+    }
+
+    /// Whether all the units cancelled out.
+    pub fn is_number(&self) -> bool {
+        self.units.is_empty()
+    }
+
+    /// The units, as in `kg*m/s^2`. Plain numbers have the unit `1`.
+    pub fn unit(&self) -> String {
+        let num: Vec<_> = self.units.iter().filter(|(_, e)| *e > 0).collect();
+        let den: Vec<_> = self.units.iter().filter(|(_, e)| *e < 0).collect();
+        let power = |(u, e): &(Unit, i32)| match e {
+            1 => u.to_string(),
+            e => format!("{u}^{e}"),
+        };
+        if num.is_empty() {
+            // `1/s` would not read back as a unit
+            if den.is_empty() {
+                return "1".to_owned();
+            }
+            return den.iter().map(|u| power(u)).collect::<Vec<_>>().join("*");
+        }
+        let mut out = num.iter().map(|u| power(u)).collect::<Vec<_>>().join("*");
+        for (u, e) in den {
+            out.push('/');
+            out.push_str(&power(&(*u, -e)));
+        }
+        out
+    }
+
+    /// The value in SI units, from zero kelvin for a lone temperature in a
+    /// scale with an offset.
+    pub fn to_si(&self) -> f64 {
+        match self.units[..] {
+            [(u, 1)] => self.value * u.factor() + u.offset(),
+            _ => self.value * self.factor(),
+        }
+    }
+
+    /// Compares the values in SI units, equal when they are a few rounding
+    /// errors apart, as the offsets of temperature scales make them.
+    pub fn compare_si(&self, rhs: &Self) -> Option<Ordering> {
+        let (a, b) = (self.to_si(), rhs.to_si());
+        if (a - b).abs() <= 4.0 * f64::EPSILON * a.abs().max(b.abs()) {
+            return Some(Ordering::Equal);
+        }
+        a.partial_cmp(&b)
+    }
+
+    /// How many SI units one of these units is worth.
+    fn factor(&self) -> f64 {
+        self.units
+            .iter()
+            .map(|(u, e)| u.factor().powi(*e))
+            .product()
+    }
+
+    /// The same units with another value.
+    pub fn with_value(mut self, value: f64) -> Self {
+        self.value = value;
+        self
+    }
+
+    pub fn scale(mut self, x: f64) -> Self {
+        self.value *= x;
+        self
+    }
+
+    /// The product, failing like [`Quantity::new`].
+    pub fn times(mut self, rhs: &Self) -> UnitResult {
+        self.value *= rhs.value;
+        for (unit, exp) in &rhs.units {
+            self.push(*unit, *exp)?;
+        }
+        self.checked()
+    }
+
+    pub fn recip(mut self) -> Self {
+        self.value = self.value.recip();
+        for (_, exp) in &mut self.units {
+            *exp = -*exp;
+        }
+        self
+    }
+
+    /// The power, failing for exponents that would leave fractional powers
+    /// of units and like [`Quantity::new`].
+    pub fn pow(mut self, exp: f64) -> UnitResult {
+        let unit = self.unit();
+        for (_, e) in &mut self.units {
+            let power = f64::from(*e) * exp;
+            if power.fract() != 0.0 || power.abs() > f64::from(i8::MAX) {
+                return Err(EvalErrorKind::UnitPower(unit));
+            }
+            *e = power as i32;
+        }
+        self.value = self.value.powf(exp);
+        self.checked().map_err(|_| EvalErrorKind::UnitPower(unit))
+    }
+
+    /// `rhs` in the units of `self`, as a difference so that temperatures
+    /// add in degrees. Fails for other dimensions.
+    pub fn convert(
+        &self,
+        rhs: &Self,
+        op: impl ToString,
+    ) -> std::result::Result<f64, EvalErrorKind> {
+        if self.dim() != rhs.dim() {
+            return Err(EvalErrorKind::IncompatibleUnits {
+                op: op.to_string(),
+                lhs: self.unit(),
+                rhs: rhs.unit(),
+            });
+        }
+        Ok(rhs.value * rhs.factor() / self.factor())
+    }
+
+    /// Adds `unit^exp` to the product, failing for an `exp` out of the range
+    /// of `i8`. Sums of those are checked by [`Quantity::checked`].
+    fn push(&mut self, unit: Unit, exp: i32) -> std::result::Result<(), EvalErrorKind> {
+        if Dimension::exponent(exp).is_none() {
+            return Err(EvalErrorKind::UnitPower(format!("{unit}^{exp}")));
+        }
+        match self.units.iter_mut().find(|(u, _)| *u == unit) {
+            Some((_, e)) => *e += exp,
+            None => self.units.push((unit, exp)),
+        }
+        self.units.retain(|(_, e)| *e != 0);
+        Ok(())
+    }
+
+    /// Cancels units of the same dimension into the first one, and names
+    /// products of several base units that make up a derived SI unit,
+    /// `kg*m/s^2` becoming `N`.
+    pub fn simplify(&mut self) {
+        let mut i = 0;
+        while i < self.units.len() {
+            let (unit, _) = self.units[i];
+            let mut j = i + 1;
+            while j < self.units.len() {
+                let (other, exp) = self.units[j];
+                if other.dim() == unit.dim() {
+                    self.value *= (other.factor() / unit.factor()).powi(exp);
+                    self.units[i].1 += exp;
+                    self.units.remove(j);
+                } else {
+                    j += 1;
+                }
+            }
+            if self.units[i].1 == 0 {
+                self.units.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+        // `W*h` is an energy too, but better left as written
+        if self.units.len() < 2 || !self.units.iter().all(|(u, _)| u.dim().is_base()) {
+            return;
+        }
+        if let Some(unit) = self.checked_dim().and_then(Unit::derived) {
+            self.value *= self.factor();
+            self.units = vec![(unit, 1)];
+        }
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} {}", self.value, self.unit())
+    }
+}
@@ -0,0 +1,750 @@
+use super::{
+    context::{AngleUnit, Context, Mode},
+    decimal::{Precision, Rounding},
+    error::EvalErrorKind,
+    value::Value,
+};
+use crate::{error::Error, eval, eval_with, Expr, ExprKind, Format, Lexer, Parser, Spanned};
+use num_complex::Complex64;
+use std::borrow::Cow;
+
+/// Evaluates in the float mode, most tests being about floats. The default
+/// decimal mode is the one of [`decimal_mode`].
+fn float_eval(source: &str) -> crate::Result<'_, Value> {
+    let mut ctx = Context::new();
+    ctx.set_mode(Mode::Float);
+    eval_with(source, &mut ctx)
+}
+
+fn test_eval(source: &str, expect: impl Into<Value>) {
+    match float_eval(source) {
+        Ok(v) => assert_eq!(v, expect.into(), "source: {source:?}"),
+        Err(err) => panic!("{source:?}: {err}"),
+    }
+}
+
+fn eval_err(source: &str) -> EvalErrorKind {
+    match float_eval(source) {
+        Err(Error::EvalError(err)) => err.kind().clone(),
+        other => panic!("{source:?}: expected eval error, got {other:?}"),
+    }
+}
+
+#[test]
+fn arithmetic() {
+    test_eval("1 + 2 * 3", 7);
+    test_eval("(1 + 2) * 3", 9);
+    test_eval("2 ^ 10", 1024);
+    test_eval("2 ^ 3 ^ 2", 512);
+    test_eval("-2 ^ 2", -4);
+    test_eval("2 ^ -1", 0.5);
+    test_eval("7 / 2", 3.5);
+    test_eval("7 // 2", 3);
+    test_eval("-7 // 2", -4);
+    test_eval("-7 % 3", 2);
+    test_eval("7 % -3", -2);
+    test_eval("7.5 // 2", 3.0);
+    test_eval("1 + 0.5", 1.5);
+    test_eval(
+        "4.20 * ((69 + 69_420) >> 69 - 60)",
+        4.20 * ((69.0 + 69420.0) / 512.0f64).floor(),
+    );
+}
+
+#[test]
+fn bitwise() {
+    test_eval("1 << 4", 16);
+    test_eval("0xff >> 4", 15);
+    test_eval("0b1100 & 0o12", 8);
+    test_eval("12 | 3", 15);
+    test_eval("12 ^^ 10", 6);
+    test_eval("12 ~ 10", !6);
+    test_eval("~0", -1);
+    test_eval("!0", -1);
+}
+
+#[test]
+fn big_ints() {
+    let big = |digits: &str| Value::BigInt(digits.parse().unwrap());
+    test_eval("9223372036854775807 + 1", big("9223372036854775808"));
+    test_eval("9223372036854775808 - 1", i64::MAX);
+    test_eval("-9223372036854775808", i64::MIN);
+    test_eval("18446744073709551616", big("18446744073709551616"));
+    test_eval("0x1_0000_0000_0000_0000 == 1 << 64", true);
+    test_eval("2 ^ 100", big("1267650600228229401496703205376"));
+    test_eval("2 ^ 100 // 2 ^ 98", 4);
+    test_eval("-(2 ^ 70) // 3", big("-393530540239137101142"));
+    test_eval("-(2 ^ 70) % 3", 2);
+    test_eval("(2 ^ 64 + 5) % 2 ^ 64", 5);
+    test_eval("(1 << 100) >> 99", 2);
+    test_eval("-(1 << 100) >> 200", -1);
+    test_eval("(1 << 64 | 1) & 3", 1);
+    test_eval("~(1 << 64)", big("-18446744073709551617"));
+    test_eval("(1 << 64) ^^ (1 << 64)", 0);
+    test_eval("2 ^ 64 > 2 ^ 63 && -(2 ^ 64) < 0", true);
+    test_eval("2 ^ 64 / 2", 9223372036854775808.0);
+    test_eval("2 ^ 64 * 1.0", 18446744073709551616.0);
+    test_eval("abs(-9223372036854775807 - 1)", big("9223372036854775808"));
+    test_eval("int(1e20)", big("100000000000000000000"));
+    test_eval("(-1) ^ (2 ^ 65 + 1)", -1);
+    test_eval("x :u64= 2 ^ 64 - 1; x", big("18446744073709551615"));
+    assert_eq!(eval_err("x :i64= 2 ^ 63"), EvalErrorKind::IntegerOverflow);
+    assert_eq!(eval_err("2 ^ 64 // 0"), EvalErrorKind::DivisionByZero);
+    assert_eq!(eval_err("1 << (1 << 64)"), EvalErrorKind::IntegerOverflow);
+}
+
+#[test]
+fn logic() {
+    test_eval("1 < 2 && 2 <= 2", true);
+    test_eval("1 > 2 || 3 >= 4", false);
+    test_eval("1 == 1.0", true);
+    test_eval("true != false", true);
+    test_eval("!(1 > 2)", true);
+    test_eval("false && undefined", false);
+    test_eval("true || undefined", true);
+}
+
+#[test]
+fn builtins() {
+    test_eval("sqrt(16)", 4.0);
+    test_eval("abs(-3)", 3);
+    test_eval("max(1, 5.5, 3)", 5.5);
+    test_eval("min(4, 2, 8)", 2);
+    test_eval("floor(2.7) + ceil(2.2)", 5.0);
+    test_eval("log(8, 2)", 3.0);
+    test_eval("int(-2.9)", -2);
+    test_eval("float(true)", 1.0);
+    test_eval("pow(3, 3)", 27);
+    test_eval("rational(4, -2)", -2);
+    test_eval("rational(2, 6) == 1 / 3 && decimal(15, -2) == 0.15", true);
+    assert_eq!(eval_err("rational(1, 0)"), EvalErrorKind::DivisionByZero);
+    assert_eq!(
+        eval_err("decimal(1.5, 0)"),
+        EvalErrorKind::type_mismatch("int", "float")
+    );
+}
+
+#[test]
+fn context() {
+    let mut ctx = Context::new();
+    ctx.set_var("x", 3);
+    ctx.set_var("y", 0.5);
+    assert_eq!(
+        eval_with("x * y + pi", &mut ctx).unwrap(),
+        Value::Float(1.5 + std::f64::consts::PI)
+    );
+
+    let mut ctx = Context::empty();
+    assert!(eval_with("sqrt(4)", &mut ctx).is_err());
+}
+
+#[test]
+fn errors() {
+    assert_eq!(eval_err("1 // 0"), EvalErrorKind::DivisionByZero);
+    assert_eq!(eval_err("1 / 0"), EvalErrorKind::DivisionByZero);
+    assert_eq!(eval_err("3 ^ (1 << 40)"), EvalErrorKind::IntegerOverflow);
+    assert_eq!(
+        eval_err("1 << -1"),
+        EvalErrorKind::InvalidShift("-1".into())
+    );
+    assert_eq!(
+        eval_err("foo + 1"),
+        EvalErrorKind::UndefinedVariable("foo".into())
+    );
+    assert_eq!(
+        eval_err("foo(1)"),
+        EvalErrorKind::UndefinedFunction("foo".into())
+    );
+    assert!(matches!(
+        eval_err("1 + true"),
+        EvalErrorKind::InvalidOperands { .. }
+    ));
+    assert!(matches!(
+        eval_err("-true"),
+        EvalErrorKind::InvalidOperand { .. }
+    ));
+    assert!(matches!(
+        eval_err("1 && true"),
+        EvalErrorKind::InvalidOperands { .. }
+    ));
+    assert!(matches!(
+        eval_err("sqrt(1, 2)"),
+        EvalErrorKind::ArgumentCount { .. }
+    ));
+    assert!(matches!(
+        eval_err("sqrt(true)"),
+        EvalErrorKind::TypeMismatch { .. }
+    ));
+    assert!(matches!(eval_err("pi.x"), EvalErrorKind::NoField { .. }));
+    assert_eq!(eval_err("2.5 -> bin"), EvalErrorKind::IntegerFormat("bin"));
+    assert_eq!(
+        eval_err("1 / 3 -> oct"),
+        EvalErrorKind::IntegerFormat("oct")
+    );
+    assert_eq!(
+        eval_err("3 m / 2 -> bin _"),
+        EvalErrorKind::IntegerFormat("bin")
+    );
+    test_eval("6.0 / 2 -> bin", 3.0);
+    test_eval("2.5 -> hex", 2.5);
+    assert!(matches!(eval(")"), Err(Error::ParsingError(_))));
+}
+
+#[test]
+fn statements() {
+    test_eval("x := 2; x += 3; x *= x; x", 25);
+    test_eval("x := 1; { x := 10; x += 1; }; x", 1);
+    test_eval("x := 1; { x = 10; }; x", 10);
+    test_eval("y :f32= 1; y", 1.0);
+    test_eval("n :u8= 200; n", 200);
+    test_eval("x := 1;", Value::Unit);
+    test_eval("if 1 > 2 { 1 } elif 2 > 1 { 2 } else { 3 }", 2);
+    test_eval("if false { 1 }", Value::Unit);
+    test_eval("1 + if true { 1 } else { 2 }", 2);
+
+    let mut ctx = Context::new();
+    eval_with("total := 0; limit :: 3", &mut ctx).unwrap();
+    eval_with("total += limit", &mut ctx).unwrap();
+    assert_eq!(ctx.get_var("total"), Some(&Value::Int(3)));
+    assert!(ctx.is_const("limit"));
+
+    // one statement at a time, as the JSON mode runs them
+    let program = Parser::new(Lexer::new("x := 4; x * 2; break;"))
+        .and_then(|mut parser| parser.parse_program())
+        .unwrap();
+    let mut ctx = Context::new();
+    let values: Vec<_> = program
+        .stmts
+        .iter()
+        .map(|stmt| ctx.run_stmt(stmt))
+        .collect();
+    assert!(matches!(values[..2], [Ok(Value::Unit), Ok(Value::Int(8))]));
+    assert!(matches!(values[2], Err(Error::EvalError(_))));
+}
+
+#[test]
+fn loops() {
+    test_eval("s := 0; for i in 0..5 { s += i; }; s", 10);
+    test_eval("s := 0; for i in 1..=5 { s += i; }; s", 15);
+    test_eval(
+        "n := 0; for k in 9223372036854775806..=9223372036854775807 { n += 1; }; n",
+        2,
+    );
+    test_eval("n := 0; for k in 0..-9223372036854775808 { n += 1; }; n", 0);
+    test_eval(
+        "s := 0; i := 0; while true { i += 1; if i % 2 == 0 { continue; }; if i > 7 { break; }; s += i; }; s",
+        16,
+    );
+    test_eval(
+        "n := 0; for i in 0..3 { for j in 0..3 { if j > i { break; }; n += 1; } }; n",
+        6,
+    );
+}
+
+#[test]
+fn functions() {
+    test_eval("sq :: (x) { x * x } sq(7)", 49);
+    test_eval(
+        "fib :: (n) { if n < 2 { return n; }; fib(n - 1) + fib(n - 2) } fib(15)",
+        610,
+    );
+    test_eval("half :: (x :float) -> float { x / 2 } half(3)", 1.5);
+    test_eval("to_int :: (x) -> int { x } to_int(3)", 3);
+    // calls only see globals and their own locals
+    test_eval("a := 1; f :: () { a } { a := 2; f() }", 1);
+    test_eval("sqrt :: (x) { x } sqrt(4)", 4);
+
+    let mut ctx = Context::new();
+    eval_with("twice :: (x) { 2 * x }", &mut ctx).unwrap();
+    assert_eq!(eval_with("twice(21)", &mut ctx).unwrap(), Value::Int(42));
+    assert_eq!(ctx.functions().count(), 1);
+}
+
+#[test]
+fn structs() {
+    let mut ctx = Context::new();
+    eval_with(
+        "Point :: struct { x; y :float } p := Point(1, 2); norm :: (p) { hypot(p.x, p.y) }",
+        &mut ctx,
+    )
+    .unwrap();
+    let p = eval_with("p", &mut ctx).unwrap();
+    assert_eq!(p.type_name(), "struct");
+    assert_eq!(p.to_string(), "Point { x: 1, y: 2.0 }");
+    assert_eq!(Format::default().value(&p), "Point { x: 1, y: 2.0 }");
+    assert_eq!(eval_with("p.x", &mut ctx).unwrap(), Value::Int(1));
+    assert_eq!(
+        eval_with("norm(Point(3, 4))", &mut ctx).unwrap(),
+        Value::Float(5.0)
+    );
+    assert_eq!(ctx.structs().count(), 1);
+
+    match eval_with("p.z", &mut ctx) {
+        Err(Error::EvalError(err)) => assert_eq!(
+            err.kind(),
+            &EvalErrorKind::NoField {
+                name: "z".into(),
+                ty: "Point".into()
+            }
+        ),
+        other => panic!("expected eval error, got {other:?}"),
+    }
+    assert!(matches!(
+        eval_with("Point(1)", &mut ctx),
+        Err(Error::EvalError(err)) if matches!(err.kind(), EvalErrorKind::ArgumentCount { .. })
+    ));
+    assert!(matches!(
+        eval_with("Point(1, true)", &mut ctx),
+        Err(Error::EvalError(err)) if matches!(err.kind(), EvalErrorKind::TypeMismatch { .. })
+    ));
+    // the last declaration of a name wins
+    eval_with("Point :: (a, b) { a + b }", &mut ctx).unwrap();
+    assert_eq!(eval_with("Point(1, 2)", &mut ctx).unwrap(), Value::Int(3));
+    assert_eq!(ctx.structs().count(), 0);
+    assert_eq!(
+        eval_err("P :: struct { x } P(1).x.y"),
+        EvalErrorKind::NoField {
+            name: "y".into(),
+            ty: "int".into()
+        }
+    );
+}
+
+#[test]
+fn statement_errors() {
+    assert_eq!(
+        eval_err("c :: 1; c = 2"),
+        EvalErrorKind::AssignToConstant("c".into())
+    );
+    assert_eq!(
+        eval_err("pi = 3"),
+        EvalErrorKind::AssignToConstant("pi".into())
+    );
+    assert_eq!(
+        eval_err("x = 1"),
+        EvalErrorKind::UndefinedVariable("x".into())
+    );
+    assert_eq!(
+        eval_err("x := 1; { y := 2; }; y"),
+        EvalErrorKind::UndefinedVariable("y".into())
+    );
+    assert_eq!(eval_err("break"), EvalErrorKind::OutsideLoop("break"));
+    assert_eq!(
+        eval_err("f :: () { continue; } for i in 0..2 { f(); }"),
+        EvalErrorKind::OutsideLoop("continue")
+    );
+    assert_eq!(eval_err("return 1"), EvalErrorKind::ReturnOutsideFunction);
+    assert_eq!(
+        eval_err("f :: (n) { f(n + 1) } f(0)"),
+        EvalErrorKind::RecursionLimit(super::MAX_CALL_DEPTH)
+    );
+    assert!(matches!(
+        eval_err("f :: (a, b) { a } f(1)"),
+        EvalErrorKind::ArgumentCount { .. }
+    ));
+    assert_eq!(eval_err("x :u8= 256"), EvalErrorKind::IntegerOverflow);
+    assert_eq!(
+        eval_err("x :string= 1"),
+        EvalErrorKind::UnknownType("string".into())
+    );
+    assert!(matches!(
+        eval_err("if 1 { 2 }"),
+        EvalErrorKind::TypeMismatch { .. }
+    ));
+    assert!(matches!(
+        eval_err("for i in 0..1.5 {}"),
+        EvalErrorKind::TypeMismatch { .. }
+    ));
+}
+
+#[test]
+fn suggestions() {
+    let suggestions = |source| match eval(source) {
+        Err(Error::EvalError(err)) => err.suggestions().to_vec(),
+        other => panic!("{source:?}: expected eval error, got {other:?}"),
+    };
+    assert_eq!(suggestions("pie * 2"), ["pi"]);
+    assert_eq!(suggestions("sqr(4)"), ["sqrt"]);
+    assert_eq!(suggestions("retrun(1)"), ["return"]);
+    assert_eq!(suggestions("total := 1; totl += 1"), ["total"]);
+    assert_eq!(suggestions("f :: (count) { cuont } f(1)"), ["count"]);
+    assert_eq!(
+        suggestions("P :: struct { width; height } P(1, 2).hieght"),
+        ["height"]
+    );
+    assert_eq!(suggestions("P :: struct { x } Pp(1)"), ["P"]);
+    assert!(suggestions("P :: struct { x } P(1).size").is_empty());
+    assert!(suggestions("zzz").is_empty());
+
+    let mut ctx = Context::new();
+    eval_with("radius := 2", &mut ctx).unwrap();
+    match eval_with("{ radios := 1; }; radis", &mut ctx) {
+        Err(Error::EvalError(err)) => assert_eq!(err.suggestions(), ["radius"]),
+        other => panic!("expected eval error, got {other:?}"),
+    }
+}
+
+#[test]
+fn to_source() {
+    let mut ctx = Context::new();
+    ctx.set_mode(Mode::Float);
+    eval_with(
+        "rate :: 0.15; n := -3; big := -9223372036854775807 - 1; on := true; none := {};
+        huge := 1e999; nan := 0.0 / 0.0; pi := 3;
+        tax :: (x :float) -> float { if x > 100 { x * rate } else { 0.0 } }
+        fact :: (n) { r := 1; for i in 1..=n { r *= i; }; r }",
+        &mut ctx,
+    )
+    .unwrap();
+    ctx.set_mode(Mode::Exact);
+    eval_with(
+        "third := -1 / 3; z := 3.0 - 4.5i; w := sqrt(-1) * 1e999",
+        &mut ctx,
+    )
+    .unwrap();
+    ctx.set_mode(Mode::Decimal);
+    eval_with(
+        "price := 1.10 * 2; tiny := 1.5e-40; speed := 90 km / 2 h; cold := -4.5 degC;
+        Point :: struct { x; y :float } corner := Point(1.5, third)",
+        &mut ctx,
+    )
+    .unwrap();
+    let source = ctx.to_source();
+    assert!(source.starts_with(
+        "Point :: struct { x; y :float }\ncold := -4.5f64 degC;\nspeed := 45.0f64 km/h;\nbig := "
+    ));
+    assert!(source.contains("corner := Point(decimal(15, -1), -0.3333333333333333f64);\n"));
+    assert!(source.contains("huge := 1e999f64;\n"));
+    assert!(source.contains("third := rational(-1, 3);\n"));
+    assert!(source.contains("price := decimal(220, -2);\n"));
+    assert!(!source.contains("tau"));
+
+    // the literals do not depend on the mode
+    for mode in [Mode::Float, Mode::Exact, Mode::Decimal] {
+        let mut restored = Context::new();
+        restored.set_mode(mode);
+        eval_with(&source, &mut restored).unwrap();
+        assert_eq!(restored.to_source(), source, "{mode:?}");
+        for name in [
+            "third", "price", "tiny", "speed", "cold", "z", "rate", "corner",
+        ] {
+            assert_eq!(
+                restored.get_var(name),
+                ctx.get_var(name),
+                "{name} in {mode:?}"
+            );
+        }
+    }
+
+    let mut restored = Context::new();
+    restored.set_mode(Mode::Float);
+    eval_with(&source, &mut restored).unwrap();
+    assert!(restored.is_const("rate") && !restored.is_const("pi"));
+    assert_eq!(restored.get_var("big"), Some(&Value::Int(i64::MIN)));
+    assert_eq!(restored.get_var("none"), Some(&Value::Unit));
+    assert!(matches!(restored.get_var("nan"), Some(Value::Float(x)) if x.is_nan()));
+    assert_eq!(
+        eval_with("tax(200) + fact(5)", &mut restored).unwrap(),
+        Value::Float(150.0)
+    );
+
+    // quantities come before the variables named like their units
+    let mut ctx = Context::new();
+    eval_with(
+        "pace := 4 min/km; speed := 5 km/h; h := 2; min := 3 km",
+        &mut ctx,
+    )
+    .unwrap();
+    let source = ctx.to_source();
+    assert_eq!(
+        source,
+        "pace := 4.0f64 min/km;\nspeed := 5.0f64 km/h;\nmin := 3.0f64 km;\nh := 2;\n"
+    );
+    let mut restored = Context::new();
+    eval_with(&source, &mut restored).unwrap();
+    assert_eq!(restored.to_source(), source);
+
+    assert_eq!(Context::new().to_source(), "");
+}
+
+#[test]
+fn angle_units() {
+    let close = |ctx: &mut Context, source: &str, expect: f64| match eval_with(source, ctx) {
+        Ok(Value::Float(x)) => assert!((x - expect).abs() < 1e-12, "{source}: {x}"),
+        other => panic!("{source}: {other:?}"),
+    };
+    let mut ctx = Context::new();
+    ctx.set_mode(Mode::Float);
+    close(&mut ctx, "sin(pi / 2)", 1.0);
+    ctx.set_precision(Precision::default());
+    ctx.set_angle_unit(AngleUnit::Deg);
+    assert_eq!(ctx.angle_unit(), AngleUnit::Deg);
+    close(&mut ctx, "sin(90)", 1.0);
+    close(&mut ctx, "cos(180)", -1.0);
+    close(&mut ctx, "atan2(1, 1)", 45.0);
+    close(&mut ctx, "asin(0.5)", 30.0);
+    ctx.set_angle_unit(AngleUnit::Grad);
+    close(&mut ctx, "tan(50)", 1.0);
+    close(&mut ctx, "acos(0)", 100.0);
+    ctx.set_angle_unit(AngleUnit::Rad);
+    close(&mut ctx, "acos(-1)", std::f64::consts::PI);
+    // hyperbolic functions do not take angles
+    ctx.set_precision(Precision::default());
+    ctx.set_angle_unit(AngleUnit::Deg);
+    close(&mut ctx, "sinh(0)", 0.0);
+    assert_eq!("grad".parse(), Ok(AngleUnit::Grad));
+    assert!("turns".parse::<AngleUnit>().is_err());
+}
+
+#[test]
+fn exact_mode() {
+    let mut ctx = Context::new();
+    ctx.set_mode(Mode::Exact);
+    let mut exact = |source: &str| match eval_with(source, &mut ctx) {
+        Ok(v) => Format::default().value(&v),
+        Err(Error::EvalError(err)) => err.kind().to_string(),
+        Err(err) => panic!("{source:?}: {err}"),
+    };
+    assert_eq!(exact("1/3 + 1/6"), "1/2");
+    assert_eq!(exact("1/3 * 3"), "1");
+    assert_eq!(exact("-4 / 6"), "-2/3");
+    assert_eq!(exact("2 ^ -2"), "1/4");
+    assert_eq!(exact("(2/3) ^ -3"), "27/8");
+    assert_eq!(exact("(7/2) // 1"), "3");
+    assert_eq!(exact("(7/2) % (2/3)"), "1/6");
+    assert_eq!(exact("floor(-7/2)"), "-4");
+    assert_eq!(exact("round(5/2)"), "3");
+    assert_eq!(exact("abs(-1/3) == 1/3"), "true");
+    assert_eq!(exact("1/3 < 0.34 && 1/3 > 1/4"), "true");
+    assert_eq!(exact("1/4 + 0.5"), "0.75");
+    assert_eq!(exact("float(1/8)"), "0.125");
+    assert_eq!(exact("x := 1; x /= 3; x"), "1/3");
+    assert_eq!(exact("2 ^ 100 / 2 ^ 101"), "1/2");
+    assert_eq!(exact("1 / 0"), "division by zero");
+    assert_eq!(exact("0 ^ -1"), "division by zero");
+
+    test_eval("1 / 4", 0.25);
+    test_eval("2 ^ -1", 0.5);
+    assert_eq!("exact".parse(), Ok(Mode::Exact));
+    assert!("ratio".parse::<Mode>().is_err());
+}
+
+#[test]
+fn complex_numbers() {
+    let complex = |source: &str| match eval(source) {
+        Ok(v) => Format::default().value(&v),
+        Err(Error::EvalError(err)) => err.kind().to_string(),
+        Err(err) => panic!("{source:?}: {err}"),
+    };
+    test_eval("3 + 4i", Complex64::new(3.0, 4.0));
+    test_eval("sqrt(-4)", Complex64::new(0.0, 2.0));
+    test_eval("(1 + 2i) * (3 - 4i)", Complex64::new(11.0, 2.0));
+    test_eval("(1 + 2i) / (3 - 4i)", Complex64::new(-0.2, 0.4));
+    test_eval("1i ^ 2", Complex64::new(-1.0, 0.0));
+    test_eval("abs(3 + 4i)", 5.0);
+    test_eval("conj(3 + 4i) == 3 - 4i", true);
+    test_eval("re(3 + 4i)", 3.0);
+    test_eval("im(3 + 4i)", 4.0);
+    test_eval("im(5)", 0);
+    test_eval("arg(-1)", std::f64::consts::PI);
+    test_eval("1i == 1", false);
+    assert_eq!(complex("2.5i"), "2.5i");
+    assert_eq!(complex("1 - 1e-3i"), "1.0-0.001i");
+    assert_eq!(
+        complex("1i < 2"),
+        "unsupported operand types complex and int for `<`"
+    );
+    assert_eq!(complex("sin(1i)"), "expected real number, got complex");
+    assert_eq!(
+        complex("1i % 2"),
+        "unsupported operand types complex and int for `%`"
+    );
+
+    let mut ctx = Context::new();
+    ctx.set_angle_unit(AngleUnit::Deg);
+    assert_eq!(eval_with("arg(1i)", &mut ctx).unwrap(), Value::Float(90.0));
+    ctx.set_mode(Mode::Decimal);
+    assert_eq!(
+        eval_with("sqrt(-0.25)", &mut ctx).unwrap(),
+        Value::from(Complex64::new(0.0, 0.5))
+    );
+}
+
+#[test]
+fn units() {
+    let show = |source: &str| match eval(source) {
+        Ok(v) => v.to_string(),
+        Err(err) => panic!("{source:?}: {err}"),
+    };
+    assert_eq!(show("5 km / 2 h"), "2.5 km/h");
+    assert_eq!(show("9.81 m/s^2 * 70 kg"), "686.7 N");
+    assert_eq!(show("1 km + 300 m"), "1.3 km");
+    assert_eq!(show("1 h + 30 min"), "1.5 h");
+    assert_eq!(show("60 mi/h * 2 h"), "120.0 mi");
+    assert_eq!(show("3 A * 2 s"), "6.0 C");
+    assert_eq!(show("100 W * 3 h"), "300.0 W*h");
+    assert_eq!(show("(3 m)^2"), "9.0 m^2");
+    assert_eq!(show("sqrt(16 m^2)"), "4.0 m");
+    assert_eq!(show("1 / 2 s"), "0.5 s^-1");
+    assert_eq!(show("-abs(-5 km)"), "-5.0 km");
+    assert_eq!(show("20 degC + 5 K"), "25.0 degC");
+    assert_eq!(show("5km/2h"), "2.5 km/h");
+    test_eval("3in == 3 in", true);
+    test_eval("10 m / 2 m", 5.0);
+    test_eval("1 ft / 1 in", 12.0);
+    test_eval("5 m < 2 km", true);
+    test_eval("0 degC == 273.15 K", true);
+    test_eval("212 degF > 99 degC", true);
+    test_eval("0 degC == 32 degF", true);
+    test_eval("100 degC == 212 degF && -40 degF == -40 degC", true);
+    test_eval(
+        "0 degC != 32 degF || 32 degF < 0 degC || 0 degC > 32 degF",
+        false,
+    );
+    test_eval("32 degF <= 0 degC && 32 degF >= 0 degC", true);
+    test_eval("0 degC < 32.0001 degF", true);
+
+    let incompatible = |op: &str, lhs: &str, rhs: &str| EvalErrorKind::IncompatibleUnits {
+        op: op.to_owned(),
+        lhs: lhs.to_owned(),
+        rhs: rhs.to_owned(),
+    };
+    assert_eq!(eval_err("3 m + 2 s"), incompatible("+", "m", "s"));
+    assert_eq!(eval_err("3 m/s < 2"), incompatible("<", "m/s", "1"));
+    assert_eq!(eval_err("(4 m)^0.5"), EvalErrorKind::UnitPower("m".into()));
+    assert_eq!(
+        eval_err("2 m * true"),
+        EvalErrorKind::invalid_operands("*", "quantity", "bool")
+    );
+    let Err(Error::EvalError(err)) = eval("1 + (3 m + 2 s)") else {
+        panic!("expected an eval error");
+    };
+    assert_eq!(err.span().range(), 4..15);
+
+    test_eval("min(2 m, 1 km) == 2 m", true);
+    assert_eq!(show("max(1 ft, 1 in, 0.5 ft)"), "1.0 ft");
+    assert_eq!(eval_err("min(1 m, 2 s)"), incompatible("<", "s", "m"));
+    assert_eq!(eval_err("max(1 m, 2)"), incompatible(">", "1", "m"));
+}
+
+#[test]
+fn unit_errors() {
+    // variables are not shadowed by units, nor units by variables
+    let spanned = |source: &str| match eval(source) {
+        Err(Error::EvalError(err)) => (err.kind().clone(), err.span().range()),
+        other => panic!("{source:?}: expected eval error, got {other:?}"),
+    };
+    assert_eq!(
+        spanned("h := 2; 5 h"),
+        (EvalErrorKind::AmbiguousUnit("h".into()), 10..11)
+    );
+    assert_eq!(
+        spanned("s := 2; 10 m / s"),
+        (EvalErrorKind::AmbiguousUnit("s".into()), 15..16)
+    );
+    test_eval("h := 2; 5 * h", 10);
+    test_eval("f :: (m) { 2 * m }; f(3)", 6);
+
+    // powers of units and of their dimension stay within `i8`
+    let power = |unit: &str| EvalErrorKind::UnitPower(unit.into());
+    assert_eq!(eval_err("1 N^100 * 1 m^100 + 1 m"), power("N^100"));
+    assert_eq!(
+        eval_err("(1 N^100 * 1 m^100) == (1 N^100 * 1 m^100)"),
+        power("N^100")
+    );
+    assert_eq!(eval_err("(1 m^100 * 1 m^100) + 1 m^-56"), power("m^200"));
+    assert_eq!(eval_err("1 m^127 / 1 m^-1"), power("m^128"));
+    assert_eq!(
+        eval_err("1 Hz^100 * 1 s^100 * 1 Hz^100"),
+        power("Hz^200*s^100")
+    );
+    assert_eq!(eval_err("(2 m)^128"), power("m"));
+    test_eval("(2 m^-1)^127 * 1 m^127", 2f64.powi(127));
+
+    // the parser only makes quantities of number literals and known units
+    type Units<'a> = Vec<(Spanned<'a, Cow<'a, str>>, i32)>;
+    let quantity = |edit: fn(&mut Expr, &mut Units)| {
+        let mut expr = Parser::new(Lexer::new("5 km"))
+            .and_then(|mut p| p.parse_expression())
+            .unwrap();
+        let ExprKind::Quantity(value, units) = &mut expr.kind else {
+            panic!("expected a quantity");
+        };
+        edit(value, units);
+        match Context::new().eval_expr(&expr) {
+            Err(Error::EvalError(err)) => (err.kind().clone(), err.span().range()),
+            other => panic!("expected eval error, got {other:?}"),
+        }
+    };
+    assert_eq!(
+        quantity(|_, units| units[0].0.value = "kmh".into()),
+        (EvalErrorKind::UnknownUnit("kmh".into()), 2..4)
+    );
+    assert_eq!(
+        quantity(|value, _| value.kind = ExprKind::Imag(1.0)),
+        (EvalErrorKind::type_mismatch("real number", "complex"), 0..1)
+    );
+}
+
+#[test]
+fn decimal_mode() {
+    assert_eq!(Context::new().mode(), Mode::Decimal);
+    assert_eq!(eval("0.1 + 0.2 == 0.3").unwrap(), Value::Bool(true));
+    assert_eq!(eval("2 ^ -1").unwrap(), eval("0.5").unwrap());
+
+    let mut ctx = Context::new();
+    let mut decimal = |source: &str| match eval_with(source, &mut ctx) {
+        Ok(v) => Format::default().value(&v),
+        Err(Error::EvalError(err)) => err.kind().to_string(),
+        Err(err) => panic!("{source:?}: {err}"),
+    };
+    assert_eq!(decimal("0.1 + 0.2 == 0.3"), "true");
+    assert_eq!(decimal("0.1 + 0.2"), "0.3");
+    assert_eq!(decimal("1.10 * 2"), "2.20");
+    assert_eq!(decimal("1 / 4"), "0.25");
+    assert_eq!(decimal("1 / 3"), "0.3333333333333333333333333333333333");
+    assert_eq!(decimal("2 / 3"), "0.6666666666666666666666666666666667");
+    assert_eq!(decimal("2 ^ -1"), "0.5");
+    assert_eq!(decimal("7.5 // 2"), "3.0");
+    assert_eq!(decimal("3 % 0.7"), "0.2");
+    assert_eq!(decimal("1e3"), "1000.0");
+    assert_eq!(decimal("1.5e-9"), "1.5e-9");
+    assert_eq!(decimal("sqrt(2)"), "1.414213562373095048801688724209698");
+    assert_eq!(decimal("exp(1) == e"), "true");
+    assert_eq!(decimal("ln(2)"), "0.6931471805599453094172321214581766");
+    assert_eq!(decimal("log(8, 2)"), "3.0");
+    assert_eq!(decimal("sin(1)"), "0.841470984807896506652502321630299");
+    assert_eq!(decimal("cos(1)"), "0.5403023058681397174009366074429766");
+    assert_eq!(decimal("tan(1)"), "1.55740772465490223050697480745836");
+    assert_eq!(decimal("pi"), "3.141592653589793238462643383279503");
+    assert_eq!(decimal("round(2.5)"), "3");
+    assert_eq!(decimal("floor(-1.5)"), "-2");
+    assert_eq!(decimal("int(7.9)"), "7");
+    assert_eq!(decimal("abs(-1.5)"), "1.5");
+    assert_eq!(decimal("float(0.1) + 0.2"), "0.30000000000000004");
+    assert_eq!(decimal("sqrt(-1)"), "1.0i");
+    assert_eq!(decimal("ln(0)"), "argument outside the domain of `ln`");
+    assert_eq!(decimal("1 / 0.0"), "division by zero");
+    assert_eq!(decimal("1e9999999"), "decimal exponent out of range");
+
+    ctx.set_precision("5 down".parse().unwrap());
+    let mut decimal = |source: &str| Format::default().value(&eval_with(source, &mut ctx).unwrap());
+    assert_eq!(decimal("2 / 3"), "0.66666");
+    assert_eq!(decimal("pi"), "3.1415");
+    assert_eq!(decimal("123456.7"), "123450.0");
+    ctx.set_precision(Precision::default());
+    ctx.set_angle_unit(AngleUnit::Deg);
+    assert_eq!(
+        eval_with("sin(30)", &mut ctx).unwrap(),
+        eval_with("0.5", &mut ctx).unwrap()
+    );
+
+    test_eval("0.1 + 0.2", 0.1 + 0.2);
+    assert_eq!(
+        "50 half-up".parse(),
+        Ok(Precision::new(50, Rounding::HalfUp))
+    );
+    assert_eq!("floor".parse(), Ok(Precision::new(34, Rounding::Floor)));
+    assert!("0".parse::<Precision>().is_err());
+    assert!("half-odd".parse::<Precision>().is_err());
+}
@@ -0,0 +1,789 @@
+use bigdecimal::BigDecimal;
+use derive_more::Display;
+use num_bigint::BigInt;
+use num_complex::Complex64;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+use std::cmp::Ordering;
+
+use super::decimal::{self, Precision};
+use super::error::EvalErrorKind;
+use super::instance::Instance;
+use super::quantity::Quantity;
+use crate::format;
+use crate::parser::ast::{BinOp, UnaryOp};
+
+type OpResult<T = Value> = std::result::Result<T, EvalErrorKind>;
+
+/// Integers larger than this many bits fail to compute, rather than taking
+/// up all memory.
+pub const MAX_INT_BITS: u64 = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq, Display)]
+pub enum Value {
+    #[display(fmt = "{_0}")]
+    Int(i64),
+    /// An integer outside of the `i64` range, the others are always
+    /// [`Value::Int`]s.
+    #[display(fmt = "{_0}")]
+    BigInt(BigInt),
+    /// A fraction in lowest terms that is not a whole number, as made by
+    /// divisions in the exact mode.
+    #[display(fmt = "{_0}")]
+    Ratio(Box<BigRational>),
+    /// A decimal fraction rounded to the working precision, as made in the
+    /// decimal mode.
+    #[display(fmt = "{}", "decimal::to_string(_0)")]
+    Decimal(Box<BigDecimal>),
+    #[display(fmt = "{_0:?}")]
+    Float(f64),
+    /// A complex number of floats, `3.0+4.0i`.
+    #[display(fmt = "{}", "format::complex(_0, |x| format!(\"{x:?}\"))")]
+    Complex(Box<Complex64>),
+    /// A float with physical units, `5.0 km/h`.
+    #[display(fmt = "{_0}")]
+    Quantity(Box<Quantity>),
+    /// A value of a struct type, `Point { x: 1, y: 2 }`.
+    #[display(fmt = "{_0}")]
+    Struct(Box<Instance>),
+    #[display(fmt = "{_0}")]
+    Bool(bool),
+    /// The value of statements and of blocks without a trailing expression.
+    #[display(fmt = "()")]
+    Unit,
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Self {
+        Self::Int(i)
+    }
+}
+impl From<i32> for Value {
+    fn from(i: i32) -> Self {
+        Self::Int(i.into())
+    }
+}
+impl From<f64> for Value {
+    fn from(x: f64) -> Self {
+        Self::Float(x)
+    }
+}
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Self::Bool(b)
+    }
+}
+
+/// Integer literals are unsigned in the lexer.
+impl From<u64> for Value {
+    fn from(u: u64) -> Self {
+        match i64::try_from(u) {
+            Ok(i) => Self::Int(i),
+            Err(_) => Self::BigInt(u.into()),
+        }
+    }
+}
+
+/// Whole fractions are integers.
+impl From<BigRational> for Value {
+    fn from(r: BigRational) -> Self {
+        if r.is_integer() {
+            Self::from(r.to_integer())
+        } else {
+            Self::Ratio(Box::new(r))
+        }
+    }
+}
+
+impl From<BigDecimal> for Value {
+    fn from(d: BigDecimal) -> Self {
+        Self::Decimal(Box::new(d))
+    }
+}
+
+impl From<Complex64> for Value {
+    fn from(c: Complex64) -> Self {
+        Self::Complex(Box::new(c))
+    }
+}
+
+/// Quantities whose units cancel out are floats.
+impl From<Quantity> for Value {
+    fn from(q: Quantity) -> Self {
+        if q.is_number() {
+            Self::Float(q.value())
+        } else {
+            Self::Quantity(Box::new(q))
+        }
+    }
+}
+
+impl From<Instance> for Value {
+    fn from(s: Instance) -> Self {
+        Self::Struct(Box::new(s))
+    }
+}
+
+/// Keeps the integer in a [`Value::Int`] when it fits.
+impl From<BigInt> for Value {
+    fn from(i: BigInt) -> Self {
+        match i.to_i64() {
+            Some(small) => Self::Int(small),
+            None => Self::BigInt(i),
+        }
+    }
+}
+
+impl Value {
+    /// Whether the value is a quantity or a struct holding one, so that its
+    /// literal names units.
+    pub(crate) fn has_quantity(&self) -> bool {
+        match self {
+            Self::Quantity(_) => true,
+            Self::Struct(s) => s.fields().any(|(_, v)| v.has_quantity()),
+            _ => false,
+        }
+    }
+
+    /// Source text that evaluates back to the value in any mode. Floats
+    /// have the `f64` suffix, the exact fractions are made by the `rational`
+    /// and `decimal` builtins, and structs by calling their name.
+    pub fn literal(&self) -> String {
+        match self {
+            Self::Ratio(r) => format!("rational({}, {})", r.numer(), r.denom()),
+            Self::Decimal(d) => {
+                let (digits, scale) = d.as_bigint_and_exponent();
+                format!("decimal({digits}, {})", -i128::from(scale))
+            }
+            Self::Float(x) => float_literal(*x),
+            Self::Complex(c) => {
+                let im = match c.im {
+                    y if y.is_nan() => format!("{} * 1i", float_literal(y)),
+                    y if y.is_infinite() => format!("{}1e999i", if y < 0.0 { "-" } else { "" }),
+                    y => format!("{y:?}i"),
+                };
+                format!("{} + {im}", float_literal(c.re))
+            }
+            // units only follow a literal
+            Self::Quantity(q) if q.value().is_nan() => {
+                format!("{} * 1.0f64 {}", float_literal(q.value()), q.unit())
+            }
+            Self::Quantity(q) => format!("{} {}", float_literal(q.value()), q.unit()),
+            Self::Struct(s) => {
+                let values: Vec<_> = s.fields().map(|(_, value)| value.literal()).collect();
+                format!("{}({})", s.name(), values.join(", "))
+            }
+            Self::Unit => "{}".to_owned(),
+            value => value.to_string(),
+        }
+    }
+
+    pub const fn type_name(&self) -> &'static str {
+        match self {
+            Self::Int(_) | Self::BigInt(_) => "int",
+            Self::Ratio(_) => "rational",
+            Self::Decimal(_) => "decimal",
+            Self::Float(_) => "float",
+            Self::Complex(_) => "complex",
+            Self::Quantity(_) => "quantity",
+            Self::Struct(_) => "struct",
+            Self::Bool(_) => "bool",
+            Self::Unit => "unit",
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Self::Int(i) => Some(i as f64),
+            Self::BigInt(ref i) => Some(i.to_f64().unwrap_or(if i.is_negative() {
+                f64::NEG_INFINITY
+            } else {
+                f64::INFINITY
+            })),
+            Self::Ratio(ref r) => Some(r.to_f64().unwrap_or(f64::NAN)),
+            // the digits parse to the nearest float
+            Self::Decimal(ref d) => decimal::to_string(d).parse().ok(),
+            Self::Float(x) => Some(x),
+            Self::Complex(_) | Self::Quantity(_) | Self::Struct(_) | Self::Bool(_) | Self::Unit => {
+                None
+            }
+        }
+    }
+
+    /// Any number as a complex one.
+    pub fn as_complex(&self) -> Option<Complex64> {
+        match *self {
+            Self::Complex(ref c) => Some(**c),
+            ref v => v.as_f64().map(Complex64::from),
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Self::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Converts the value to the type of an annotation like `x :f32= 1`.
+    pub fn coerce(self, ty: &str) -> OpResult {
+        let range = match ty {
+            "i8" => i128::from(i8::MIN)..=i128::from(i8::MAX),
+            "i16" => i128::from(i16::MIN)..=i128::from(i16::MAX),
+            "i32" => i128::from(i32::MIN)..=i128::from(i32::MAX),
+            "i64" => i128::from(i64::MIN)..=i128::from(i64::MAX),
+            "u8" => 0..=i128::from(u8::MAX),
+            "u16" => 0..=i128::from(u16::MAX),
+            "u32" => 0..=i128::from(u32::MAX),
+            "u64" => 0..=i128::from(u64::MAX),
+            _ => i128::MIN..=i128::MAX,
+        };
+        match (ty, &self) {
+            ("int", Self::Int(_) | Self::BigInt(_)) => Ok(self),
+            (
+                "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64",
+                Self::Int(_) | Self::BigInt(_),
+            ) => match self.to_bigint().to_i128() {
+                Some(i) if range.contains(&i) => Ok(self),
+                _ => Err(EvalErrorKind::IntegerOverflow),
+            },
+            ("int" | "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64", v) => {
+                Err(EvalErrorKind::type_mismatch("int", v.type_name()))
+            }
+            ("float" | "f64" | "f32", v) => match v.as_f64() {
+                Some(x) if ty == "f32" => Ok(Self::Float(x as f32 as f64)),
+                Some(x) => Ok(Self::Float(x)),
+                None => Err(EvalErrorKind::type_mismatch("float", v.type_name())),
+            },
+            ("bool", Self::Bool(_)) => Ok(self),
+            ("bool", v) => Err(EvalErrorKind::type_mismatch("bool", v.type_name())),
+            _ => Err(EvalErrorKind::UnknownType(ty.to_owned())),
+        }
+    }
+
+    pub fn unary(self, op: UnaryOp) -> OpResult {
+        match (op, self) {
+            (UnaryOp::Neg, Self::Int(i)) => Ok(i
+                .checked_neg()
+                .map_or_else(|| Self::from(-BigInt::from(i)), Self::Int)),
+            (UnaryOp::Neg, Self::BigInt(i)) => Ok(Self::from(-i)),
+            (UnaryOp::Neg, Self::Ratio(r)) => Ok(Self::Ratio(Box::new(-*r))),
+            (UnaryOp::Neg, Self::Decimal(d)) => Ok(Self::Decimal(Box::new(-*d))),
+            (UnaryOp::Neg, Self::Float(x)) => Ok(Self::Float(-x)),
+            (UnaryOp::Neg, Self::Complex(c)) => Ok(Self::Complex(Box::new(-*c))),
+            (UnaryOp::Neg, Self::Quantity(q)) => Ok(Self::from(q.scale(-1.0))),
+            (
+                UnaryOp::Plus,
+                v @ (Self::Int(_)
+                | Self::BigInt(_)
+                | Self::Ratio(_)
+                | Self::Decimal(_)
+                | Self::Float(_)
+                | Self::Complex(_)
+                | Self::Quantity(_)),
+            ) => Ok(v),
+            (UnaryOp::Not, Self::Bool(b)) => Ok(Self::Bool(!b)),
+            (UnaryOp::Not | UnaryOp::BitNot, Self::Int(i)) => Ok(Self::Int(!i)),
+            (UnaryOp::Not | UnaryOp::BitNot, Self::BigInt(i)) => Ok(Self::from(!i)),
+            (op, v) => Err(EvalErrorKind::invalid_operand(op, v.type_name())),
+        }
+    }
+
+    /// Applies a binary operator, `LogicAnd`/`LogicOr` included although the
+    /// evaluator short-circuits them before both sides are known. Integer
+    /// results that overflow an `i64` carry on as big integers.
+    pub fn binary(self, op: BinOp, rhs: Self) -> OpResult {
+        use Value::*;
+        let invalid = || EvalErrorKind::invalid_operands(op, self.type_name(), rhs.type_name());
+        let ints = self.is_int() && rhs.is_int();
+        let exact = self.is_exact() && rhs.is_exact();
+        // exact operands but not only integers
+        let ratios = exact && !ints;
+
+        if let (Quantity(_), _) | (_, Quantity(_)) = (&self, &rhs) {
+            return quantity_binary(op, &self, &rhs)?.ok_or_else(invalid);
+        }
+        if matches!(self, Complex(_)) || matches!(rhs, Complex(_)) {
+            let (a, b) = self
+                .as_complex()
+                .zip(rhs.as_complex())
+                .ok_or_else(invalid)?;
+            return complex_binary(op, a, b).ok_or_else(invalid);
+        }
+        match op {
+            BinOp::LogicAnd | BinOp::LogicOr => match (&self, &rhs) {
+                (Bool(a), Bool(b)) if op == BinOp::LogicAnd => Ok(Bool(*a && *b)),
+                (Bool(a), Bool(b)) => Ok(Bool(*a || *b)),
+                _ => Err(invalid()),
+            },
+            BinOp::Eq | BinOp::Ne => {
+                let eq = match (&self, &rhs) {
+                    (Bool(a), Bool(b)) => a == b,
+                    // numbers are normalized, equal values have equal variants
+                    _ if exact => self == rhs,
+                    _ if self.is_rational() && rhs.is_rational() => {
+                        self.to_ratio() == rhs.to_ratio()
+                    }
+                    _ => {
+                        let (a, b) = self.float_pair(&rhs).ok_or_else(invalid)?;
+                        a == b
+                    }
+                };
+                Ok(Bool(eq == (op == BinOp::Eq)))
+            }
+            BinOp::Lt | BinOp::Lte | BinOp::Gt | BinOp::Gte => {
+                let ord = self.compare(&rhs).ok_or_else(invalid)?;
+                Ok(Bool(ord.is_some_and(|ord| match op {
+                    BinOp::Lt => ord.is_lt(),
+                    BinOp::Lte => ord.is_le(),
+                    BinOp::Gt => ord.is_gt(),
+                    _ => ord.is_ge(),
+                })))
+            }
+            BinOp::BitAnd | BinOp::BitOr | BinOp::BitXor | BinOp::BitNotXor => {
+                match (&self, &rhs) {
+                    (Int(a), Int(b)) => Ok(Int(match op {
+                        BinOp::BitAnd => a & b,
+                        BinOp::BitOr => a | b,
+                        BinOp::BitXor => a ^ b,
+                        _ => !(a ^ b),
+                    })),
+                    (Bool(a), Bool(b)) => Ok(Bool(match op {
+                        BinOp::BitAnd => a & b,
+                        BinOp::BitOr => a | b,
+                        BinOp::BitXor => a ^ b,
+                        _ => !(a ^ b),
+                    })),
+                    _ if ints => big_binary(op, self.to_bigint(), rhs.to_bigint()),
+                    _ => Err(invalid()),
+                }
+            }
+            BinOp::Shl | BinOp::Shr => match (&self, &rhs) {
+                (Int(a), Int(b)) if op == BinOp::Shr && *b >= 0 => Ok(Int(a >> b.min(&63))),
+                (Int(a), Int(b @ 0..=62)) if a.checked_mul(1 << b).is_some() => Ok(Int(a << b)),
+                _ if ints => {
+                    let shift = rhs.to_bigint();
+                    if shift.is_negative() {
+                        return Err(EvalErrorKind::InvalidShift(shift.to_string()));
+                    }
+                    shift_big(op, self.to_bigint(), shift.to_u64().unwrap_or(u64::MAX))
+                }
+                _ => Err(invalid()),
+            },
+            BinOp::Add | BinOp::Sub | BinOp::Mul => match (&self, &rhs) {
+                (Int(a), Int(b)) => match op {
+                    BinOp::Add => a.checked_add(*b),
+                    BinOp::Sub => a.checked_sub(*b),
+                    _ => a.checked_mul(*b),
+                }
+                .map_or_else(|| big_binary(op, (*a).into(), (*b).into()), |i| Ok(Int(i))),
+                _ if ints => big_binary(op, self.to_bigint(), rhs.to_bigint()),
+                _ if ratios => ratio_binary(op, self.to_ratio(), rhs.to_ratio()),
+                _ => {
+                    let (a, b) = self.float_pair(&rhs).ok_or_else(invalid)?;
+                    Ok(Float(match op {
+                        BinOp::Add => a + b,
+                        BinOp::Sub => a - b,
+                        _ => a * b,
+                    }))
+                }
+            },
+            BinOp::Div => match rhs {
+                Int(0) if exact => Err(EvalErrorKind::DivisionByZero),
+                _ if ratios => ratio_binary(op, self.to_ratio(), rhs.to_ratio()),
+                _ => {
+                    let (a, b) = self.float_pair(&rhs).ok_or_else(invalid)?;
+                    Ok(Float(a / b))
+                }
+            },
+            BinOp::IDiv | BinOp::Rem => match (&self, &rhs) {
+                (_, Int(0)) if exact => Err(EvalErrorKind::DivisionByZero),
+                (Int(a), Int(b)) => {
+                    let r = if op == BinOp::IDiv {
+                        floor_div(*a, *b)
+                    } else {
+                        floor_rem(*a, *b)
+                    };
+                    r.map_or_else(|| big_binary(op, (*a).into(), (*b).into()), |i| Ok(Int(i)))
+                }
+                _ if ints => big_binary(op, self.to_bigint(), rhs.to_bigint()),
+                _ if ratios => ratio_binary(op, self.to_ratio(), rhs.to_ratio()),
+                _ => {
+                    let (a, b) = self.float_pair(&rhs).ok_or_else(invalid)?;
+                    let q = (a / b).floor();
+                    Ok(Float(if op == BinOp::IDiv { q } else { a - b * q }))
+                }
+            },
+            BinOp::Pow => match (&self, &rhs) {
+                (Int(a), Int(b @ 0..)) => {
+                    match u32::try_from(*b).ok().and_then(|b| a.checked_pow(b)) {
+                        Some(i) => Ok(Int(i)),
+                        None => pow_big(self.to_bigint(), rhs.to_bigint()),
+                    }
+                }
+                _ if ints && !rhs.to_bigint().is_negative() => {
+                    pow_big(self.to_bigint(), rhs.to_bigint())
+                }
+                (Ratio(_), _) if rhs.is_int() => pow_ratio(self.to_ratio(), rhs.to_bigint()),
+                _ => {
+                    let (a, b) = self.float_pair(&rhs).ok_or_else(invalid)?;
+                    Ok(Float(a.powf(b)))
+                }
+            },
+        }
+    }
+
+    /// Like [`Value::binary`], except that `/` and `^` with a negative
+    /// exponent give exact rationals for integer operands.
+    pub fn binary_exact(self, op: BinOp, rhs: Self) -> OpResult {
+        if !(self.is_exact() && rhs.is_exact()) {
+            return self.binary(op, rhs);
+        }
+        match op {
+            BinOp::Div if rhs == Value::Int(0) => Err(EvalErrorKind::DivisionByZero),
+            BinOp::Div => ratio_binary(op, self.to_ratio(), rhs.to_ratio()),
+            BinOp::Pow if rhs.is_int() && rhs.to_bigint().is_negative() => {
+                pow_ratio(self.to_ratio(), rhs.to_bigint())
+            }
+            _ => self.binary(op, rhs),
+        }
+    }
+
+    /// Like [`Value::binary`], except that arithmetic on decimals gives
+    /// decimals rounded to `precision`, and so do `/` and `^` with a negative
+    /// exponent on integers.
+    pub fn binary_decimal(self, op: BinOp, rhs: Self, precision: Precision) -> OpResult {
+        if !(self.is_rational() && rhs.is_rational()) {
+            return self.binary(op, rhs);
+        }
+        let decimals = matches!(self, Self::Decimal(_)) || matches!(rhs, Self::Decimal(_));
+        match op {
+            BinOp::Div => {}
+            BinOp::Pow if rhs.is_int() && rhs.to_bigint().is_negative() => {}
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::IDiv | BinOp::Rem | BinOp::Pow
+                if decimals => {}
+            _ => return self.binary(op, rhs),
+        }
+        let (a, b) = (self.to_decimal(precision)?, rhs.to_decimal(precision)?);
+        decimal::binary(op, &a, &b, precision).map(Value::from)
+    }
+
+    fn is_int(&self) -> bool {
+        matches!(self, Self::Int(_) | Self::BigInt(_))
+    }
+
+    /// The integer as a big one, zero for other values.
+    fn to_bigint(&self) -> BigInt {
+        match self {
+            Self::Int(i) => BigInt::from(*i),
+            Self::BigInt(i) => i.clone(),
+            _ => BigInt::zero(),
+        }
+    }
+
+    /// Integers and rationals, the numbers without rounding errors.
+    fn is_exact(&self) -> bool {
+        matches!(self, Self::Int(_) | Self::BigInt(_) | Self::Ratio(_))
+    }
+
+    /// Exact numbers and decimals, the numbers with a value as a fraction.
+    pub(crate) fn is_rational(&self) -> bool {
+        self.is_exact() || matches!(self, Self::Decimal(_))
+    }
+
+    /// The number as a fraction, zero for values that are not rational.
+    fn to_ratio(&self) -> BigRational {
+        match self {
+            Self::Ratio(r) => (**r).clone(),
+            Self::Decimal(d) => {
+                let (digits, scale) = d.as_bigint_and_exponent();
+                let power = num_traits::pow(BigInt::from(10), scale.unsigned_abs() as usize);
+                match scale {
+                    0.. => BigRational::new(digits, power),
+                    _ => BigRational::from_integer(digits * power),
+                }
+            }
+            _ => BigRational::from_integer(self.to_bigint()),
+        }
+    }
+
+    /// The number as a decimal, rationals rounded to `precision`.
+    pub(crate) fn to_decimal(&self, precision: Precision) -> OpResult<BigDecimal> {
+        match self {
+            Self::Decimal(d) => Ok((**d).clone()),
+            Self::Ratio(r) => decimal::binary(
+                BinOp::Div,
+                &BigDecimal::from(r.numer().clone()),
+                &BigDecimal::from(r.denom().clone()),
+                precision,
+            ),
+            _ => Ok(BigDecimal::from(self.to_bigint())),
+        }
+    }
+
+    fn float_pair(&self, rhs: &Self) -> Option<(f64, f64)> {
+        Some((self.as_f64()?, rhs.as_f64()?))
+    }
+
+    /// Numeric ordering, `None` for bools. Any comparison against NaN is
+    /// false, like in IEEE 754.
+    fn compare(&self, rhs: &Self) -> Option<Option<Ordering>> {
+        match (self, rhs) {
+            (Self::Int(a), Self::Int(b)) => Some(Some(a.cmp(b))),
+            _ if self.is_int() && rhs.is_int() => {
+                Some(Some(self.to_bigint().cmp(&rhs.to_bigint())))
+            }
+            _ if self.is_rational() && rhs.is_rational() => {
+                Some(Some(self.to_ratio().cmp(&rhs.to_ratio())))
+            }
+            _ => {
+                let (a, b) = self.float_pair(rhs)?;
+                Some(a.partial_cmp(&b))
+            }
+        }
+    }
+}
+
+fn float_literal(x: f64) -> String {
+    match x {
+        x if x.is_nan() => "(0.0f64 / 0.0f64)".to_owned(),
+        x if x.is_infinite() => format!("{}1e999f64", if x < 0.0 { "-" } else { "" }),
+        x => format!("{x:?}f64"),
+    }
+}
+
+/// Fails for integers over [`MAX_INT_BITS`] bits.
+fn bounded(i: BigInt) -> OpResult {
+    if i.bits() > MAX_INT_BITS {
+        Err(EvalErrorKind::IntegerOverflow)
+    } else {
+        Ok(Value::from(i))
+    }
+}
+
+/// The integer operators on big integers, divisors are not zero.
+fn big_binary(op: BinOp, a: BigInt, b: BigInt) -> OpResult {
+    // a product has at least this many bits, no need to compute it to know
+    // it is too large
+    if op == BinOp::Mul && (a.bits() + b.bits()).saturating_sub(1) > MAX_INT_BITS {
+        return Err(EvalErrorKind::IntegerOverflow);
+    }
+    bounded(match op {
+        BinOp::Add => a + b,
+        BinOp::Sub => a - b,
+        BinOp::Mul => a * b,
+        BinOp::IDiv => a.div_floor(&b),
+        BinOp::Rem => a.mod_floor(&b),
+        BinOp::BitAnd => a & b,
+        BinOp::BitOr => a | b,
+        BinOp::BitXor => a ^ b,
+        BinOp::BitNotXor => !(a ^ b),
+        _ => unreachable!("`{op}` is not an integer operator"),
+    })
+}
+
+/// Fails for fractions with a numerator or denominator over
+/// [`MAX_INT_BITS`] bits.
+fn bounded_ratio(r: BigRational) -> OpResult {
+    if r.numer().bits() > MAX_INT_BITS || r.denom().bits() > MAX_INT_BITS {
+        Err(EvalErrorKind::IntegerOverflow)
+    } else {
+        Ok(Value::from(r))
+    }
+}
+
+/// The arithmetic operators on fractions, divisors are not zero.
+fn ratio_binary(op: BinOp, a: BigRational, b: BigRational) -> OpResult {
+    bounded_ratio(match op {
+        BinOp::Add => a + b,
+        BinOp::Sub => a - b,
+        BinOp::Mul => a * b,
+        BinOp::Div => a / b,
+        BinOp::IDiv => (a / b).floor(),
+        BinOp::Rem => {
+            let q = (&a / &b).floor();
+            a - b * q
+        }
+        _ => unreachable!("`{op}` is not a rational operator"),
+    })
+}
+
+/// The arithmetic and equality operators on complex numbers, `None` for the
+/// others.
+fn complex_binary(op: BinOp, a: Complex64, b: Complex64) -> Option<Value> {
+    let c = match op {
+        BinOp::Eq => return Some(Value::Bool(a == b)),
+        BinOp::Ne => return Some(Value::Bool(a != b)),
+        BinOp::Add => a + b,
+        BinOp::Sub => a - b,
+        BinOp::Mul => a * b,
+        BinOp::Div => a / b,
+        // whole powers by multiplication, which keeps `i ^ 2` real
+        BinOp::Pow if b.im == 0.0 && b.re.fract() == 0.0 && b.re.abs() <= i32::MAX as f64 => {
+            a.powi(b.re as i32)
+        }
+        BinOp::Pow => a.powc(b),
+        _ => return None,
+    };
+    Some(Value::from(c))
+}
+
+/// The operators on quantities, at least one side being one. Plain numbers
+/// scale quantities but do not add to or compare with them. `None` for
+/// operands that are not numbers.
+fn quantity_binary(op: BinOp, a: &Value, b: &Value) -> OpResult<Option<Value>> {
+    let incompatible = |lhs: &Value, rhs: &Value| {
+        let unit = |v: &Value| match v {
+            Value::Quantity(q) => q.unit(),
+            _ => "1".to_owned(),
+        };
+        EvalErrorKind::IncompatibleUnits {
+            op: op.to_string(),
+            lhs: unit(lhs),
+            rhs: unit(rhs),
+        }
+    };
+    let number = |v: &Value| matches!(v, Value::Quantity(_)) || v.as_f64().is_some();
+    if !(number(a) && number(b)) {
+        return Ok(None);
+    }
+    let float = |v: &Value| v.as_f64().unwrap_or(f64::NAN);
+    let q = match (op, a, b) {
+        (BinOp::Mul, Value::Quantity(x), Value::Quantity(y)) => x.as_ref().clone().times(y)?,
+        (BinOp::Div, Value::Quantity(x), Value::Quantity(y)) => {
+            x.as_ref().clone().times(&y.as_ref().clone().recip())?
+        }
+        (BinOp::Mul, Value::Quantity(x), n) | (BinOp::Mul, n, Value::Quantity(x)) => {
+            x.as_ref().clone().scale(float(n))
+        }
+        (BinOp::Div, Value::Quantity(x), n) => x.as_ref().clone().scale(float(n).recip()),
+        (BinOp::Div, n, Value::Quantity(x)) => x.as_ref().clone().recip().scale(float(n)),
+        (
+            BinOp::Pow,
+            Value::Quantity(x),
+            n @ (Value::Int(_)
+            | Value::BigInt(_)
+            | Value::Ratio(_)
+            | Value::Decimal(_)
+            | Value::Float(_)),
+        ) => x.as_ref().clone().pow(float(n))?,
+        (
+            BinOp::Add | BinOp::Sub | BinOp::Rem | BinOp::IDiv,
+            Value::Quantity(x),
+            Value::Quantity(y),
+        ) => {
+            let (a, b) = (x.value(), x.convert(y, op)?);
+            x.as_ref().clone().with_value(match op {
+                BinOp::Add => a + b,
+                BinOp::Sub => a - b,
+                BinOp::IDiv => return Ok(Some(Value::Float((a / b).floor()))),
+                _ => a - b * (a / b).floor(),
+            })
+        }
+        (
+            BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Lte | BinOp::Gt | BinOp::Gte,
+            Value::Quantity(x),
+            Value::Quantity(y),
+        ) => {
+            x.convert(y, op)?;
+            let ord = x.compare_si(y);
+            return Ok(Some(Value::Bool(match op {
+                BinOp::Eq => ord == Some(Ordering::Equal),
+                BinOp::Ne => ord != Some(Ordering::Equal),
+                BinOp::Lt => ord == Some(Ordering::Less),
+                BinOp::Lte => matches!(ord, Some(Ordering::Less | Ordering::Equal)),
+                BinOp::Gt => ord == Some(Ordering::Greater),
+                _ => matches!(ord, Some(Ordering::Greater | Ordering::Equal)),
+            })));
+        }
+        (
+            BinOp::Add
+            | BinOp::Sub
+            | BinOp::Rem
+            | BinOp::IDiv
+            | BinOp::Eq
+            | BinOp::Ne
+            | BinOp::Lt
+            | BinOp::Lte
+            | BinOp::Gt
+            | BinOp::Gte,
+            _,
+            _,
+        ) => return Err(incompatible(a, b)),
+        _ => return Ok(None),
+    };
+    Ok(Some(Value::from(q)))
+}
+
+/// `base ^ exp` for any integer exponent.
+fn pow_ratio(base: BigRational, exp: BigInt) -> OpResult {
+    let (base, exp) = if exp.is_negative() {
+        if base.is_zero() {
+            return Err(EvalErrorKind::DivisionByZero);
+        }
+        (base.recip(), -exp)
+    } else {
+        (base, exp)
+    };
+    let (numer, denom) = base.into();
+    let numer = pow_big(numer, exp.clone())?.to_bigint();
+    let denom = pow_big(denom, exp)?.to_bigint();
+    Ok(Value::from(BigRational::new(numer, denom)))
+}
+
+fn shift_big(op: BinOp, a: BigInt, shift: u64) -> OpResult {
+    if op == BinOp::Shr {
+        // shifting out every bit leaves 0, or -1 for negative numbers
+        let shift = shift.min(a.bits());
+        return Ok(Value::from(a >> shift));
+    }
+    if a.is_zero() {
+        return Ok(Value::Int(0));
+    }
+    if a.bits().saturating_add(shift) > MAX_INT_BITS {
+        return Err(EvalErrorKind::IntegerOverflow);
+    }
+    Ok(Value::from(a << shift))
+}
+
+/// `base ^ exp` for a non negative exponent.
+fn pow_big(base: BigInt, exp: BigInt) -> OpResult {
+    if base.magnitude().is_one() || base.is_zero() {
+        // the only bases that stay small, whatever the exponent
+        return Ok(match (base.is_negative(), exp.is_even()) {
+            _ if base.is_zero() => Value::Int(exp.is_zero().into()),
+            (true, false) => Value::Int(-1),
+            _ => Value::Int(1),
+        });
+    }
+    // the power has more than this many bits
+    let min_bits = exp
+        .to_u64()
+        .and_then(|exp| exp.checked_mul(base.bits() - 1))
+        .unwrap_or(u64::MAX);
+    match u32::try_from(exp) {
+        Ok(exp) if min_bits < MAX_INT_BITS => bounded(base.pow(exp)),
+        _ => Err(EvalErrorKind::IntegerOverflow),
+    }
+}
+
+/// Integer division rounding towards negative infinity, like `f64::floor`.
+fn floor_div(a: i64, b: i64) -> Option<i64> {
+    let q = a.checked_div(b)?;
+    if a % b != 0 && (a < 0) != (b < 0) {
+        Some(q - 1)
+    } else {
+        Some(q)
+    }
+}
+
+/// Remainder taking the sign of the divisor, so `a == b * (a // b) + a % b`.
+fn floor_rem(a: i64, b: i64) -> Option<i64> {
+    let r = a.checked_rem(b)?;
+    if r != 0 && (r < 0) != (b < 0) {
+        Some(r + b)
+    } else {
+        Some(r)
+    }
+}
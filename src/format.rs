@@ -0,0 +1,590 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, RoundingMode};
+use num_bigint::BigInt;
+use num_complex::Complex64;
+use num_traits::{Signed, Zero};
+
+use crate::eval::decimal;
+use crate::eval::value::Value;
+
+/// How numbers are written out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Notation {
+    #[default]
+    Dec,
+    Hex,
+    Bin,
+    Oct,
+    /// `1.5e3`
+    Sci,
+    /// Like [`Notation::Sci`] with the exponent a multiple of 3, `15e3`.
+    Eng,
+}
+
+impl Notation {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Dec => "dec",
+            Self::Hex => "hex",
+            Self::Bin => "bin",
+            Self::Oct => "oct",
+            Self::Sci => "sci",
+            Self::Eng => "eng",
+        }
+    }
+
+    const fn radix(self) -> Option<(u32, &'static str, usize)> {
+        match self {
+            Self::Hex => Some((16, "0x", 4)),
+            Self::Bin => Some((2, "0b", 4)),
+            Self::Oct => Some((8, "0o", 3)),
+            _ => None,
+        }
+    }
+}
+
+/// A display format for values. The output reads back as a literal, but for
+/// complex numbers in `polar` form and fractions in `bin` or `oct`, which
+/// those write in decimal.
+///
+/// It is written as words: a [`Notation`] name, a number of significant
+/// digits, `_` to group digits and `polar` for complex numbers, as in `sci 4`
+/// or `hex _`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Format {
+    notation: Notation,
+    digits: Option<usize>,
+    group: bool,
+    polar: bool,
+}
+
+/// What format words there are.
+pub const FORMAT_HELP: &str =
+    "formats are dec, hex, bin, oct, sci or eng, a number of significant digits, `_` to group digits and `polar`; bin and oct only write integers";
+
+/// Significant digits above this are not worth the allocation.
+pub const MAX_DIGITS: usize = 1000;
+
+impl Format {
+    pub const fn new(notation: Notation) -> Self {
+        Self {
+            notation,
+            digits: None,
+            group: false,
+            polar: false,
+        }
+    }
+
+    /// Rounds floats to `digits` significant digits, integers are only
+    /// rounded in scientific notations.
+    pub fn digits(mut self, digits: Option<usize>) -> Self {
+        self.digits = digits;
+        self
+    }
+
+    /// Separates groups of digits with `_`.
+    pub fn group(mut self, group: bool) -> Self {
+        self.group = group;
+        self
+    }
+
+    /// Writes complex numbers as a magnitude and an angle in degrees,
+    /// `5.0∠90.0°`.
+    pub fn polar(mut self, polar: bool) -> Self {
+        self.polar = polar;
+        self
+    }
+
+    pub fn notation(&self) -> Notation {
+        self.notation
+    }
+
+    pub fn value(&self, value: &Value) -> String {
+        match *value {
+            Value::Int(i) => self.int(i),
+            Value::BigInt(ref i) => self.big_int(i),
+            // a fraction is exact only as itself
+            Value::Ratio(ref r) if self.notation == Notation::Dec && self.digits.is_none() => {
+                format!(
+                    "{}/{}",
+                    self.decimal(&r.numer().to_string()),
+                    self.decimal(&r.denom().to_string())
+                )
+            }
+            Value::Ratio(_) => self.float(value.as_f64().unwrap_or(f64::NAN)),
+            Value::Decimal(ref d) => self.big_decimal(d),
+            Value::Float(x) => self.float(x),
+            Value::Complex(ref c) if self.polar => {
+                let (r, theta) = c.to_polar();
+                format!("{}∠{}°", self.float(r), self.float(theta.to_degrees()))
+            }
+            Value::Complex(ref c) => complex(c, |x| self.float(x)),
+            Value::Quantity(ref q) => format!("{} {}", self.float(q.value()), q.unit()),
+            Value::Struct(ref s) => s.show(|value| self.value(value)),
+            Value::Bool(_) | Value::Unit => value.to_string(),
+        }
+    }
+
+    /// Whether the notation writes the value, which `bin` and `oct` only do
+    /// for integers. They write other numbers in decimal.
+    pub fn supports(&self, value: &Value) -> bool {
+        if !matches!(self.notation, Notation::Bin | Notation::Oct) {
+            return true;
+        }
+        // `inf` and `nan` are written as such
+        let radix_float = |x: f64| !x.is_finite() || whole_int(x);
+        match value {
+            Value::Ratio(_) | Value::Decimal(_) | Value::Float(_) => {
+                value.as_f64().is_some_and(radix_float)
+            }
+            Value::Complex(c) if self.polar => {
+                let (r, theta) = c.to_polar();
+                radix_float(r) && radix_float(theta.to_degrees())
+            }
+            Value::Complex(c) => radix_float(c.re) && radix_float(c.im),
+            Value::Quantity(q) => radix_float(q.value()),
+            Value::Struct(s) => s.fields().all(|(_, value)| self.supports(value)),
+            _ => true,
+        }
+    }
+
+    fn int(&self, i: i64) -> String {
+        if let Some((radix, prefix, group)) = self.notation.radix() {
+            let digits = match radix {
+                16 => format!("{:x}", i.unsigned_abs()),
+                2 => format!("{:b}", i.unsigned_abs()),
+                _ => format!("{:o}", i.unsigned_abs()),
+            };
+            let sign = if i < 0 { "-" } else { "" };
+            return format!("{sign}{prefix}{}", self.grouped(&digits, group));
+        }
+        match self.notation {
+            Notation::Sci | Notation::Eng => {
+                let sci = match self.digits {
+                    Some(digits) => format!("{:.*e}", digits - 1, i),
+                    None => format!("{i:e}"),
+                };
+                self.exponential(&sci)
+            }
+            _ => self.decimal(&i.to_string()),
+        }
+    }
+
+    fn big_int(&self, i: &BigInt) -> String {
+        let sign = if i.is_negative() { "-" } else { "" };
+        if let Some((radix, prefix, group)) = self.notation.radix() {
+            let digits = i.magnitude().to_str_radix(radix);
+            return format!("{sign}{prefix}{}", self.grouped(&digits, group));
+        }
+        match self.notation {
+            Notation::Sci | Notation::Eng => {
+                let sci = int_sci(&i.magnitude().to_string(), self.digits);
+                self.exponential(&format!("{sign}{sci}"))
+            }
+            _ => self.decimal(&i.to_string()),
+        }
+    }
+
+    fn float(&self, x: f64) -> String {
+        if !x.is_finite() {
+            return x.to_string();
+        }
+        if let Some((radix, ..)) = self.notation.radix() {
+            // whole floats read best as integers, others only have hex digits
+            if whole_int(x) {
+                return self.int(x as i64);
+            }
+            if radix != 16 {
+                return self.decimal(&format!("{x:?}"));
+            }
+            return hex_float(x);
+        }
+        let sci = match self.digits {
+            Some(digits) => format!("{:.*e}", digits - 1, x),
+            None => format!("{x:e}"),
+        };
+        match (self.notation, self.digits) {
+            (Notation::Sci | Notation::Eng, _) => self.exponential(&sci),
+            (_, None) => self.decimal(&format!("{x:?}")),
+            (_, Some(digits)) => {
+                let exp = exponent(&sci);
+                if exp < -5 || exp >= digits as i32 {
+                    sci
+                } else {
+                    let decimals = (digits as i32 - 1 - exp).max(0) as usize;
+                    self.decimal(&format!("{x:.decimals$}"))
+                }
+            }
+        }
+    }
+
+    /// Decimals are written in full, other than in radix notations where
+    /// they are floats.
+    fn big_decimal(&self, d: &BigDecimal) -> String {
+        if self.notation.radix().is_some() {
+            return self.float(
+                Value::Decimal(Box::new(d.clone()))
+                    .as_f64()
+                    .unwrap_or(f64::NAN),
+            );
+        }
+        let (int, scale) = d.as_bigint_and_exponent();
+        let sign = if int.is_negative() { "-" } else { "" };
+        let sci = int_sci(&int.magnitude().to_string(), self.digits);
+        let exp = match int.is_zero() {
+            true => 0,
+            false => exponent(&sci) as i64 - scale,
+        };
+        let mantissa = sci.split_once('e').map_or(sci.as_str(), |(m, _)| m);
+        let sci = format!("{sign}{mantissa}e{exp}");
+        match (self.notation, self.digits) {
+            (Notation::Sci | Notation::Eng, _) => self.exponential(&sci),
+            (_, None) => self.decimal(&decimal::to_string(d)),
+            (_, Some(digits)) => {
+                if exp < -5 || exp >= digits as i64 {
+                    sci
+                } else {
+                    let decimals = (digits as i64 - 1 - exp).max(0);
+                    let fixed = d.with_scale_round(decimals, RoundingMode::HalfUp);
+                    self.decimal(&fixed.to_plain_string())
+                }
+            }
+        }
+    }
+
+    /// Takes `d.ddde±x` as printed by `{:e}` to the notation.
+    fn exponential(&self, sci: &str) -> String {
+        if self.notation != Notation::Eng {
+            return sci.to_owned();
+        }
+        let (mantissa, exp) = sci.split_once('e').unwrap_or((sci, "0"));
+        let exp: i32 = exp.parse().unwrap_or(0);
+        let (sign, mantissa) = match mantissa.strip_prefix('-') {
+            Some(mantissa) => ("-", mantissa),
+            None => ("", mantissa),
+        };
+        let mut digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+        let shift = exp.rem_euclid(3) as usize;
+        while digits.len() <= shift {
+            digits.push('0');
+        }
+        let (int, frac) = digits.split_at(shift + 1);
+        let exp = exp - shift as i32;
+        match frac {
+            "" => format!("{sign}{int}e{exp}"),
+            frac => format!("{sign}{int}.{frac}e{exp}"),
+        }
+    }
+
+    /// Groups the integer part of a decimal number.
+    fn decimal(&self, number: &str) -> String {
+        if !self.group {
+            return number.to_owned();
+        }
+        let (sign, number) = match number.strip_prefix('-') {
+            Some(number) => ("-", number),
+            None => ("", number),
+        };
+        match number.split_once('.') {
+            Some((int, frac)) => format!("{sign}{}.{frac}", self.grouped(int, 3)),
+            None => format!("{sign}{}", self.grouped(number, 3)),
+        }
+    }
+
+    fn grouped(&self, digits: &str, size: usize) -> String {
+        if !self.group {
+            return digits.to_owned();
+        }
+        let mut out = String::with_capacity(digits.len() + digits.len() / size);
+        for (idx, c) in digits.chars().enumerate() {
+            if idx > 0 && (digits.len() - idx).is_multiple_of(size) {
+                out.push('_');
+            }
+            out.push(c);
+        }
+        out
+    }
+}
+
+/// Whether `x` is an integer that fits in an `i64`.
+fn whole_int(x: f64) -> bool {
+    x.fract() == 0.0 && x.abs() < i64::MAX as f64
+}
+
+/// `d.ddde<exp>` for the decimal digits of a whole number, rounded half up
+/// to `precision` significant digits. Exact where `{:e}` would go through a
+/// float.
+fn int_sci(digits: &str, precision: Option<usize>) -> String {
+    let mut exp = digits.len().saturating_sub(1);
+    let mantissa = match precision {
+        None => digits.trim_end_matches('0').to_owned(),
+        Some(n) if n >= digits.len() => format!("{digits:0<n$}"),
+        Some(n) => {
+            let mut kept = digits.as_bytes()[..n].to_vec();
+            if digits.as_bytes()[n] >= b'5' {
+                // the carry runs through trailing nines, up to a new digit
+                match kept.iter().rposition(|&d| d != b'9') {
+                    Some(idx) => {
+                        kept[idx] += 1;
+                        kept[idx + 1..].fill(b'0');
+                    }
+                    None => {
+                        kept.fill(b'0');
+                        kept[0] = b'1';
+                        exp += 1;
+                    }
+                }
+            }
+            String::from_utf8(kept).unwrap_or_default()
+        }
+    };
+    match mantissa.split_at(mantissa.len().min(1)) {
+        ("", _) => "0e0".to_owned(),
+        (lead, "") => format!("{lead}e{exp}"),
+        (lead, rest) => format!("{lead}.{rest}e{exp}"),
+    }
+}
+
+fn exponent(sci: &str) -> i32 {
+    sci.split_once('e')
+        .and_then(|(_, exp)| exp.parse().ok())
+        .unwrap_or(0)
+}
+
+/// `re+imi` with the parts written by `part`, just `imi` without a real part.
+pub(crate) fn complex(c: &Complex64, part: impl Fn(f64) -> String) -> String {
+    match (c.re, c.im) {
+        (re, im) if re == 0.0 && !re.is_sign_negative() && im != 0.0 => format!("{}i", part(im)),
+        (re, im) if im.is_sign_negative() => format!("{}-{}i", part(re), part(-im)),
+        (re, im) => format!("{}+{}i", part(re), part(im)),
+    }
+}
+
+/// `0x1.8p1` for 3.0, the mantissa digits are exact.
+fn hex_float(x: f64) -> String {
+    let sign = if x.is_sign_negative() { "-" } else { "" };
+    if x == 0.0 {
+        return format!("{sign}0x0p0");
+    }
+    let bits = x.to_bits();
+    let biased = ((bits >> 52) & 0x7ff) as i32;
+    let mantissa = bits & ((1 << 52) - 1);
+    let (lead, exp) = match biased {
+        0 => (0, -1022),
+        _ => (1, biased - 1023),
+    };
+    let frac = format!("{mantissa:013x}");
+    match frac.trim_end_matches('0') {
+        "" => format!("{sign}0x{lead}p{exp}"),
+        frac => format!("{sign}0x{lead}.{frac}p{exp}"),
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.notation.name())?;
+        if let Some(digits) = self.digits {
+            write!(f, " {digits}")?;
+        }
+        if self.group {
+            f.write_str(" _")?;
+        }
+        if self.polar {
+            f.write_str(" polar")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the words of a format, the error is the word that is not one.
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut format = Self::default();
+        for word in s.split(|c: char| c.is_whitespace() || c == ',') {
+            format.notation = match word {
+                "" => continue,
+                "dec" => Notation::Dec,
+                "hex" => Notation::Hex,
+                "bin" => Notation::Bin,
+                "oct" => Notation::Oct,
+                "sci" => Notation::Sci,
+                "eng" => Notation::Eng,
+                "_" => {
+                    format.group = true;
+                    continue;
+                }
+                "polar" => {
+                    format.polar = true;
+                    continue;
+                }
+                word => match word.parse() {
+                    Ok(digits @ 1..=MAX_DIGITS) => {
+                        format.digits = Some(digits);
+                        continue;
+                    }
+                    _ => return Err(word.to_owned()),
+                },
+            };
+        }
+        Ok(format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt(spec: &str, value: impl Into<Value>) -> String {
+        spec.parse::<Format>().unwrap().value(&value.into())
+    }
+
+    #[test]
+    fn radix() {
+        assert_eq!(fmt("hex", 255), "0xff");
+        assert_eq!(fmt("hex", -255), "-0xff");
+        assert_eq!(fmt("bin", 10), "0b1010");
+        assert_eq!(fmt("oct", 8), "0o10");
+        assert_eq!(fmt("hex _", 0xdeadbeef_i64), "0xdead_beef");
+        assert_eq!(fmt("bin _", 0b1_0000), "0b1_0000");
+        assert_eq!(fmt("hex", i64::MIN), "-0x8000000000000000");
+        assert_eq!(fmt("hex", 16.0), "0x10");
+        assert_eq!(fmt("hex", 3.0), "0x3");
+        assert_eq!(fmt("hex", 1.5), "0x1.8p0");
+        assert_eq!(fmt("hex", -0.1), "-0x1.999999999999ap-4");
+        assert_eq!(fmt("bin", 0.5), "0.5");
+    }
+
+    #[test]
+    fn supports() {
+        let supports = |spec: &str, value: Value| spec.parse::<Format>().unwrap().supports(&value);
+        assert!(supports("bin", Value::Float(4.0)));
+        assert!(supports("oct", Value::Float(f64::INFINITY)));
+        assert!(supports("hex", Value::Float(2.5)));
+        assert!(supports("dec", Value::Float(2.5)));
+        assert!(!supports("bin", Value::Float(2.5)));
+        assert!(!supports("oct", Value::Float(1e300)));
+        assert!(!supports(
+            "oct",
+            Value::from(num_complex::Complex64::new(1.0, 0.5))
+        ));
+        assert!(!supports(
+            "bin polar",
+            Value::from(num_complex::Complex64::new(1.0, 1.0))
+        ));
+        assert!(supports(
+            "bin polar",
+            Value::from(num_complex::Complex64::new(0.0, 2.0))
+        ));
+    }
+
+    #[test]
+    fn decimal() {
+        assert_eq!(fmt("dec", 1234567), "1234567");
+        assert_eq!(fmt("_", -1234567), "-1_234_567");
+        assert_eq!(fmt("_", 1234.5), "1_234.5");
+        assert_eq!(fmt("4", 1.0 / 3.0), "0.3333");
+        assert_eq!(fmt("4", 2.5), "2.500");
+        assert_eq!(fmt("3", 123456.0), "1.23e5");
+        assert_eq!(fmt("3", 0.000001234), "1.23e-6");
+        assert_eq!(fmt("2", 99.9), "1.0e2");
+        assert_eq!(fmt("3", 12), "12");
+        assert_eq!(fmt("dec", 0.1), "0.1");
+        assert_eq!(fmt("hex", f64::INFINITY), "inf");
+        assert_eq!(fmt("hex", true), "true");
+    }
+
+    #[test]
+    fn exponential() {
+        assert_eq!(fmt("sci", 1234), "1.234e3");
+        assert_eq!(fmt("sci 2", 1299), "1.3e3");
+        assert_eq!(fmt("sci", 0.00015), "1.5e-4");
+        assert_eq!(fmt("eng", 15000.0), "15e3");
+        assert_eq!(fmt("eng", 1234567), "1.234567e6");
+        assert_eq!(fmt("eng", 0.00015), "150e-6");
+        assert_eq!(fmt("eng 4", -123456.0), "-123.5e3");
+        assert_eq!(fmt("eng", 1.0), "1e0");
+    }
+
+    #[test]
+    fn big_ints() {
+        let big = |digits: &str| Value::BigInt(digits.parse().unwrap());
+        assert_eq!(
+            fmt("_", big("-12345678901234567890")),
+            "-12_345_678_901_234_567_890"
+        );
+        assert_eq!(
+            fmt("hex _", big("18446744073709551616")),
+            "0x1_0000_0000_0000_0000"
+        );
+        assert_eq!(
+            fmt("oct", big("-18446744073709551616")),
+            "-0o2000000000000000000000"
+        );
+        assert_eq!(fmt("sci", big("12300000000000000000000")), "1.23e22");
+        assert_eq!(fmt("sci 3", big("99960000000000000000")), "1.00e20");
+        assert_eq!(fmt("sci 2", big("-12500000000000000000")), "-1.3e19");
+        assert_eq!(fmt("eng 4", big("12345678901234567890")), "12.35e18");
+        assert_eq!(
+            fmt("sci 25", big("10000000000000000000")),
+            "1.000000000000000000000000e19"
+        );
+    }
+
+    #[test]
+    fn ratios() {
+        let ratio =
+            |n: i64, d: i64| Value::from(num_rational::BigRational::new(n.into(), d.into()));
+        assert_eq!(fmt("", ratio(-1, 3)), "-1/3");
+        assert_eq!(fmt("_", ratio(10001, 1000)), "10_001/1_000");
+        assert_eq!(fmt("4", ratio(1, 3)), "0.3333");
+        assert_eq!(fmt("sci", ratio(3, 2)), "1.5e0");
+        assert_eq!(fmt("", ratio(4, 2)), "2");
+    }
+
+    #[test]
+    fn complexes() {
+        let complex = |re: f64, im: f64| Value::from(num_complex::Complex64::new(re, im));
+        assert_eq!(fmt("", complex(3.0, -4.0)), "3.0-4.0i");
+        assert_eq!(fmt("", complex(0.0, 2.0)), "2.0i");
+        assert_eq!(fmt("", complex(-1.0, 0.0)), "-1.0+0.0i");
+        assert_eq!(fmt("3", complex(1.0 / 3.0, 2.0)), "0.333+2.00i");
+        assert_eq!(fmt("sci", complex(1500.0, 2.0)), "1.5e3+2e0i");
+        assert_eq!(fmt("polar", complex(0.0, -2.0)), "2.0∠-90.0°");
+        assert_eq!(fmt("polar 3", complex(3.0, 4.0)), "5.00∠53.1°");
+        assert_eq!(fmt("polar", Value::Float(2.0)), "2.0");
+    }
+
+    #[test]
+    fn decimals() {
+        let dec = |s: &str| Value::from(s.parse::<bigdecimal::BigDecimal>().unwrap());
+        assert_eq!(fmt("", dec("1.10")), "1.10");
+        assert_eq!(fmt("", dec("-2e-9")), "-2e-9");
+        assert_eq!(fmt("_", dec("12345.5")), "12_345.5");
+        assert_eq!(fmt("4", dec("2.71828")), "2.718");
+        assert_eq!(fmt("3", dec("0.0001234")), "0.000123");
+        assert_eq!(fmt("sci", dec("1500")), "1.5e3");
+        assert_eq!(fmt("eng 2", dec("0.00015")), "150e-6");
+        assert_eq!(fmt("hex", dec("255.0")), "0xff");
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("hex".parse(), Ok(Format::new(Notation::Hex)));
+        assert_eq!(
+            "sci, 4 _".parse(),
+            Ok(Format::new(Notation::Sci).digits(Some(4)).group(true))
+        );
+        assert_eq!("".parse(), Ok(Format::default()));
+        assert_eq!("hexa".parse::<Format>(), Err("hexa".to_owned()));
+        assert_eq!("0".parse::<Format>(), Err("0".to_owned()));
+        let format = Format::new(Notation::Eng)
+            .digits(Some(3))
+            .group(true)
+            .polar(true);
+        assert_eq!(format.to_string().parse(), Ok(format));
+    }
+}
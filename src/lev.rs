@@ -1,4 +1,3 @@
-#[allow(unused)]
 pub fn lev(a: impl AsRef<str>, b: impl AsRef<str>) -> usize {
     let a = a.as_ref();
     let b = b.as_ref();
@@ -55,7 +54,6 @@ pub fn lev(a: impl AsRef<str>, b: impl AsRef<str>) -> usize {
     result
 }
 
-#[allow(unused)]
 pub fn suggest_word(a: &'_ str, b: &'_ [&'_ str]) -> Vec<(usize, String)> {
     let mut item: Vec<_> = b
         .iter()
@@ -65,6 +63,27 @@ pub fn suggest_word(a: &'_ str, b: &'_ [&'_ str]) -> Vec<(usize, String)> {
     item
 }
 
+/// How many names a "did you mean" hint lists at most.
+pub const MAX_SUGGESTIONS: usize = 3;
+
+/// The `candidates` close enough to `word` to be what was meant, closest
+/// first. A third of the chars may be edited, rounded up, but never all of
+/// them.
+pub fn closest_words(word: &str, candidates: &[&str]) -> Vec<String> {
+    let len = word.chars().count();
+    let cutoff = len.div_ceil(3);
+    let mut words = Vec::new();
+    for (dist, candidate) in suggest_word(word, candidates) {
+        if dist > cutoff || words.len() == MAX_SUGGESTIONS {
+            break;
+        }
+        if dist > 0 && dist < len && !words.contains(&candidate) {
+            words.push(candidate);
+        }
+    }
+    words
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -80,4 +99,15 @@ mod tests {
         let result = lev("kitten", "kitten");
         assert_eq!(result, 0);
     }
+
+    #[test]
+    fn test_closest_words() {
+        let keywords = ["if", "while", "return", "for", "break"];
+        assert_eq!(closest_words("retrun", &keywords), ["return"]);
+        assert_eq!(closest_words("whiel", &keywords), ["while"]);
+        assert_eq!(closest_words("of", &keywords), ["if"]);
+        assert!(closest_words("x", &["e", "y"]).is_empty());
+        assert!(closest_words("banana", &keywords).is_empty());
+        assert_eq!(closest_words("ab", &["ab", "ac", "ac"]), ["ac"]);
+    }
 }
@@ -3,7 +3,7 @@ use crate::location::Loc;
 use derive_more::Display;
 use thiserror::Error;
 
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, PartialEq, Error)]
 pub enum LexErrorKind {
     #[error("{0}")]
     Any(String),
@@ -27,6 +27,8 @@ pub enum LexErrorKind {
     InvalidLongStringDelimiter,
     #[error("unfinished long string")]
     UnfinishedLongString,
+    #[error("unfinished block comment")]
+    UnfinishedBlockComment,
 
     #[error("invalid integer number {0}")]
     InvalidNumberInt(String),
@@ -34,7 +36,7 @@ pub enum LexErrorKind {
     InvalidNumberFloat(String),
 }
 
-#[derive(Debug, Display)]
+#[derive(Debug, Clone, Display)]
 #[display(fmt = "{loc}: ERROR - {kind}")]
 pub struct LexError<'loc> {
     kind: LexErrorKind,
@@ -61,6 +63,13 @@ impl<'loc> LexError<'loc> {
         self
     }
 
+    pub fn kind(&self) -> &LexErrorKind {
+        &self.kind
+    }
+    pub fn loc(&self) -> &Loc<'loc> {
+        &self.loc
+    }
+
     pub fn unfinished_short_string(s: char, loc: Loc<'loc>) -> Self {
         Self {
             kind: LexErrorKind::UnfinishedShortString(s),
@@ -1,42 +1,47 @@
 pub mod error;
+pub mod number;
 pub mod token;
 
 #[cfg(test)]
 mod tests;
 
 use std::borrow::Cow;
-use std::{iter::Peekable, str::Chars};
 
-use crate::location::Loc;
+use crate::diagnostic::Diagnostic;
+use crate::location::{Loc, Span, Spanned};
 
 use crate::error::{Error, Result};
 use error::LexError;
 use token::Token;
 
 use self::error::LexErrorKind;
-use crate::util::{is_ident, parse_hex_integer};
+use crate::util::{from_hex_digit, is_alpha, is_digit, is_hex_digit, is_space};
 
-#[derive(Debug)]
+/// Lexer over the bytes of the input. `loc` is the position of the next
+/// unread byte, tokens borrow their text from the input wherever possible.
+#[derive(Debug, Clone)]
 pub struct Lexer<'l> {
-    curr_char: char,
+    input: &'l str,
+    bytes: &'l [u8],
     loc: Loc<'l>,
-    buf: String,
-    source: Peekable<Chars<'l>>,
+    emit_comments: bool,
+    emit_trivia: bool,
+    recover: bool,
+    errors: Vec<LexError<'l>>,
 }
 
 impl<'l> Lexer<'l> {
-    const NULL_BYTE_CHAR: char = '\0';
-
     #[inline]
     pub fn new(s: &'l str) -> Lexer<'l> {
-        let mut s = Self {
-            curr_char: '\0',
-            loc: Loc::new(0, 0),
-            buf: String::new(),
-            source: s.chars().peekable(),
-        };
-        s.read_char();
-        s
+        Self {
+            input: s,
+            bytes: s.as_bytes(),
+            loc: Loc::new(1, 1),
+            emit_comments: false,
+            emit_trivia: false,
+            recover: false,
+            errors: Vec::new(),
+        }
     }
 
     #[inline]
@@ -44,64 +49,237 @@ impl<'l> Lexer<'l> {
         self.loc = self.loc.set_source(path);
         self
     }
+
+    /// Emit plain comments as [`Token::Comment`] instead of skipping them,
+    /// doc comments are always emitted as [`Token::DocComment`].
+    #[inline]
+    pub fn emit_comments(mut self, emit: bool) -> Self {
+        self.emit_comments = emit;
+        self
+    }
+
+    /// Lossless mode: whitespace, newlines and comments are emitted as
+    /// tokens too, so the spans of all tokens tile the whole input and
+    /// concatenating their text reproduces it byte for byte.
+    #[inline]
+    pub fn emit_trivia(mut self, emit: bool) -> Self {
+        self.emit_trivia = emit;
+        self.emit_comments |= emit;
+        self
+    }
+
+    /// Recovery mode: bad input is emitted as a [`Token::Illegal`] spanning
+    /// it and lexing goes on, the errors are collected in [`Lexer::errors`]
+    /// instead of being returned.
+    #[inline]
+    pub fn recover(mut self, recover: bool) -> Self {
+        self.recover = recover;
+        self
+    }
+
+    /// Errors collected so far in recovery mode, in source order.
+    #[inline]
+    pub fn errors(&self) -> &[LexError<'l>] {
+        &self.errors
+    }
+
+    #[inline]
+    pub fn take_errors(&mut self) -> Vec<LexError<'l>> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// The collected errors, ready to be rendered.
+    pub fn diagnostics(&self) -> Vec<Diagnostic<'l>> {
+        self.errors.iter().map(Diagnostic::from).collect()
+    }
 }
 
 impl<'l> Lexer<'l> {
+    /// Rewinds to the start of the input, dropping collected errors.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.loc.set_line(1);
+        self.loc.set_col(1);
+        self.loc.set_offset(0);
+        self.errors.clear();
+    }
+
     #[inline]
-    #[allow(unused)]
-    pub fn reset(&mut self) {}
+    fn is_eof(&self) -> bool {
+        self.loc.offset() >= self.bytes.len()
+    }
 
+    /// The byte `n` places after the cursor, `0` past the end of input.
     #[inline]
-    fn on_newline(&mut self) {
-        if matches!(self.curr_char, '\n' | '\r') {
-            self.loc.inc_line();
-            self.loc.set_col(0);
-        } else {
-            self.loc.inc_col();
-        }
+    fn peek_at(&self, n: usize) -> u8 {
+        self.bytes.get(self.loc.offset() + n).copied().unwrap_or(0)
+    }
+
+    #[inline]
+    fn peek(&self) -> u8 {
+        self.peek_at(0)
     }
 
-    #[allow(unused)]
     #[inline]
-    fn read_char_if(&mut self, pred: impl FnOnce(&char) -> bool) -> Option<&char> {
-        match self.source.next_if(pred) {
-            Some(s) => {
-                self.on_newline();
-                self.curr_char = s;
-                Some(&self.curr_char)
+    fn rest(&self) -> &'l [u8] {
+        &self.bytes[self.loc.offset()..]
+    }
+
+    #[inline]
+    fn peek_char(&self) -> Option<char> {
+        self.input[self.loc.offset()..].chars().next()
+    }
+
+    /// Moves the cursor `n` bytes ahead, onto a char boundary. Columns count
+    /// chars, so UTF-8 continuation bytes do not take any room.
+    #[inline]
+    fn bump(&mut self, n: usize) {
+        let bytes = self.bytes;
+        let start = self.loc.offset();
+        for &b in &bytes[start..start + n] {
+            if b == b'\n' {
+                self.loc.inc_line();
+                self.loc.set_col(1);
+            } else if b & 0xC0 != 0x80 {
+                self.loc.inc_col();
             }
-            None => None,
         }
+        self.loc.inc_offset(n);
     }
+
+    /// Length in bytes of the whitespace char at the cursor, `0` if there
+    /// is none.
     #[inline]
-    fn read_char(&mut self) -> &char {
-        self.on_newline();
-        self.curr_char = self.source.next().unwrap_or(Self::NULL_BYTE_CHAR);
-        &self.curr_char
+    fn whitespace_len(&self) -> usize {
+        match self.peek() {
+            b if is_space(b) => 1,
+            b if b >= 0x80 => self
+                .peek_char()
+                .filter(|c| c.is_whitespace())
+                .map_or(0, char::len_utf8),
+            _ => 0,
+        }
     }
 
     #[inline]
     fn skip_whitespace(&mut self) {
-        while self.curr_char.is_whitespace() {
-            dbg!(&self.curr_char);
+        while !self.emit_trivia {
+            match self.whitespace_len() {
+                0 => break,
+                n => self.bump(n),
+            }
+        }
+    }
+
+    /// Reads a newline (`\n` or `\r\n`) or a run of other whitespace.
+    fn read_whitespace(&mut self) -> Token<'l> {
+        let start = self.loc.offset();
+        match (self.peek(), self.peek_at(1)) {
+            (b'\n', _) => {
+                self.bump(1);
+                return Token::Newline;
+            }
+            (b'\r', b'\n') => {
+                self.bump(2);
+                return Token::Newline;
+            }
+            _ => {}
+        }
+        let n = self.whitespace_len();
+        self.bump(n);
+        while !matches!(self.peek(), b'\n' | b'\r') {
+            match self.whitespace_len() {
+                0 => break,
+                n => self.bump(n),
+            }
+        }
+        Token::Whitespace(Cow::Borrowed(&self.input[start..self.loc.offset()]))
+    }
+
+    /// Reads the comment at the cursor, if there is one.
+    ///
+    /// `# ...` runs to the end of the line and `/* ... */` blocks nest, `##`
+    /// and `/**` start doc comments.
+    fn read_comment(&mut self) -> Option<Result<'l, Token<'l>>> {
+        let start = self.loc.clone();
+        let rest = self.rest();
+
+        let (is_doc, marker, len, text_len) = match rest {
+            [b'#', ..] => {
+                let is_doc = rest.starts_with(b"##") && !rest.starts_with(b"###");
+                // the line ending, `\r\n` included, is not part of the comment
+                let mut len = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+                if len < rest.len() && rest[..len].ends_with(b"\r") {
+                    len -= 1;
+                }
+                (is_doc, if is_doc { 2 } else { 1 }, len, len)
+            }
+            [b'/', b'*', ..] => {
+                let is_doc = rest.starts_with(b"/**")
+                    && !rest.starts_with(b"/**/")
+                    && !rest.starts_with(b"/***");
+                let mut depth = 1;
+                let mut len = 2;
+                while depth > 0 {
+                    match &rest[len..] {
+                        [] => {
+                            self.bump(len);
+                            return Some(Err(
+                                self.error(LexErrorKind::UnfinishedBlockComment, start)
+                            ));
+                        }
+                        [b'/', b'*', ..] => {
+                            depth += 1;
+                            len += 2;
+                        }
+                        [b'*', b'/', ..] => {
+                            depth -= 1;
+                            len += 2;
+                        }
+                        _ => len += 1,
+                    }
+                }
+                (is_doc, if is_doc { 3 } else { 2 }, len, len - 2)
+            }
+            _ => return None,
+        };
+
+        let text = Cow::Borrowed(&self.input[start.offset() + marker..start.offset() + text_len]);
+        self.bump(len);
+        Some(Ok(if is_doc {
+            Token::DocComment(text)
+        } else {
+            Token::Comment(text)
+        }))
+    }
+
+    /// Whether an identifier starts at the cursor: ASCII letters and `_`,
+    /// or any alphanumeric char outside of ASCII.
+    #[inline]
+    fn at_ident_start(&self) -> bool {
+        match self.peek() {
+            b if is_alpha(b) => true,
+            b if b >= 0x80 => self.peek_char().is_some_and(char::is_alphanumeric),
+            _ => false,
         }
     }
 
     fn read_ident(&mut self) -> Token<'l> {
-        self.buf.clear();
-        self.buf.push(self.curr_char);
-        while self
-            .source
-            .peek()
-            .filter(|s| s.is_ascii_alphanumeric() || matches!(s, '_'))
-            .is_some()
-        {
-            self.read_char();
-            self.buf.push(self.curr_char);
+        let start = self.loc.offset();
+        let mut end = start;
+        loop {
+            match self.bytes.get(end) {
+                Some(&b) if is_alpha(b) || is_digit(b) => end += 1,
+                Some(&b) if b >= 0x80 => match self.input[end..].chars().next() {
+                    Some(c) if c.is_alphanumeric() => end += c.len_utf8(),
+                    _ => break,
+                },
+                _ => break,
+            }
         }
-        // dbg!(&self.buf, self.curr_char);
+        self.bump(end - start);
 
-        match self.buf.as_str() {
+        match &self.input[start..end] {
             "if" => Token::If,
             "else" => Token::Else,
             "elif" => Token::Elif,
@@ -113,171 +291,340 @@ impl<'l> Lexer<'l> {
             "struct" => Token::Struct,
             "true" => Token::True,
             "false" => Token::False,
-            _ => Token::ident(self.buf.clone()),
+            ident => Token::Ident(Cow::Borrowed(ident)),
         }
     }
 
-    fn read_num(&mut self) -> Result<'l, Token<'l>> {
-        let mut s = String::with_capacity(16);
-        let mut has_radix = false;
-        let mut is_float = false;
-
-        let is_hex = if matches!(self.curr_char, '0') {
-            s.push(self.curr_char);
-            if matches!(self.source.peek(), Some('x') | Some('X')) {
-                s.push(*self.read_char());
-                self.read_char();
-                true
-            } else {
-                false
+    /// `$1`, `$2`... name the fields of a line in the `-e` filter mode.
+    fn read_field(&mut self) -> Token<'l> {
+        let start = self.loc.offset();
+        let digits = self.rest()[1..]
+            .iter()
+            .take_while(|&&b| is_digit(b))
+            .count();
+        self.bump(1 + digits);
+        Token::Ident(Cow::Borrowed(&self.input[start..start + 1 + digits]))
+    }
+
+    fn error(&self, kind: LexErrorKind, loc: Loc<'l>) -> Error<'l> {
+        Error::lexing_error(LexError::new(kind, loc))
+    }
+
+    /// Reads a `'...'` or `"..."` string, borrowing it from the input unless
+    /// it contains escapes. On error the rest of the string is skipped.
+    fn read_short_string(&mut self) -> Result<'l, Token<'l>> {
+        let quote = self.peek();
+        let start = self.loc.clone();
+        self.bump(1);
+        let content_start = self.loc.offset();
+        let mut owned: Option<String> = None;
+
+        loop {
+            let run = self
+                .rest()
+                .iter()
+                .position(|&b| b == quote || matches!(b, b'\\' | b'\n'))
+                .unwrap_or(self.rest().len());
+            if let Some(buf) = &mut owned {
+                let offset = self.loc.offset();
+                buf.push_str(&self.input[offset..offset + run]);
             }
-        } else {
-            false
-        };
+            self.bump(run);
 
-        'num_loop: loop {
-            match self.curr_char {
-                '0'..='9' | 'a'..='f' => s.push(self.curr_char),
-                'A'..='F' => s.push(self.curr_char.to_ascii_lowercase()),
-                '-' | '+' if matches!(s.chars().last(), Some('e')) => {
-                    s.push(self.curr_char);
-                    is_float = true;
+            match self.peek() {
+                // the line break is left out of an unfinished string
+                _ if self.is_eof() => break,
+                b'\n' => break,
+                b'\\' if self.loc.offset() + 1 == self.bytes.len() => {
+                    self.bump(1);
+                    break;
                 }
-                '.' if !has_radix => {
-                    s.push(self.curr_char);
-                    has_radix = true;
-                    is_float = true;
+                b'\\' => {
+                    let prefix_end = self.loc.offset();
+                    match self.read_escape() {
+                        Ok(c) => owned
+                            .get_or_insert_with(|| self.input[content_start..prefix_end].to_owned())
+                            .push(c),
+                        Err(err) => {
+                            while !self.is_eof() && self.peek() != b'\n' {
+                                let b = self.peek();
+                                self.bump(self.peek_char().map_or(1, char::len_utf8));
+                                if b == quote {
+                                    break;
+                                }
+                            }
+                            return Err(Error::lexing_error(err));
+                        }
+                    }
                 }
-                '.' => {
-                    self.read_char();
-                    return Err(Error::lexing_error(LexError::new(
-                        LexErrorKind::InvalidNumberInt(format!(
-                            "misplace decimal radix '{}'",
-                            self.curr_char
-                        )),
-                        self.loc.clone(),
-                    )));
+                _ => {
+                    let content_end = self.loc.offset();
+                    self.bump(1);
+                    return Ok(Token::Str(match owned {
+                        Some(s) => Cow::Owned(s),
+                        None => Cow::Borrowed(&self.input[content_start..content_end]),
+                    }));
                 }
-                '_' | ',' => {}
-                _ => break 'num_loop,
             }
-            self.read_char();
-        }
-        if has_radix || is_float {
-            return s.parse::<f64>().map(Token::Float).map_err(|err| {
-                Error::lexing_error(LexError::new(
-                    LexErrorKind::InvalidNumberFloat(format!("number: {s} - {err}")),
-                    self.loc.clone(),
-                ))
-            });
         }
-        if is_hex {
-            return parse_hex_integer(s).map(Token::Int).map_err(|err| {
-                Error::lexing_error(LexError::new(
-                    LexErrorKind::InvalidNumberInt(err.to_string()),
-                    self.loc.clone(),
-                ))
-            });
-        }
-        s.parse::<u64>().map(Token::Int).map_err(|err| {
-            Error::lexing_error(LexError::new(
-                LexErrorKind::InvalidNumberInt(format!("{s} - {err}")),
-                self.loc.clone(),
-            ))
-        })
+        Err(self.error(LexErrorKind::UnfinishedShortString(quote as char), start))
     }
-}
 
-impl<'lit> Iterator for Lexer<'lit> {
-    type Item = Result<'lit, Token<'lit>>;
+    /// Reads the escape sequence at the `\` under the cursor. On error the
+    /// cursor is left on the offending char.
+    fn read_escape(&mut self) -> std::result::Result<char, LexError<'l>> {
+        let escape_loc = self.loc.clone();
+        self.bump(1);
 
-    fn next(&mut self) -> Option<Self::Item> {
-        macro_rules! tok {
-            ($t:expr) => {{
-                self.read_char();
-                $t
-            }};
-
-            (NEXT_TOKEN => ($($c:literal THEN $t:expr),* $(,)?) OR $def:expr) => {{
-                match self.source.peek() {
-                    $(Some($c) => tok!($t),)*
-                    _ => $def,
-                }
-            }};
+        let simple = match self.peek() {
+            b'a' => Some('\x07'),
+            b'b' => Some('\x08'),
+            b'f' => Some('\x0C'),
+            b'n' => Some('\n'),
+            b'r' => Some('\r'),
+            b't' => Some('\t'),
+            b'v' => Some('\x0B'),
+            c @ (b'\\' | b'"' | b'\'' | b'\n') => Some(c as char),
+            _ => None,
+        };
+        if let Some(c) = simple {
+            self.bump(1);
+            return Ok(c);
         }
 
-        self.skip_whitespace();
-        let token = match self.curr_char {
-            '{' => Token::LSquirly,
-            '(' => Token::LParen,
-            '[' => Token::LBracket,
-            '}' => Token::RSquirly,
-            ')' => Token::RParen,
-            ']' => Token::RBracket,
-            ',' => Token::Comma,
-            ';' => Token::SemiColon,
-            '!' => tok!(NEXT_TOKEN => ('=' THEN Token::Ne) OR Token::Not),
-            '%' => tok!(NEXT_TOKEN => ('=' THEN Token::RemAssign) OR Token::Rem),
-            '+' => tok!(NEXT_TOKEN => ('=' THEN Token::AddAssign) OR Token::Add),
-            '~' => {
-                tok!(NEXT_TOKEN => ('=' THEN Token::BitNotXorAssign) OR Token::BitNotXor)
-            }
-
-            '*' => {
-                tok!(NEXT_TOKEN => ('*' THEN Token::Pow, '=' THEN Token::MulAssign) OR Token::Mul)
-            }
-            '=' => {
-                tok!(NEXT_TOKEN => ('=' THEN Token::Eq,  '>' THEN Token::FatArrow) OR Token::Assign)
-            }
-            '-' => {
-                tok!(NEXT_TOKEN => ('>' THEN Token::Arrow, '=' THEN Token::SubAssign) OR Token::Sub)
-            }
-            ':' => {
-                tok!(NEXT_TOKEN => (':' THEN Token::Decl,'=' THEN Token::DeclAssign) OR Token::Colon)
-            }
-            '|' => {
-                tok!(NEXT_TOKEN => ('=' THEN Token::BitOrAssign, '|' THEN Token::LogicOr) OR Token::BitOr)
-            }
-            '&' => {
-                tok!(NEXT_TOKEN => ('=' THEN Token::BitAndAssign, '&' THEN Token::LogicAnd) OR Token::BitAnd)
+        let c = match self.peek() {
+            b'x' => {
+                self.bump(1);
+                let mut value = 0;
+                for _ in 0..2 {
+                    if !is_hex_digit(self.peek()) {
+                        return Err(LexError::new(
+                            LexErrorKind::HexDigitExpected,
+                            self.loc.clone(),
+                        ));
+                    }
+                    value = value * 16 + from_hex_digit(self.peek()).unwrap_or_default() as u32;
+                    self.bump(1);
+                }
+                char::from_u32(value).unwrap_or_default()
             }
-            '.' => {
-                tok!(NEXT_TOKEN => ('.' THEN tok!( NEXT_TOKEN => ('=' THEN Token::RangeInc) OR Token::Range)) OR Token::Dot)
+            b'0'..=b'9' => {
+                let mut value = 0;
+                for _ in 0..3 {
+                    if !is_digit(self.peek()) {
+                        break;
+                    }
+                    value = value * 10 + (self.peek() - b'0') as u32;
+                    self.bump(1);
+                }
+                if value > 255 {
+                    return Err(LexError::new(
+                        LexErrorKind::EscapeDecimalTooLarge,
+                        escape_loc,
+                    ));
+                }
+                char::from_u32(value).unwrap_or_default()
             }
-            '<' => {
-                tok!(NEXT_TOKEN => ('<' THEN tok!( NEXT_TOKEN => ('=' THEN Token::ShlAssign) OR Token::Shl),'=' THEN Token::Lte) OR Token::Lt)
+            b'u' => {
+                self.bump(1);
+                if self.peek() != b'{' {
+                    return Err(LexError::new(
+                        LexErrorKind::EscapeUnicodeStart,
+                        self.loc.clone(),
+                    ));
+                }
+                self.bump(1);
+                let mut value: u32 = 0;
+                let mut digits = 0;
+                while let Some(digit) = from_hex_digit(self.peek()) {
+                    self.bump(1);
+                    digits += 1;
+                    value = value.saturating_mul(16).saturating_add(digit as u32);
+                }
+                if digits == 0 {
+                    return Err(LexError::new(
+                        LexErrorKind::HexDigitExpected,
+                        self.loc.clone(),
+                    ));
+                }
+                if self.peek() != b'}' {
+                    return Err(LexError::new(
+                        LexErrorKind::EscapeUnicodeEnd,
+                        self.loc.clone(),
+                    ));
+                }
+                self.bump(1);
+                char::from_u32(value)
+                    .ok_or_else(|| LexError::new(LexErrorKind::EscapeUnicodeInvalid, escape_loc))?
             }
-            '>' => {
-                tok!(NEXT_TOKEN => ('>' THEN tok!( NEXT_TOKEN => ('=' THEN Token::ShrAssign) OR Token::Shr), '=' THEN Token::Gte) OR Token::Gt)
+            _ => return Err(LexError::new(LexErrorKind::InvalidEscape, self.loc.clone())),
+        };
+        Ok(c)
+    }
+
+    /// Reads a `[[...]]` or `[==[...]==]` string, its contents are taken
+    /// verbatim except for a newline right after the opening delimiter.
+    fn read_long_string(&mut self) -> Result<'l, Token<'l>> {
+        let start = self.loc.clone();
+        let rest = self.rest();
+        let level = rest[1..].iter().take_while(|&&b| b == b'=').count();
+        if rest.get(level + 1) != Some(&b'[') {
+            self.bump(level + 1);
+            return Err(self.error(LexErrorKind::InvalidLongStringDelimiter, start));
+        }
+        let mut open = level + 2;
+        if rest.get(open) == Some(&b'\n') {
+            open += 1;
+        }
+
+        let close = format!("]{}]", "=".repeat(level));
+        let content = &self.input[start.offset() + open..];
+        let Some(content_len) = content.find(&close) else {
+            self.bump(rest.len());
+            return Err(self.error(LexErrorKind::UnfinishedLongString, start));
+        };
+        self.bump(open + content_len + close.len());
+        Ok(Token::Str(Cow::Borrowed(&content[..content_len])))
+    }
+
+    /// Reads the number literal at the cursor, see [`number`] for the
+    /// grammar. On error the whole malformed literal is skipped.
+    fn read_num(&mut self) -> Result<'l, Token<'l>> {
+        let start = self.loc.clone();
+        match number::scan_number(self.rest()) {
+            Ok((token, len)) => {
+                self.bump(len);
+                Ok(token)
             }
-            '/' => {
-                tok!(NEXT_TOKEN => ('/' THEN tok!( NEXT_TOKEN => ('=' THEN Token::IDivAssign) OR Token::IDiv),'=' THEN Token::DivAssign) OR Token::Div)
+            Err(err) => {
+                // literals never span lines, so the bad digit is `pos` chars in
+                let mut loc = start;
+                loc.set_col(loc.col() + err.pos as u32);
+                loc.set_offset(loc.offset() + err.pos);
+                self.bump(err.len);
+                Err(self.error(err.kind, loc))
             }
-            '^' => {
-                tok!(NEXT_TOKEN => ('^' THEN tok!( NEXT_TOKEN => ('=' THEN Token::BitXorAssign) OR Token::BitXor),'=' THEN Token::PowAssign) OR Token::Pow)
+        }
+    }
+
+    /// Reads an operator or punctuation token, `None` if there is none at
+    /// the cursor.
+    fn read_symbol(&mut self) -> Option<Token<'l>> {
+        let (len, token) = match (self.peek(), self.peek_at(1), self.peek_at(2)) {
+            (b'{', ..) => (1, Token::LSquirly),
+            (b'}', ..) => (1, Token::RSquirly),
+            (b'(', ..) => (1, Token::LParen),
+            (b')', ..) => (1, Token::RParen),
+            (b'[', ..) => (1, Token::LBracket),
+            (b']', ..) => (1, Token::RBracket),
+            (b',', ..) => (1, Token::Comma),
+            (b';', ..) => (1, Token::SemiColon),
+
+            (b'!', b'=', _) => (2, Token::Ne),
+            (b'!', ..) => (1, Token::Not),
+            (b'%', b'=', _) => (2, Token::RemAssign),
+            (b'%', ..) => (1, Token::Rem),
+            (b'+', b'=', _) => (2, Token::AddAssign),
+            (b'+', ..) => (1, Token::Add),
+            (b'~', b'=', _) => (2, Token::BitNotXorAssign),
+            (b'~', ..) => (1, Token::BitNotXor),
+            (b'*', b'*', _) => (2, Token::Pow),
+            (b'*', b'=', _) => (2, Token::MulAssign),
+            (b'*', ..) => (1, Token::Mul),
+            (b'=', b'=', _) => (2, Token::Eq),
+            (b'=', b'>', _) => (2, Token::FatArrow),
+            (b'=', ..) => (1, Token::Assign),
+            (b'-', b'>', _) => (2, Token::Arrow),
+            (b'-', b'=', _) => (2, Token::SubAssign),
+            (b'-', ..) => (1, Token::Sub),
+            (b':', b':', _) => (2, Token::Decl),
+            (b':', b'=', _) => (2, Token::DeclAssign),
+            (b':', ..) => (1, Token::Colon),
+            (b'|', b'=', _) => (2, Token::BitOrAssign),
+            (b'|', b'|', _) => (2, Token::LogicOr),
+            (b'|', ..) => (1, Token::BitOr),
+            (b'&', b'=', _) => (2, Token::BitAndAssign),
+            (b'&', b'&', _) => (2, Token::LogicAnd),
+            (b'&', ..) => (1, Token::BitAnd),
+
+            (b'.', b'.', b'=') => (3, Token::RangeInc),
+            (b'.', b'.', _) => (2, Token::Range),
+            (b'.', ..) => (1, Token::Dot),
+            (b'<', b'<', b'=') => (3, Token::ShlAssign),
+            (b'<', b'<', _) => (2, Token::Shl),
+            (b'<', b'=', _) => (2, Token::Lte),
+            (b'<', ..) => (1, Token::Lt),
+            (b'>', b'>', b'=') => (3, Token::ShrAssign),
+            (b'>', b'>', _) => (2, Token::Shr),
+            (b'>', b'=', _) => (2, Token::Gte),
+            (b'>', ..) => (1, Token::Gt),
+            (b'/', b'/', b'=') => (3, Token::IDivAssign),
+            (b'/', b'/', _) => (2, Token::IDiv),
+            (b'/', b'=', _) => (2, Token::DivAssign),
+            (b'/', ..) => (1, Token::Div),
+            (b'^', b'^', b'=') => (3, Token::BitXorAssign),
+            (b'^', b'^', _) => (2, Token::BitXor),
+            (b'^', b'=', _) => (2, Token::PowAssign),
+            (b'^', ..) => (1, Token::Pow),
+            _ => return None,
+        };
+        self.bump(len);
+        Some(token)
+    }
+}
+
+impl<'l> Lexer<'l> {
+    /// Lexes the next token, moving the cursor past it. Returns where the
+    /// token starts along with the token or the error, the bad input being
+    /// skipped in the latter case.
+    fn lex_token(&mut self) -> Option<(Loc<'l>, Result<'l, Token<'l>>)> {
+        loop {
+            self.skip_whitespace();
+            let start = self.loc.clone();
+            match self.read_comment() {
+                None => break,
+                Some(Ok(Token::Comment(_))) if !self.emit_comments => continue,
+                Some(comment) => return Some((start, comment)),
             }
+        }
+        if self.is_eof() {
+            return None;
+        }
 
-            '0'..='9' => match self.read_num() {
-                Ok(k) => k,
-                Err(err) => {
-                    self.read_char();
-                    return Some(Err(err));
+        let start = self.loc.clone();
+        let token = match self.peek() {
+            _ if self.emit_trivia && self.whitespace_len() > 0 => Ok(self.read_whitespace()),
+            b'[' if matches!(self.peek_at(1), b'[' | b'=') => self.read_long_string(),
+            b'"' | b'\'' => self.read_short_string(),
+            b'0'..=b'9' => self.read_num(),
+            _ if self.at_ident_start() => Ok(self.read_ident()),
+            b'$' if is_digit(self.peek_at(1)) => Ok(self.read_field()),
+            _ => match self.read_symbol() {
+                Some(token) => Ok(token),
+                None => {
+                    let c = self.peek_char().unwrap_or_default();
+                    self.bump(c.len_utf8());
+                    Err(self.error(LexErrorKind::UnexpectedCharacter(c), start.clone()))
                 }
             },
-            c if is_ident(&c) => self.read_ident(),
+        };
+        Some((start, token))
+    }
+}
 
-            Self::NULL_BYTE_CHAR => return None,
+impl<'lit> Iterator for Lexer<'lit> {
+    type Item = Result<'lit, Spanned<'lit, Token<'lit>>>;
 
-            _ => {
-                let r = Some(Err(Error::lexing_error(LexError::new(
-                    LexErrorKind::UnexpectedCharacter(self.curr_char),
-                    self.loc.clone(),
-                ))));
-                self.read_char();
-                return r;
+    fn next(&mut self) -> Option<Self::Item> {
+        let (start, token) = self.lex_token()?;
+        let span = Span::new(start, self.loc.clone());
+        match token {
+            Ok(token) => Some(Ok(Spanned::new(token, span))),
+            Err(Error::LexingError(err)) if self.recover => {
+                self.errors.push(err);
+                Some(Ok(Spanned::new(Token::Illegal, span)))
             }
-        };
-        self.read_char();
-        Some(Ok(token))
+            Err(err) => Some(Err(err)),
+        }
     }
 }
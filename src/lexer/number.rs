@@ -0,0 +1,276 @@
+//! Numeric literal grammar:
+//!
+//! ```text
+//! number  := (dec | hex | oct | bin) suffix?
+//! dec     := digits ('.' digits?)? (('e' | 'E') ('+' | '-')? digits)?
+//! hex     := '0x' hexdigits ('.' hexdigits?)? (('p' | 'P') ('+' | '-')? digits)?
+//! oct     := '0o' octdigits
+//! bin     := '0b' bindigits
+//! suffix  := 'i8' | 'i16' | 'i32' | 'i64' | 'u8' | 'u16' | 'u32' | 'u64' | 'f32' | 'f64' | 'i'
+//! ```
+//!
+//! Digits may be separated with `_`. A hexadecimal literal with a fraction
+//! needs the binary `p` exponent, as in C99. Integers without a suffix that
+//! do not fit in 64 bits are [`Token::BigInt`]s, and decimal floats without
+//! a suffix are [`Token::Decimal`]s. The `i` suffix makes an imaginary
+//! [`Token::Imag`] of any number. Any other suffix that is a unit ends the
+//! literal, so that `5km` is the quantity `5 km`.
+
+use std::borrow::Cow;
+
+use bigdecimal::BigDecimal;
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+
+use super::error::LexErrorKind;
+use super::token::Token;
+use crate::units::Unit;
+use crate::util::{from_hex_digit, is_alpha, is_digit, parse_hex_float};
+
+/// An error at byte `pos` of a literal spanning `len` bytes, `pos` may point
+/// right past the literal.
+#[derive(Debug, PartialEq)]
+pub(crate) struct NumberError {
+    pub kind: LexErrorKind,
+    pub pos: usize,
+    pub len: usize,
+}
+
+type ScanResult = std::result::Result<(Token<'static>, usize), NumberError>;
+
+const INT_SUFFIXES: &[(&str, u64)] = &[
+    ("i8", i8::MAX as u64),
+    ("i16", i16::MAX as u64),
+    ("i32", i32::MAX as u64),
+    ("i64", i64::MAX as u64),
+    ("u8", u8::MAX as u64),
+    ("u16", u16::MAX as u64),
+    ("u32", u32::MAX as u64),
+    ("u64", u64::MAX),
+];
+
+#[inline]
+const fn is_ident_byte(c: u8) -> bool {
+    is_alpha(c) || is_digit(c) || c >= 0x80
+}
+
+#[inline]
+fn digit_value(c: u8, radix: u32) -> Option<u32> {
+    from_hex_digit(c).map(u32::from).filter(|d| *d < radix)
+}
+
+const fn radix_name(radix: u32) -> &'static str {
+    match radix {
+        2 => "binary",
+        8 => "octal",
+        16 => "hexadecimal",
+        _ => "decimal",
+    }
+}
+
+/// Scans the number literal at the start of `input`, which has to start with
+/// an ASCII digit. Returns the token and the length of the literal in bytes.
+pub(crate) fn scan_number(input: &[u8]) -> ScanResult {
+    let at = |i: usize| input.get(i).copied().unwrap_or(0);
+
+    // the whole run of literal chars, skipped over when the literal is bad
+    let mut full_len = 0;
+    while is_ident_byte(at(full_len))
+        || (at(full_len) == b'.' && is_digit(at(full_len + 1)))
+        || (full_len > 0
+            && matches!(at(full_len), b'+' | b'-')
+            && matches!(at(full_len - 1), b'e' | b'E' | b'p' | b'P'))
+    {
+        full_len += 1;
+    }
+    let err = |kind: LexErrorKind, pos: usize| NumberError {
+        kind,
+        pos,
+        len: full_len,
+    };
+
+    let (radix, mut i) = match (at(0), at(1)) {
+        (b'0', b'x' | b'X') => (16, 2),
+        (b'0', b'o' | b'O') => (8, 2),
+        (b'0', b'b' | b'B') => (2, 2),
+        _ => (10, 0),
+    };
+    let prefix = i;
+
+    let read_digits = |i: &mut usize, radix: u32| {
+        let mut count = 0;
+        while at(*i) == b'_' || digit_value(at(*i), radix).is_some() {
+            if at(*i) != b'_' {
+                count += 1;
+            }
+            *i += 1;
+        }
+        count
+    };
+
+    let int_digits = read_digits(&mut i, radix);
+    // C99 allows hex floats without integer part, `0x.8p1`
+    if int_digits == 0 && radix != 10 && !(radix == 16 && at(i) == b'.') {
+        return Err(err(
+            LexErrorKind::InvalidNumberInt(format!(
+                "expected {} digits after `{}`",
+                radix_name(radix),
+                std::str::from_utf8(&input[..prefix]).unwrap_or_default()
+            )),
+            i,
+        ));
+    }
+
+    let mut is_float = false;
+    // `1..2` is a range and `1.max` a field access, not fractions
+    let next = at(i + 1);
+    if at(i) == b'.'
+        && next != b'.'
+        && (!is_alpha(next) || (radix == 16 && digit_value(next, 16).is_some()))
+    {
+        if matches!(radix, 2 | 8) {
+            return Err(err(
+                LexErrorKind::InvalidNumberFloat(format!(
+                    "{} literals cannot have a fraction",
+                    radix_name(radix)
+                )),
+                i,
+            ));
+        }
+        is_float = true;
+        i += 1;
+        read_digits(&mut i, radix);
+    }
+
+    let exp_marker: &[u8] = if radix == 16 { b"pP" } else { b"eE" };
+    if radix != 16 || is_float || matches!(at(i), b'p' | b'P') {
+        if exp_marker.contains(&at(i)) {
+            is_float = true;
+            i += 1;
+            if matches!(at(i), b'+' | b'-') {
+                i += 1;
+            }
+            if read_digits(&mut i, 10) == 0 {
+                return Err(err(
+                    LexErrorKind::InvalidNumberFloat("expected digits in exponent".to_owned()),
+                    i,
+                ));
+            }
+        } else if radix == 16 {
+            return Err(err(
+                LexErrorKind::InvalidNumberFloat(
+                    "hexadecimal float literal requires a `p` exponent".to_owned(),
+                ),
+                i,
+            ));
+        }
+    }
+
+    let suffix_start = i;
+    // the literal without suffix, only copied to drop `_` separators
+    let text = std::str::from_utf8(&input[..suffix_start]).unwrap_or_default();
+    let text = if text.contains('_') {
+        Cow::Owned(text.replace('_', ""))
+    } else {
+        Cow::Borrowed(text)
+    };
+    while is_ident_byte(at(i)) {
+        i += 1;
+    }
+    let mut suffix = std::str::from_utf8(&input[suffix_start..i]).unwrap_or_default();
+    if suffix.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err(err(
+            LexErrorKind::InvalidNumberInt(format!(
+                "invalid digit '{}' in {} literal",
+                &suffix[..1],
+                radix_name(radix)
+            )),
+            suffix_start,
+        ));
+    }
+
+    let int_limit = INT_SUFFIXES.iter().find(|(s, _)| *s == suffix);
+    let type_suffix = int_limit.is_some() || matches!(suffix, "i" | "f32" | "f64");
+    if !type_suffix && Unit::lookup(suffix).is_some() {
+        suffix = "";
+        i = suffix_start;
+    }
+    let invalid_suffix = |msg: String| err(LexErrorKind::InvalidNumberInt(msg), suffix_start);
+    let parse_float = || {
+        let x = if radix == 16 {
+            parse_hex_float(&text).map_err(|e| e.to_string())
+        } else {
+            text.parse::<f64>().map_err(|e| e.to_string())
+        };
+        x.map_err(|e| err(LexErrorKind::InvalidNumberFloat(e), 0))
+    };
+    let token = match suffix {
+        "i" if is_float => Token::Imag(parse_float()?),
+        "i" => Token::Imag(
+            parse_big_int(&text[prefix..], radix)
+                .to_f64()
+                .unwrap_or(f64::INFINITY),
+        ),
+        "" | "f32" | "f64" if is_float => {
+            let x = parse_float()?;
+            match suffix {
+                "" if radix == 10 => {
+                    let exact = text
+                        .parse::<BigDecimal>()
+                        .map_err(|e| err(LexErrorKind::InvalidNumberFloat(e.to_string()), 0))?;
+                    Token::Decimal(x, exact)
+                }
+                "f32" => Token::Float(x as f32 as f64),
+                _ => Token::Float(x),
+            }
+        }
+        "f32" | "f64" => {
+            let value = parse_big_int(&text[prefix..], radix)
+                .to_f64()
+                .unwrap_or(f64::INFINITY);
+            Token::Float(if suffix == "f32" {
+                value as f32 as f64
+            } else {
+                value
+            })
+        }
+        _ if int_limit.is_some() && is_float => {
+            return Err(invalid_suffix(format!(
+                "integer suffix `{suffix}` on a float literal"
+            )))
+        }
+        // only literals without a suffix may be arbitrarily large
+        "" => match parse_int(&text[prefix..], radix) {
+            Ok(value) => Token::Int(value),
+            Err(_) => Token::BigInt(parse_big_int(&text[prefix..], radix)),
+        },
+        _ if int_limit.is_some() => {
+            let value = parse_int(&text[prefix..], radix).map_err(|kind| err(kind, 0))?;
+            if let Some((_, max)) = int_limit.filter(|(_, max)| value > *max) {
+                return Err(invalid_suffix(format!(
+                    "literal out of range for `{suffix}`, the maximum is {max}"
+                )));
+            }
+            Token::Int(value)
+        }
+        _ => {
+            return Err(invalid_suffix(format!(
+                "invalid suffix `{suffix}` on number literal"
+            )))
+        }
+    };
+    Ok((token, i))
+}
+
+/// The digits have been checked against the radix already.
+fn parse_big_int(digits: &str, radix: u32) -> BigUint {
+    BigUint::parse_bytes(digits.as_bytes(), radix).unwrap_or_default()
+}
+
+fn parse_int(digits: &str, radix: u32) -> std::result::Result<u64, LexErrorKind> {
+    u64::from_str_radix(digits, radix).map_err(|_| {
+        LexErrorKind::InvalidNumberInt(format!(
+            "integer literal `{digits}` does not fit in 64 bits"
+        ))
+    })
+}
@@ -1,10 +1,14 @@
-use super::{Lexer, Token};
+use super::{error::LexErrorKind, Lexer, Token};
+use crate::error::Error;
+use crate::location::Span;
+use num_bigint::BigUint;
+use std::borrow::Cow;
 
 fn test_tokens<'a>(source: &'a str, expect: Vec<Option<Token<'a>>>) {
     let lexer = Lexer::new(source);
     let lex_tokens: Vec<_> = lexer
         .map(|x| match x {
-            Ok(ok) => Some(ok),
+            Ok(ok) => Some(ok.value),
             Err(err) => {
                 eprintln!("{err}");
                 None
@@ -12,7 +16,7 @@ fn test_tokens<'a>(source: &'a str, expect: Vec<Option<Token<'a>>>) {
         })
         .collect();
 
-    for (idx, (tok, exp)) in lex_tokens.into_iter().zip(expect.into_iter()).enumerate() {
+    for (idx, (tok, exp)) in lex_tokens.into_iter().zip(expect).enumerate() {
         assert_eq!(tok, exp, "on_index: {idx}");
     }
 }
@@ -34,18 +38,207 @@ fn numerals() {
     test_tokens(
         r#"
 0xdeadbeef 12345 12345. 3.1415e-2 0.123E-10 1.7976931348623157E+500 9223372036854775807 9223372036854775808.
+18446744073709551616 0x1_0000_0000_0000_0000 18446744073709551616f64
 "#,
         vec![
             Some(Token::Int(0xdeadbeef)),
             Some(Token::Int(12345)),
-            Some(Token::Float(12345.0)),
-            Some(Token::Float(3.1415e-2)),
-            Some(Token::Float(0.123e-10)),
-            Some(Token::Float(f64::INFINITY)),
+            Some(dec("12345.")),
+            Some(dec("3.1415e-2")),
+            Some(dec("0.123E-10")),
+            Some(dec("1.7976931348623157E+500")),
             Some(Token::Int(9223372036854775807)),
-            Some(Token::Float(9223372036854775808.0)),
+            Some(dec("9223372036854775808.")),
+            Some(Token::BigInt(BigUint::from(u64::MAX) + 1u8)),
+            Some(Token::BigInt(BigUint::from(u64::MAX) + 1u8)),
+            Some(Token::Float(18446744073709551616.0)),
+        ],
+    );
+}
+
+#[test]
+fn numerals_radix() {
+    test_tokens(
+        r#"
+0b1010 0B1111_0000 0o17 0O777 0xDEAD_beef 1_000_000 007 18446744073709551615
+1e10 2E-3 1_0e+1_0 0x1.8p3 0x.8p1 0X1P-2 0xa.bp0 0x10p0 0x1_0.0p+0
+42u8 255u8 7i64 3f32 0.1f32 0.1f64 1e3f64 0xffu8 0x1p4f64
+1..2 1.max 3. 4.5
+"#,
+        vec![
+            Some(Token::Int(0b1010)),
+            Some(Token::Int(0b1111_0000)),
+            Some(Token::Int(0o17)),
+            Some(Token::Int(0o777)),
+            Some(Token::Int(0xdead_beef)),
+            Some(Token::Int(1_000_000)),
+            Some(Token::Int(7)),
+            Some(Token::Int(u64::MAX)),
+            Some(dec("1e10")),
+            Some(dec("2E-3")),
+            Some(dec("10e+10")),
+            Some(Token::Float(12.0)),
+            Some(Token::Float(1.0)),
+            Some(Token::Float(0.25)),
+            Some(Token::Float(10.6875)),
+            Some(Token::Float(16.0)),
+            Some(Token::Float(16.0)),
+            Some(Token::Int(42)),
+            Some(Token::Int(255)),
+            Some(Token::Int(7)),
+            Some(Token::Float(3.0)),
+            Some(Token::Float(0.1f32 as f64)),
+            Some(Token::Float(0.1)),
+            Some(Token::Float(1e3)),
+            Some(Token::Int(255)),
+            Some(Token::Float(16.0)),
+            Some(Token::Int(1)),
+            Some(Token::Range),
+            Some(Token::Int(2)),
+            Some(Token::Int(1)),
+            Some(Token::Dot),
+            Some(ident("max")),
+            Some(dec("3.")),
+            Some(dec("4.5")),
+        ],
+    );
+}
+
+#[test]
+fn hex_float_rounding() {
+    let next_up = |x: f64, ulps: u64| Some(Token::Float(f64::from_bits(x.to_bits() + ulps)));
+    test_tokens(
+        r#"
+0x1p-1074 0x0.0000000000001p-1022 0x1p-1075 0x1.0000000000000000001p-1075 0x1.8p-1074
+0x1.00000000000008p0 0x1.00000000000018p0 0x1.000000000000080000001p0
+0x1.fffffffffffffp1023 0x1p1024
+"#,
+        vec![
+            next_up(0.0, 1),
+            next_up(0.0, 1),
+            Some(Token::Float(0.0)),
+            next_up(0.0, 1),
+            next_up(0.0, 2),
+            // ties go to the even mantissa, unless a later digit is not 0
+            Some(Token::Float(1.0)),
+            next_up(1.0, 2),
+            next_up(1.0, 1),
+            Some(Token::Float(f64::MAX)),
+            Some(Token::Float(f64::INFINITY)),
+        ],
+    );
+}
+
+#[test]
+fn imaginary() {
+    test_tokens(
+        "3i 2.5i 1e-3i 0x10i 1_000i 2 * i",
+        vec![
+            Some(Token::Imag(3.0)),
+            Some(Token::Imag(2.5)),
+            Some(Token::Imag(1e-3)),
+            Some(Token::Imag(16.0)),
+            Some(Token::Imag(1000.0)),
+            Some(Token::Int(2)),
+            Some(Token::Mul),
+            Some(ident("i")),
+        ],
+    );
+}
+
+#[test]
+fn unit_suffixes() {
+    // units end the literal, other suffixes are errors
+    test_tokens(
+        "2in 1.5km 3m/s 2e3mi",
+        vec![
+            Some(Token::Int(2)),
+            Some(ident("in")),
+            Some(dec("1.5")),
+            Some(ident("km")),
+            Some(Token::Int(3)),
+            Some(ident("m")),
+            Some(Token::Div),
+            Some(ident("s")),
+            Some(dec("2e3")),
+            Some(ident("mi")),
         ],
     );
+    assert_eq!(
+        lex_errors("2inch"),
+        [(
+            LexErrorKind::InvalidNumberInt("invalid suffix `inch` on number literal".to_owned()),
+            1,
+            2
+        )]
+    );
+}
+
+#[test]
+fn number_errors() {
+    let int_err = |s: &str| LexErrorKind::InvalidNumberInt(s.to_owned());
+    let float_err = |s: &str| LexErrorKind::InvalidNumberFloat(s.to_owned());
+
+    assert_eq!(
+        lex_errors("x + 0b1021"),
+        [(int_err("invalid digit '2' in binary literal"), 1, 9)]
+    );
+    assert_eq!(
+        lex_errors("0o78"),
+        [(int_err("invalid digit '8' in octal literal"), 1, 4)]
+    );
+    assert_eq!(
+        lex_errors("12a4"),
+        [(int_err("invalid suffix `a4` on number literal"), 1, 3)]
+    );
+    assert_eq!(
+        lex_errors("0x + 1"),
+        [(int_err("expected hexadecimal digits after `0x`"), 1, 3)]
+    );
+    assert_eq!(
+        lex_errors("1e+"),
+        [(float_err("expected digits in exponent"), 1, 4)]
+    );
+    assert_eq!(
+        lex_errors("0x1.8"),
+        [(
+            float_err("hexadecimal float literal requires a `p` exponent"),
+            1,
+            6
+        )]
+    );
+    assert_eq!(
+        lex_errors("0b1.1"),
+        [(float_err("binary literals cannot have a fraction"), 1, 4)]
+    );
+    assert_eq!(
+        lex_errors("256u8"),
+        [(
+            int_err("literal out of range for `u8`, the maximum is 255"),
+            1,
+            4
+        )]
+    );
+    assert_eq!(
+        lex_errors("1.5i32"),
+        [(int_err("integer suffix `i32` on a float literal"), 1, 4)]
+    );
+    assert_eq!(
+        lex_errors("18446744073709551616u64"),
+        [(
+            int_err("integer literal `18446744073709551616` does not fit in 64 bits"),
+            1,
+            1
+        )]
+    );
+}
+
+#[test]
+fn number_error_recovery() {
+    let tokens: Vec<_> = Lexer::new("0b12_3z + 1")
+        .map(|tok| tok.map(|t| t.value).ok())
+        .collect();
+    assert_eq!(tokens, [None, Some(Token::Add), Some(Token::Int(1))]);
 }
 #[test]
 fn ops() {
@@ -117,7 +310,7 @@ fn ops() {
 fn words() {
     test_tokens(
         r#"
-            break else elif if for while return true false custom names name123num
+            break else elif if for while return true false custom names name123num $1 $23
         "#,
         vec![
             Some(Token::Break),
@@ -132,6 +325,8 @@ fn words() {
             Some(Token::ident("custom".to_owned())),
             Some(Token::ident("names".to_owned())),
             Some(Token::ident("name123num".to_owned())),
+            Some(Token::ident("$1".to_owned())),
+            Some(Token::ident("$23".to_owned())),
         ],
     );
 }
@@ -141,12 +336,15 @@ fn ident(i: &str) -> Token<'_> {
     Token::ident(i.to_owned())
 }
 
-#[test]
-fn code() {
-    test_tokens(
-        r#"
+fn dec(s: &str) -> Token<'static> {
+    Token::Decimal(s.parse().unwrap(), s.parse().unwrap())
+}
+
+const CODE: &str = r#"
+## A person, with a doc comment attached.
 MyStruct :: struct {
-   name  :string;
+   name  :string; # plain comments are skipped
+   /* so are /* nested */ block comments */
    age   :i32;
    hight :i32;
 }
@@ -168,8 +366,16 @@ main :: () {
 
    println(my_function());
 }
-    "#,
+    "#;
+
+#[test]
+fn code() {
+    test_tokens(
+        CODE,
         vec![
+            Some(Token::DocComment(
+                " A person, with a doc comment attached.".into(),
+            )),
             Some(ident("MyStruct")),
             Some(Token::Decl),
             Some(Token::Struct),
@@ -215,7 +421,7 @@ main :: () {
             Some(Token::Colon),
             Some(ident("f32")),
             Some(Token::Assign),
-            Some(Token::Float(123.0)),
+            Some(dec("123.0")),
             Some(Token::SemiColon),
             Some(ident("new_mutable_var")),
             Some(Token::Assign),
@@ -223,7 +429,7 @@ main :: () {
             Some(Token::SemiColon),
             Some(ident("new_explicit_type")),
             Some(Token::Assign),
-            Some(Token::Float(6.9)),
+            Some(dec("6.9")),
             Some(Token::SemiColon),
             Some(ident("println")),
             Some(Token::LParen),
@@ -252,3 +458,287 @@ main :: () {
         ],
     )
 }
+
+#[test]
+fn spans() {
+    let source = "ab := 0x1f;\n  é_x // 2.5";
+    let lexer = Lexer::new(source).set_source_path("spans.rsc");
+    let spans: Vec<Span<'_>> = lexer.map(|tok| tok.unwrap().span).collect();
+
+    let texts: Vec<_> = spans.iter().map(|s| &source[s.range()]).collect();
+    assert_eq!(texts, ["ab", ":=", "0x1f", ";", "é_x", "//", "2.5"]);
+
+    let starts: Vec<_> = spans
+        .iter()
+        .map(|s| (s.start.line(), s.start.col()))
+        .collect();
+    assert_eq!(
+        starts,
+        [(1, 1), (1, 4), (1, 7), (1, 11), (2, 3), (2, 7), (2, 10)]
+    );
+    assert_eq!((spans[4].end.line(), spans[4].end.col()), (2, 6));
+    assert_eq!(spans[6].end.offset(), source.len());
+    assert_eq!(spans[0].start.to_string(), "spans.rsc:1:1");
+}
+
+/// Lexes `source` expecting errors, returning their kind and `line:col`.
+fn lex_errors(source: &str) -> Vec<(LexErrorKind, u32, u32)> {
+    Lexer::new(source)
+        .filter_map(|tok| match tok {
+            Err(Error::LexingError(err)) => {
+                Some((err.kind().clone(), err.loc().line(), err.loc().col()))
+            }
+            Err(err) => panic!("unexpected error {err}"),
+            Ok(_) => None,
+        })
+        .collect()
+}
+
+#[test]
+fn strings() {
+    test_tokens(
+        r#"
+"hello" 'world' "a\tb\n" '\x41\66\u{1F600}' "it's" '\'"\\' "" "\u{e9}t\233"
+"#,
+        vec![
+            Some(Token::Str("hello".into())),
+            Some(Token::Str("world".into())),
+            Some(Token::Str("a\tb\n".into())),
+            Some(Token::Str("AB\u{1F600}".into())),
+            Some(Token::Str("it's".into())),
+            Some(Token::Str("'\"\\".into())),
+            Some(Token::Str("".into())),
+            Some(Token::Str("été".into())),
+        ],
+    );
+}
+
+#[test]
+fn long_strings() {
+    test_tokens(
+        "[[raw \\n]] [==[\nhas ]] and ]=] inside]==] [ [=[]=]",
+        vec![
+            Some(Token::Str("raw \\n".into())),
+            Some(Token::Str("has ]] and ]=] inside".into())),
+            Some(Token::LBracket),
+            Some(Token::Str("".into())),
+        ],
+    );
+}
+
+#[test]
+fn idents_borrow() {
+    let source = "naïve_x1 while\u{3000}x\0";
+    let mut lexer = Lexer::new(source).recover(true);
+    let tokens: Vec<_> = lexer.by_ref().map(|tok| tok.unwrap()).collect();
+    assert!(matches!(
+        &tokens[0].value,
+        Token::Ident(Cow::Borrowed("naïve_x1"))
+    ));
+    assert_eq!(tokens[1].value, Token::While);
+    // unicode whitespace separates tokens, a NUL byte is not the end of input
+    assert!(matches!(&tokens[2].value, Token::Ident(Cow::Borrowed("x"))));
+    assert_eq!(
+        (tokens[2].span.start.col(), tokens[2].span.start.offset()),
+        (16, 18)
+    );
+    assert_eq!(tokens[3].value, Token::Illegal);
+    assert_eq!(
+        lexer.errors()[0].kind(),
+        &LexErrorKind::UnexpectedCharacter('\0')
+    );
+}
+
+#[test]
+fn strings_borrow() {
+    let tokens: Vec<_> = Lexer::new(r#""plain" "esc\n" [[long]]"#)
+        .map(|tok| tok.unwrap().value)
+        .collect();
+    assert!(matches!(&tokens[0], Token::Str(Cow::Borrowed("plain"))));
+    assert!(matches!(&tokens[1], Token::Str(Cow::Owned(s)) if s == "esc\n"));
+    assert!(matches!(&tokens[2], Token::Str(Cow::Borrowed("long"))));
+}
+
+#[test]
+fn string_errors() {
+    assert_eq!(
+        lex_errors("x = \"abc\ny"),
+        [(LexErrorKind::UnfinishedShortString('"'), 1, 5)]
+    );
+    assert_eq!(
+        lex_errors("'abc"),
+        [(LexErrorKind::UnfinishedShortString('\''), 1, 1)]
+    );
+    assert_eq!(lex_errors(r#""\q""#), [(LexErrorKind::InvalidEscape, 1, 3)]);
+    assert_eq!(
+        lex_errors(r#""\x4g""#),
+        [(LexErrorKind::HexDigitExpected, 1, 5)]
+    );
+    assert_eq!(
+        lex_errors(r#""ab\256""#),
+        [(LexErrorKind::EscapeDecimalTooLarge, 1, 4)]
+    );
+    assert_eq!(
+        lex_errors(r#""\u41}""#),
+        [(LexErrorKind::EscapeUnicodeStart, 1, 4)]
+    );
+    assert_eq!(
+        lex_errors(r#""\u{41""#),
+        [(LexErrorKind::EscapeUnicodeEnd, 1, 7)]
+    );
+    assert_eq!(
+        lex_errors(r#""\u{}""#),
+        [(LexErrorKind::HexDigitExpected, 1, 5)]
+    );
+    assert_eq!(
+        lex_errors(r#""\u{D800}""#),
+        [(LexErrorKind::EscapeUnicodeInvalid, 1, 2)]
+    );
+    assert_eq!(
+        lex_errors("[==x"),
+        [(LexErrorKind::InvalidLongStringDelimiter, 1, 1)]
+    );
+    assert_eq!(
+        lex_errors("\n [[never closed]"),
+        [(LexErrorKind::UnfinishedLongString, 2, 2)]
+    );
+}
+
+#[test]
+fn string_error_recovery() {
+    let tokens: Vec<_> = Lexer::new(r#""bad \q escape" + 'ok'"#)
+        .map(|tok| tok.map(|t| t.value).ok())
+        .collect();
+    assert_eq!(
+        tokens,
+        [None, Some(Token::Add), Some(Token::Str("ok".into()))]
+    );
+}
+
+#[test]
+fn comments() {
+    test_tokens(
+        "1 // 2 # not //= division\n/** doc\n block */ 3 /**/ /*** banner ***/ ### banner\n4 #",
+        vec![
+            Some(Token::Int(1)),
+            Some(Token::IDiv),
+            Some(Token::Int(2)),
+            Some(Token::DocComment(" doc\n block ".into())),
+            Some(Token::Int(3)),
+            Some(Token::Int(4)),
+        ],
+    );
+}
+
+#[test]
+fn emit_comments() {
+    let source = "a # line\n/* block /* nested */ */ ## doc\nb /**/";
+    let tokens: Vec<_> = Lexer::new(source)
+        .emit_comments(true)
+        .map(|tok| tok.unwrap())
+        .collect();
+    let values: Vec<_> = tokens.iter().map(|t| t.value.clone()).collect();
+    assert_eq!(
+        values,
+        [
+            ident("a"),
+            Token::Comment(" line".into()),
+            Token::Comment(" block /* nested */ ".into()),
+            Token::DocComment(" doc".into()),
+            ident("b"),
+            Token::Comment("".into()),
+        ]
+    );
+    let texts: Vec<_> = tokens.iter().map(|t| &source[t.span.range()]).collect();
+    assert_eq!(
+        texts,
+        [
+            "a",
+            "# line",
+            "/* block /* nested */ */",
+            "## doc",
+            "b",
+            "/**/"
+        ]
+    );
+}
+
+#[test]
+fn trivia() {
+    let source = "a  :=\t1; # one\r\n\n  b /* x */ := [[\nlong]] + 'q\\n'\r\r\n";
+    let tokens: Vec<_> = Lexer::new(source)
+        .emit_trivia(true)
+        .map(|tok| tok.unwrap())
+        .collect();
+    let text: String = tokens.iter().map(|t| &source[t.span.range()]).collect();
+    assert_eq!(text, source);
+    let values: Vec<_> = tokens.iter().map(|t| t.value.clone()).take(10).collect();
+    assert_eq!(
+        values,
+        [
+            ident("a"),
+            Token::Whitespace("  ".into()),
+            Token::DeclAssign,
+            Token::Whitespace("\t".into()),
+            Token::Int(1),
+            Token::SemiColon,
+            Token::Whitespace(" ".into()),
+            Token::Comment(" one".into()),
+            Token::Newline,
+            Token::Newline,
+        ]
+    );
+    let newlines: Vec<_> = tokens
+        .iter()
+        .filter(|t| t.value == Token::Newline)
+        .map(|t| &source[t.span.range()])
+        .collect();
+    assert_eq!(newlines, ["\r\n", "\n", "\r\n"]);
+    assert!(tokens.windows(2).all(|w| w[0].span.end == w[1].span.start));
+
+    let text: String = Lexer::new(CODE)
+        .emit_trivia(true)
+        .map(|tok| &CODE[tok.unwrap().span.range()])
+        .collect();
+    assert_eq!(text, CODE);
+}
+
+#[test]
+fn comment_errors() {
+    assert_eq!(
+        lex_errors("1 /* open /* nested */"),
+        [(LexErrorKind::UnfinishedBlockComment, 1, 3)]
+    );
+}
+
+#[test]
+fn recovery() {
+    let source = "a = 1 $ 0x + 'oops\nb = \"\\q\" @ 2";
+    let mut lexer = Lexer::new(source).recover(true);
+    let tokens: Vec<_> = lexer.by_ref().map(|tok| tok.unwrap()).collect();
+    let illegal: Vec<_> = tokens
+        .iter()
+        .filter(|t| t.value == Token::Illegal)
+        .map(|t| &source[t.span.range()])
+        .collect();
+    assert_eq!(illegal, ["$", "0x", "'oops", "\"\\q\"", "@"]);
+    assert_eq!(tokens.last().unwrap().value, Token::Int(2));
+
+    let errors: Vec<_> = lexer
+        .errors()
+        .iter()
+        .map(|err| (err.loc().line(), err.loc().col()))
+        .collect();
+    assert_eq!(errors, [(1, 7), (1, 11), (1, 14), (2, 7), (2, 10)]);
+    assert_eq!(lexer.diagnostics().len(), 5);
+    assert_eq!(lexer.take_errors().len(), 5);
+    assert!(lexer.errors().is_empty());
+
+    // with trivia the input is still reproduced in full
+    let text: String = Lexer::new(source)
+        .emit_trivia(true)
+        .recover(true)
+        .map(|tok| &source[tok.unwrap().span.range()])
+        .collect();
+    assert_eq!(text, source);
+}
@@ -1,6 +1,9 @@
 use derive_more::Display;
 use std::borrow::Cow;
 
+use bigdecimal::BigDecimal;
+use num_bigint::BigUint;
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Display)]
 pub enum Token<'lit> {
     #[display(fmt = "<IllegalToken>")]
@@ -140,13 +143,29 @@ pub enum Token<'lit> {
     Arrow, // =
     #[display(fmt = "'=>'")]
     FatArrow, // =
-    #[display(fmt = "'//'")]
-    Comment, // =
+    #[display(fmt = "<Whitespace>")]
+    Whitespace(Cow<'lit, str>),
+    #[display(fmt = "<Newline>")]
+    Newline,
+    #[display(fmt = "#{_0}")]
+    Comment(Cow<'lit, str>),
+    #[display(fmt = "##{_0}")]
+    DocComment(Cow<'lit, str>),
 
     #[display(fmt = "`{_0}`")]
     Int(u64),
+    /// An integer literal too large for [`Token::Int`].
+    #[display(fmt = "`{_0}`")]
+    BigInt(BigUint),
     #[display(fmt = "`{_0}`")]
     Float(f64),
+    /// A decimal literal with a point or an exponent and no suffix, both as
+    /// a float and exactly.
+    #[display(fmt = "`{_1}`")]
+    Decimal(f64, BigDecimal),
+    /// An imaginary literal, `2.5i`.
+    #[display(fmt = "`{_0}i`")]
+    Imag(f64),
     #[display(fmt = "`{_0}`")]
     Ident(Cow<'lit, str>),
     #[display(fmt = "\"{_0}\"")]
@@ -158,4 +177,16 @@ impl<'lit> Token<'lit> {
     pub fn ident(i: impl Into<Cow<'lit, str>>) -> Self {
         Self::Ident(i.into())
     }
+
+    /// Whitespace, newlines and plain comments, the tokens that only show up
+    /// in trivia mode and carry no meaning.
+    #[inline]
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, Self::Whitespace(_) | Self::Newline | Self::Comment(_))
+    }
 }
+
+/// The words lexed as keywords rather than identifiers.
+pub const KEYWORDS: &[&str] = &[
+    "if", "else", "elif", "return", "while", "for", "break", "continue", "struct", "true", "false",
+];
@@ -1,9 +1,64 @@
+mod completion;
+mod diagnostic;
 mod error;
+mod eval;
+mod format;
 mod lev;
 mod lexer;
 mod location;
+mod parser;
+mod units;
 mod util;
 
+pub use completion::complete;
+pub use diagnostic::{Diagnostic, Label, Severity};
 pub use error::{Error, Result};
-pub use lev::{lev, suggest_word};
-pub use lexer::Lexer;
+pub use eval::{
+    context::{AngleUnit, Builtin, BuiltinFn, Context, DecimalFn, Mode},
+    decimal::{Precision, Rounding, PRECISION_HELP},
+    error::{EvalError, EvalErrorKind},
+    instance::Instance,
+    quantity::Quantity,
+    value::{Value, MAX_INT_BITS},
+};
+pub use format::{Format, Notation, FORMAT_HELP, MAX_DIGITS};
+pub use lev::{closest_words, lev, suggest_word};
+pub use lexer::{
+    error::{LexError, LexErrorKind},
+    token::{Token, KEYWORDS},
+    Lexer,
+};
+pub use location::{Loc, Span, Spanned};
+pub use parser::{
+    ast::{BinOp, Block, Expr, ExprKind, FnDecl, Param, Stmt, StmtKind, StructDecl, UnaryOp},
+    error::{ParseError, ParseErrorKind},
+    Parser,
+};
+pub use units::{Dimension, Unit};
+
+/// Runs `source` as a program with the default [`Context`], its value is
+/// the one of the trailing expression.
+///
+/// ```
+/// assert_eq!(rscalc::eval("2 ^ 10").unwrap(), rscalc::Value::Int(1024));
+/// assert_eq!(rscalc::eval("x := 3; x *= 2; x + 1").unwrap(), rscalc::Value::Int(7));
+/// assert_eq!(rscalc::eval("0.1 + 0.2 == 0.3").unwrap(), rscalc::Value::Bool(true));
+/// ```
+pub fn eval(source: &str) -> Result<'_, Value> {
+    eval_with(source, &mut Context::new())
+}
+
+/// Runs `source` as a program against `ctx`, which keeps its bindings.
+///
+/// ```
+/// let mut ctx = rscalc::Context::new();
+/// ctx.set_mode(rscalc::Mode::Float);
+/// ctx.set_var("x", 4);
+/// assert_eq!(rscalc::eval_with("sqrt(x) * 1.5", &mut ctx).unwrap(), rscalc::Value::Float(3.0));
+/// rscalc::eval_with("sq :: (n) { n * n }", &mut ctx).unwrap();
+/// assert_eq!(rscalc::eval_with("sq(x)", &mut ctx).unwrap(), rscalc::Value::Int(16));
+/// ```
+pub fn eval_with<'s>(source: &'s str, ctx: &mut Context) -> Result<'s, Value> {
+    let program = Parser::new(Lexer::new(source))?.parse_program()?;
+    ctx.exec(&program)
+}
@@ -1,10 +1,13 @@
-use std::{borrow::Cow, fmt::Display};
+use std::{borrow::Cow, fmt::Display, ops::Range};
 
+/// A position in the source, `line` and `col` are 1-based (counted in chars)
+/// and `offset` is the 0-based byte offset.
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct Loc<'a> {
     source: Option<Cow<'a, str>>,
     line: u32,
     col: u32,
+    offset: usize,
 }
 
 impl<'a> Loc<'a> {
@@ -13,6 +16,7 @@ impl<'a> Loc<'a> {
             source: None,
             line,
             col,
+            offset: 0,
         }
     }
 
@@ -24,6 +28,11 @@ impl<'a> Loc<'a> {
         self
     }
 
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
     pub fn source(&self) -> Option<&Cow<'a, str>> {
         self.source.as_ref()
     }
@@ -36,6 +45,10 @@ impl<'a> Loc<'a> {
         self.col
     }
 
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
     pub fn inc_col(&mut self) {
         self.col += 1;
     }
@@ -44,12 +57,19 @@ impl<'a> Loc<'a> {
         self.line += 1;
     }
 
+    pub fn inc_offset(&mut self, n: usize) {
+        self.offset += n;
+    }
+
     pub fn set_col(&mut self, col: u32) {
         self.col = col;
     }
     pub fn set_line(&mut self, line: u32) {
         self.line = line;
     }
+    pub fn set_offset(&mut self, offset: usize) {
+        self.offset = offset;
+    }
 }
 
 impl<'a> Display for Loc<'a> {
@@ -61,3 +81,55 @@ impl<'a> Display for Loc<'a> {
         }
     }
 }
+
+/// A half open source range, `end` points just past the last char.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Span<'a> {
+    pub start: Loc<'a>,
+    pub end: Loc<'a>,
+}
+
+impl<'a> Span<'a> {
+    pub fn new(start: Loc<'a>, end: Loc<'a>) -> Self {
+        Self { start, end }
+    }
+
+    /// Byte range of the span, usable to slice the source.
+    pub fn range(&self) -> Range<usize> {
+        self.start.offset..self.end.offset
+    }
+
+    /// The span from the start of `self` to the end of `other`.
+    pub fn to(&self, other: &Span<'a>) -> Self {
+        Self {
+            start: self.start.clone(),
+            end: other.end.clone(),
+        }
+    }
+}
+
+impl<'a> Display for Span<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.start.fmt(f)
+    }
+}
+
+/// A value along with the span of source it was read from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<'a, T> {
+    pub value: T,
+    pub span: Span<'a>,
+}
+
+impl<'a, T> Spanned<'a, T> {
+    pub fn new(value: T, span: Span<'a>) -> Self {
+        Self { value, span }
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Spanned<'a, U> {
+        Spanned {
+            value: f(self.value),
+            span: self.span,
+        }
+    }
+}
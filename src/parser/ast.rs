@@ -0,0 +1,678 @@
+use bigdecimal::BigDecimal;
+use derive_more::Display;
+use num_bigint::BigUint;
+use std::{borrow::Cow, fmt};
+
+use crate::eval::decimal;
+use crate::format::Format;
+use crate::lexer::token::Token;
+use crate::location::{Loc, Span, Spanned};
+
+/// Binding power of prefix operators, sits between the multiplicative
+/// operators and `^` so that `-2^2` parses as `-(2^2)`.
+pub const UNARY_PRECEDENCE: u8 = 10;
+
+/// Binding power of atoms and postfix expressions (calls, field access).
+pub const POSTFIX_PRECEDENCE: u8 = u8::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assoc {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum UnaryOp {
+    #[display(fmt = "-")]
+    Neg,
+    #[display(fmt = "+")]
+    Plus,
+    #[display(fmt = "!")]
+    Not,
+    #[display(fmt = "~")]
+    BitNot,
+}
+
+impl UnaryOp {
+    pub fn from_token(tok: &Token<'_>) -> Option<Self> {
+        match tok {
+            Token::Sub => Some(Self::Neg),
+            Token::Add => Some(Self::Plus),
+            Token::Not => Some(Self::Not),
+            Token::BitNotXor => Some(Self::BitNot),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum BinOp {
+    #[display(fmt = "||")]
+    LogicOr,
+    #[display(fmt = "&&")]
+    LogicAnd,
+
+    #[display(fmt = "==")]
+    Eq,
+    #[display(fmt = "!=")]
+    Ne,
+    #[display(fmt = "<")]
+    Lt,
+    #[display(fmt = "<=")]
+    Lte,
+    #[display(fmt = ">")]
+    Gt,
+    #[display(fmt = ">=")]
+    Gte,
+
+    #[display(fmt = "|")]
+    BitOr,
+    #[display(fmt = "^^")]
+    BitXor,
+    #[display(fmt = "~")]
+    BitNotXor,
+    #[display(fmt = "&")]
+    BitAnd,
+
+    #[display(fmt = "<<")]
+    Shl,
+    #[display(fmt = ">>")]
+    Shr,
+
+    #[display(fmt = "+")]
+    Add,
+    #[display(fmt = "-")]
+    Sub,
+
+    #[display(fmt = "*")]
+    Mul,
+    #[display(fmt = "/")]
+    Div,
+    #[display(fmt = "//")]
+    IDiv,
+    #[display(fmt = "%")]
+    Rem,
+
+    #[display(fmt = "^")]
+    Pow,
+}
+
+impl BinOp {
+    pub fn from_token(tok: &Token<'_>) -> Option<Self> {
+        let op = match tok {
+            Token::LogicOr => Self::LogicOr,
+            Token::LogicAnd => Self::LogicAnd,
+            Token::Eq => Self::Eq,
+            Token::Ne => Self::Ne,
+            Token::Lt => Self::Lt,
+            Token::Lte => Self::Lte,
+            Token::Gt => Self::Gt,
+            Token::Gte => Self::Gte,
+            Token::BitOr => Self::BitOr,
+            Token::BitXor => Self::BitXor,
+            Token::BitNotXor => Self::BitNotXor,
+            Token::BitAnd => Self::BitAnd,
+            Token::Shl => Self::Shl,
+            Token::Shr => Self::Shr,
+            Token::Add => Self::Add,
+            Token::Sub => Self::Sub,
+            Token::Mul => Self::Mul,
+            Token::Div => Self::Div,
+            Token::IDiv => Self::IDiv,
+            Token::Rem => Self::Rem,
+            Token::Pow => Self::Pow,
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    /// The operator of a compound assignment like `+=`.
+    pub fn from_assign_token(tok: &Token<'_>) -> Option<Self> {
+        let op = match tok {
+            Token::AddAssign => Self::Add,
+            Token::SubAssign => Self::Sub,
+            Token::MulAssign => Self::Mul,
+            Token::DivAssign => Self::Div,
+            Token::IDivAssign => Self::IDiv,
+            Token::RemAssign => Self::Rem,
+            Token::PowAssign => Self::Pow,
+            Token::ShlAssign => Self::Shl,
+            Token::ShrAssign => Self::Shr,
+            Token::BitOrAssign => Self::BitOr,
+            Token::BitAndAssign => Self::BitAnd,
+            Token::BitXorAssign => Self::BitXor,
+            Token::BitNotXorAssign => Self::BitNotXor,
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    /// Binding power of the operator, higher binds tighter.
+    /// See the table in the [`parser`](crate::parser) module docs.
+    pub const fn precedence(self) -> u8 {
+        match self {
+            Self::LogicOr => 1,
+            Self::LogicAnd => 2,
+            Self::Eq | Self::Ne | Self::Lt | Self::Lte | Self::Gt | Self::Gte => 3,
+            Self::BitOr => 4,
+            Self::BitXor | Self::BitNotXor => 5,
+            Self::BitAnd => 6,
+            Self::Shl | Self::Shr => 7,
+            Self::Add | Self::Sub => 8,
+            Self::Mul | Self::Div | Self::IDiv | Self::Rem => 9,
+            Self::Pow => 11,
+        }
+    }
+
+    pub const fn assoc(self) -> Assoc {
+        match self {
+            Self::Pow => Assoc::Right,
+            _ => Assoc::Left,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind<'a> {
+    Int(u64),
+    BigInt(BigUint),
+    Float(f64),
+    /// A literal that is a decimal in the decimal mode and a float otherwise.
+    Decimal(f64, BigDecimal),
+    /// An imaginary number, `2i`.
+    Imag(f64),
+    /// A number with units and their powers, `9.81 m/s^2`.
+    Quantity(Box<Expr<'a>>, Vec<(Spanned<'a, Cow<'a, str>>, i32)>),
+    Bool(bool),
+    Ident(Cow<'a, str>),
+    Unary(UnaryOp, Box<Expr<'a>>),
+    Binary(BinOp, Box<Expr<'a>>, Box<Expr<'a>>),
+    Call(Box<Expr<'a>>, Vec<Expr<'a>>),
+    Field(Box<Expr<'a>>, Cow<'a, str>),
+    Block(Block<'a>),
+    /// `if` and `elif` branches along with the `else` block.
+    If(Vec<(Expr<'a>, Block<'a>)>, Option<Block<'a>>),
+    /// `expr -> hex`, a display format for the value of a statement.
+    Format(Box<Expr<'a>>, Format),
+}
+
+/// A node of the expression tree, `span` covers all of its source text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr<'a> {
+    pub kind: ExprKind<'a>,
+    pub span: Span<'a>,
+}
+
+impl<'a> Expr<'a> {
+    #[inline]
+    pub fn new(kind: ExprKind<'a>, span: Span<'a>) -> Self {
+        Self { kind, span }
+    }
+
+    #[inline]
+    pub fn boxed(self) -> Box<Self> {
+        Box::new(self)
+    }
+
+    /// Binding power of this node when printed, used to decide where
+    /// parentheses are needed. Blocks and `if` always get them when nested,
+    /// at the start of a statement they would end it.
+    pub fn precedence(&self) -> u8 {
+        match &self.kind {
+            // `-5 m` needs no parentheses but `(5 m)^2` does
+            ExprKind::Unary(..) | ExprKind::Quantity(..) => UNARY_PRECEDENCE,
+            ExprKind::Binary(op, ..) => op.precedence(),
+            ExprKind::Block(_) | ExprKind::If(..) | ExprKind::Format(..) => 0,
+            _ => POSTFIX_PRECEDENCE,
+        }
+    }
+
+    /// Detaches the tree from the source it was parsed from.
+    pub fn into_owned(self) -> Expr<'static> {
+        let kind = match self.kind {
+            ExprKind::Int(i) => ExprKind::Int(i),
+            ExprKind::BigInt(i) => ExprKind::BigInt(i),
+            ExprKind::Float(x) => ExprKind::Float(x),
+            ExprKind::Decimal(x, d) => ExprKind::Decimal(x, d),
+            ExprKind::Imag(x) => ExprKind::Imag(x),
+            ExprKind::Quantity(value, units) => ExprKind::Quantity(
+                value.into_owned().boxed(),
+                units
+                    .into_iter()
+                    .map(|(u, e)| (Spanned::new(owned(u.value), owned_span(u.span)), e))
+                    .collect(),
+            ),
+            ExprKind::Bool(b) => ExprKind::Bool(b),
+            ExprKind::Ident(name) => ExprKind::Ident(owned(name)),
+            ExprKind::Unary(op, expr) => ExprKind::Unary(op, expr.into_owned().boxed()),
+            ExprKind::Binary(op, lhs, rhs) => {
+                ExprKind::Binary(op, lhs.into_owned().boxed(), rhs.into_owned().boxed())
+            }
+            ExprKind::Call(callee, args) => ExprKind::Call(
+                callee.into_owned().boxed(),
+                args.into_iter().map(Expr::into_owned).collect(),
+            ),
+            ExprKind::Field(expr, name) => ExprKind::Field(expr.into_owned().boxed(), owned(name)),
+            ExprKind::Block(block) => ExprKind::Block(block.into_owned()),
+            ExprKind::If(branches, otherwise) => ExprKind::If(
+                branches
+                    .into_iter()
+                    .map(|(cond, block)| (cond.into_owned(), block.into_owned()))
+                    .collect(),
+                otherwise.map(Block::into_owned),
+            ),
+            ExprKind::Format(expr, format) => ExprKind::Format(expr.into_owned().boxed(), format),
+        };
+        Expr::new(kind, owned_span(self.span))
+    }
+}
+
+/// `{ stmts; tail }`, the trailing expression without `;` is the value of
+/// the block. A whole program is a block without the braces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block<'a> {
+    pub stmts: Vec<Stmt<'a>>,
+    pub tail: Option<Box<Expr<'a>>>,
+    pub span: Span<'a>,
+}
+
+impl<'a> Block<'a> {
+    pub fn into_owned(self) -> Block<'static> {
+        Block {
+            stmts: self.stmts.into_iter().map(Stmt::into_owned).collect(),
+            tail: self.tail.map(|tail| tail.into_owned().boxed()),
+            span: owned_span(self.span),
+        }
+    }
+
+    /// The format asked for with `-> format` after the trailing expression.
+    pub fn format(&self) -> Option<Format> {
+        match &self.tail.as_deref()?.kind {
+            ExprKind::Format(_, format) => Some(*format),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param<'a> {
+    pub name: Cow<'a, str>,
+    pub ty: Option<Cow<'a, str>>,
+}
+
+/// `name :: (a, b :int) -> float { body }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FnDecl<'a> {
+    pub name: Cow<'a, str>,
+    pub params: Vec<Param<'a>>,
+    pub ret: Option<Cow<'a, str>>,
+    pub body: Block<'a>,
+}
+
+impl<'a> FnDecl<'a> {
+    pub fn into_owned(self) -> FnDecl<'static> {
+        FnDecl {
+            name: owned(self.name),
+            params: self
+                .params
+                .into_iter()
+                .map(|param| Param {
+                    name: owned(param.name),
+                    ty: param.ty.map(owned),
+                })
+                .collect(),
+            ret: self.ret.map(owned),
+            body: self.body.into_owned(),
+        }
+    }
+}
+
+/// `name :: struct { field; other :ty }`, whose values are made by calling
+/// `name` with the fields in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructDecl<'a> {
+    pub name: Cow<'a, str>,
+    pub fields: Vec<Param<'a>>,
+}
+
+impl<'a> StructDecl<'a> {
+    pub fn into_owned(self) -> StructDecl<'static> {
+        StructDecl {
+            name: owned(self.name),
+            fields: self
+                .fields
+                .into_iter()
+                .map(|field| Param {
+                    name: owned(field.name),
+                    ty: field.ty.map(owned),
+                })
+                .collect(),
+        }
+    }
+
+    /// The position of the field.
+    pub fn field(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|field| field.name == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind<'a> {
+    Expr(Expr<'a>),
+    /// `name := value`, or `name :: value` for constants. With a type the
+    /// forms are `name :ty= value` and `name :ty: value`.
+    Decl {
+        name: Cow<'a, str>,
+        ty: Option<Cow<'a, str>>,
+        value: Expr<'a>,
+        constant: bool,
+    },
+    /// `name = value`, or `name += value` and friends with `op`.
+    Assign {
+        name: Cow<'a, str>,
+        op: Option<BinOp>,
+        value: Expr<'a>,
+    },
+    Fn(Box<FnDecl<'a>>),
+    Struct(StructDecl<'a>),
+    While(Expr<'a>, Block<'a>),
+    /// `for var in start..end { body }`, `..=` when `inclusive`.
+    For {
+        var: Cow<'a, str>,
+        start: Box<Expr<'a>>,
+        end: Box<Expr<'a>>,
+        inclusive: bool,
+        body: Block<'a>,
+    },
+    Return(Option<Expr<'a>>),
+    Break,
+    Continue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt<'a> {
+    pub kind: StmtKind<'a>,
+    pub span: Span<'a>,
+}
+
+impl<'a> Stmt<'a> {
+    #[inline]
+    pub fn new(kind: StmtKind<'a>, span: Span<'a>) -> Self {
+        Self { kind, span }
+    }
+
+    /// Statements ending with a block, which need no `;` after them.
+    pub fn is_block_like(&self) -> bool {
+        match &self.kind {
+            StmtKind::Expr(expr) => matches!(expr.kind, ExprKind::Block(_) | ExprKind::If(..)),
+            StmtKind::Fn(_) | StmtKind::Struct(_) | StmtKind::While(..) | StmtKind::For { .. } => {
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn into_owned(self) -> Stmt<'static> {
+        let kind = match self.kind {
+            StmtKind::Expr(expr) => StmtKind::Expr(expr.into_owned()),
+            StmtKind::Decl {
+                name,
+                ty,
+                value,
+                constant,
+            } => StmtKind::Decl {
+                name: owned(name),
+                ty: ty.map(owned),
+                value: value.into_owned(),
+                constant,
+            },
+            StmtKind::Assign { name, op, value } => StmtKind::Assign {
+                name: owned(name),
+                op,
+                value: value.into_owned(),
+            },
+            StmtKind::Fn(decl) => StmtKind::Fn(Box::new(decl.into_owned())),
+            StmtKind::Struct(decl) => StmtKind::Struct(decl.into_owned()),
+            StmtKind::While(cond, body) => StmtKind::While(cond.into_owned(), body.into_owned()),
+            StmtKind::For {
+                var,
+                start,
+                end,
+                inclusive,
+                body,
+            } => StmtKind::For {
+                var: owned(var),
+                start: Box::new(start.into_owned()),
+                end: Box::new(end.into_owned()),
+                inclusive,
+                body: body.into_owned(),
+            },
+            StmtKind::Return(value) => StmtKind::Return(value.map(Expr::into_owned)),
+            StmtKind::Break => StmtKind::Break,
+            StmtKind::Continue => StmtKind::Continue,
+        };
+        Stmt::new(kind, owned_span(self.span))
+    }
+}
+
+fn owned(s: Cow<'_, str>) -> Cow<'static, str> {
+    Cow::Owned(s.into_owned())
+}
+
+fn owned_loc(loc: Loc<'_>) -> Loc<'static> {
+    let mut owned_loc = Loc::new(loc.line(), loc.col()).with_offset(loc.offset());
+    if let Some(source) = loc.source() {
+        owned_loc = owned_loc.set_source(source.to_string());
+    }
+    owned_loc
+}
+
+fn owned_span(span: Span<'_>) -> Span<'static> {
+    Span::new(owned_loc(span.start), owned_loc(span.end))
+}
+
+struct Parens<'e, 'a>(&'e Expr<'a>, bool);
+
+impl fmt::Display for Parens<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.1 {
+            write!(f, "({})", self.0)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+/// Prints the expression back as source, with only the parentheses that are
+/// needed to parse to the same tree.
+impl fmt::Display for Expr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ExprKind::Int(i) => write!(f, "{i}"),
+            ExprKind::BigInt(i) => write!(f, "{i}"),
+            ExprKind::Float(x) => write!(f, "{x:?}"),
+            ExprKind::Decimal(_, d) => f.write_str(&decimal::to_string(d)),
+            ExprKind::Imag(x) => write!(f, "{x:?}i"),
+            ExprKind::Quantity(value, units) => {
+                write!(f, "{value} ")?;
+                for (idx, (unit, exp)) in units.iter().enumerate() {
+                    let exp = match idx {
+                        0 => *exp,
+                        _ if *exp < 0 => {
+                            f.write_str("/")?;
+                            -exp
+                        }
+                        _ => {
+                            f.write_str("*")?;
+                            *exp
+                        }
+                    };
+                    match exp {
+                        1 => write!(f, "{}", unit.value)?,
+                        _ => write!(f, "{}^{exp}", unit.value)?,
+                    }
+                }
+                Ok(())
+            }
+            ExprKind::Bool(b) => write!(f, "{b}"),
+            ExprKind::Ident(i) => write!(f, "{i}"),
+            ExprKind::Unary(op, expr) => {
+                let parens = expr.precedence() < UNARY_PRECEDENCE;
+                write!(f, "{op}{}", Parens(expr, parens))
+            }
+            ExprKind::Binary(op, lhs, rhs) => {
+                let prec = op.precedence();
+                let (lparen, rparen) = match op.assoc() {
+                    Assoc::Left => (lhs.precedence() < prec, rhs.precedence() <= prec),
+                    Assoc::Right => (lhs.precedence() <= prec, rhs.precedence() < prec),
+                };
+                write!(f, "{} {op} {}", Parens(lhs, lparen), Parens(rhs, rparen))
+            }
+            ExprKind::Call(callee, args) => {
+                let parens = callee.precedence() < POSTFIX_PRECEDENCE;
+                write!(f, "{}(", Parens(callee, parens))?;
+                for (idx, arg) in args.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                f.write_str(")")
+            }
+            ExprKind::Field(expr, name) => {
+                let parens = expr.precedence() < POSTFIX_PRECEDENCE;
+                write!(f, "{}.{name}", Parens(expr, parens))
+            }
+            ExprKind::Block(block) => write!(f, "{block}"),
+            ExprKind::If(branches, otherwise) => {
+                for (idx, (cond, block)) in branches.iter().enumerate() {
+                    let keyword = if idx == 0 { "if" } else { " elif" };
+                    write!(f, "{keyword} {cond} {block}")?;
+                }
+                match otherwise {
+                    Some(block) => write!(f, " else {block}"),
+                    None => Ok(()),
+                }
+            }
+            ExprKind::Format(expr, format) => {
+                let parens = expr.precedence() == 0;
+                write!(f, "{} -> {format}", Parens(expr, parens))
+            }
+        }
+    }
+}
+
+impl Block<'_> {
+    /// The statements without the surrounding braces, as a program.
+    pub fn body(&self) -> impl fmt::Display + '_ {
+        struct Body<'b, 'a>(&'b Block<'a>);
+        impl fmt::Display for Body<'_, '_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let block = self.0;
+                for (idx, stmt) in block.stmts.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_str(" ")?;
+                    }
+                    write!(f, "{stmt};")?;
+                }
+                if let Some(tail) = &block.tail {
+                    if !block.stmts.is_empty() {
+                        f.write_str(" ")?;
+                    }
+                    write!(f, "{tail}")?;
+                }
+                Ok(())
+            }
+        }
+        Body(self)
+    }
+}
+
+impl fmt::Display for Block<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.stmts.is_empty() && self.tail.is_none() {
+            f.write_str("{}")
+        } else {
+            write!(f, "{{ {} }}", self.body())
+        }
+    }
+}
+
+impl fmt::Display for FnDecl<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} :: (", self.name)?;
+        for (idx, param) in self.params.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(", ")?;
+            }
+            match &param.ty {
+                Some(ty) => write!(f, "{} :{ty}", param.name)?,
+                None => write!(f, "{}", param.name)?,
+            }
+        }
+        f.write_str(")")?;
+        if let Some(ret) = &self.ret {
+            write!(f, " -> {ret}")?;
+        }
+        write!(f, " {}", self.body)
+    }
+}
+
+impl fmt::Display for StructDecl<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} :: struct {{", self.name)?;
+        for (idx, field) in self.fields.iter().enumerate() {
+            let sep = if idx > 0 { ";" } else { "" };
+            match &field.ty {
+                Some(ty) => write!(f, "{sep} {} :{ty}", field.name)?,
+                None => write!(f, "{sep} {}", field.name)?,
+            }
+        }
+        if self.fields.is_empty() {
+            f.write_str("}")
+        } else {
+            f.write_str(" }")
+        }
+    }
+}
+
+/// Prints the statement back as source, without the trailing `;`.
+impl fmt::Display for Stmt<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            StmtKind::Expr(expr) => write!(f, "{expr}"),
+            StmtKind::Decl {
+                name,
+                ty,
+                value,
+                constant,
+            } => match (ty, constant) {
+                (None, false) => write!(f, "{name} := {value}"),
+                (None, true) => write!(f, "{name} :: {value}"),
+                (Some(ty), false) => write!(f, "{name} :{ty}= {value}"),
+                (Some(ty), true) => write!(f, "{name} :{ty}: {value}"),
+            },
+            StmtKind::Assign { name, op, value } => match op {
+                Some(op) => write!(f, "{name} {op}= {value}"),
+                None => write!(f, "{name} = {value}"),
+            },
+            StmtKind::Fn(decl) => write!(f, "{decl}"),
+            StmtKind::Struct(decl) => write!(f, "{decl}"),
+            StmtKind::While(cond, body) => write!(f, "while {cond} {body}"),
+            StmtKind::For {
+                var,
+                start,
+                end,
+                inclusive,
+                body,
+            } => {
+                let range = if *inclusive { "..=" } else { ".." };
+                write!(f, "for {var} in {start}{range}{end} {body}")
+            }
+            StmtKind::Return(Some(value)) => write!(f, "return {value}"),
+            StmtKind::Return(None) => f.write_str("return"),
+            StmtKind::Break => f.write_str("break"),
+            StmtKind::Continue => f.write_str("continue"),
+        }
+    }
+}
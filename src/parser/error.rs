@@ -0,0 +1,93 @@
+use crate::location::{Loc, Span};
+
+use derive_more::Display;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ParseErrorKind {
+    #[error("{0}")]
+    Any(String),
+    #[error("unexpected token {found}, expected {expected}")]
+    UnexpectedToken { found: String, expected: String },
+    #[error("unexpected end of input, expected {0}")]
+    UnexpectedEof(String),
+    #[error("invalid left-hand side of assignment")]
+    InvalidAssignTarget,
+    #[error("unknown format `{0}`")]
+    UnknownFormat(String),
+    #[error("field `{0}` is declared twice")]
+    DuplicateField(String),
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "{span}: ERROR - {kind}")]
+pub struct ParseError<'loc> {
+    kind: ParseErrorKind,
+    span: Span<'loc>,
+    suggestions: Vec<String>,
+}
+
+impl<'loc> From<ParseErrorKind> for ParseError<'loc> {
+    fn from(kind: ParseErrorKind) -> Self {
+        Self {
+            kind,
+            span: Default::default(),
+            suggestions: Vec::new(),
+        }
+    }
+}
+
+impl<'loc> ParseError<'loc> {
+    pub fn new(kind: impl Into<ParseErrorKind>, span: Span<'loc>) -> Self {
+        Self {
+            kind: kind.into(),
+            span,
+            suggestions: Vec::new(),
+        }
+    }
+    pub fn set_span(mut self, span: Span<'loc>) -> Self {
+        self.span = span;
+        self
+    }
+    /// Names that were likely meant instead of the offending one.
+    pub fn with_suggestions(mut self, suggestions: Vec<String>) -> Self {
+        self.suggestions = suggestions;
+        self
+    }
+
+    pub fn kind(&self) -> &ParseErrorKind {
+        &self.kind
+    }
+    pub fn loc(&self) -> &Loc<'loc> {
+        &self.span.start
+    }
+    pub fn span(&self) -> &Span<'loc> {
+        &self.span
+    }
+    pub fn suggestions(&self) -> &[String] {
+        &self.suggestions
+    }
+
+    pub fn unexpected_token(
+        found: impl ToString,
+        expected: impl ToString,
+        span: Span<'loc>,
+    ) -> Self {
+        Self {
+            kind: ParseErrorKind::UnexpectedToken {
+                found: found.to_string(),
+                expected: expected.to_string(),
+            },
+            span,
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn unexpected_eof(expected: impl ToString, span: Span<'loc>) -> Self {
+        Self {
+            kind: ParseErrorKind::UnexpectedEof(expected.to_string()),
+            span,
+            suggestions: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,696 @@
+//! Precedence climbing (Pratt) parser over the [`Lexer`] token stream.
+//!
+//! Operators from loosest to tightest binding:
+//!
+//! | prec | operators                      | assoc |
+//! |------|--------------------------------|-------|
+//! | 1    | `\|\|`                         | left  |
+//! | 2    | `&&`                           | left  |
+//! | 3    | `==` `!=` `<` `<=` `>` `>=`    | left  |
+//! | 4    | `\|`                           | left  |
+//! | 5    | `^^` `~` (xnor)                | left  |
+//! | 6    | `&`                            | left  |
+//! | 7    | `<<` `>>`                      | left  |
+//! | 8    | `+` `-`                        | left  |
+//! | 9    | `*` `/` `//` `%`               | left  |
+//! | 10   | prefix `-` `+` `!` `~`         | right |
+//! | 11   | `^`                            | right |
+//! | max  | calls `f(x)`, fields `a.b`     | left  |
+//!
+//! `^` binds tighter than prefix operators, so `-2^2` is `-(2^2)`, while its
+//! right operand may still start with one: `2^-1` is `2^(-1)`.
+//!
+//! A program is a list of statements separated by `;`:
+//!
+//! ```text
+//! stmt  := name (':=' | '::' | ':' type ('=' | ':')) expr format?
+//!        | name '::' '(' params ')' ('->' type)? block
+//!        | name '::' 'struct' '{' (name (':' type)? (';' | ','))* '}'
+//!        | name ('=' | '+=' | '-=' | ...) expr
+//!        | 'while' expr block
+//!        | 'for' name 'in' expr ('..' | '..=') expr block
+//!        | 'return' expr? | 'break' | 'continue'
+//!        | expr format?
+//! block := '{' (stmt ';')* expr? '}'
+//! format := '->' (name | int)+
+//! ```
+//!
+//! A number followed by known units is a quantity, `9.81 m/s^2` or `5km`:
+//!
+//! ```text
+//! quantity := number unit (('*' | '/') unit)*
+//! unit     := name ('^' '-'? int)?
+//! ```
+//!
+//! Statements ending with a block need no `;`, and a trailing expression is
+//! the value of its block. `if c { .. } elif c { .. } else { .. }` and blocks
+//! are expressions.
+
+pub mod ast;
+pub mod error;
+
+#[cfg(test)]
+mod tests;
+
+use crate::error::{Error, Result};
+use std::borrow::Cow;
+
+use crate::lev::closest_words;
+use crate::lexer::{
+    token::{Token, KEYWORDS},
+    Lexer,
+};
+use crate::location::{Loc, Span, Spanned};
+use crate::units::Unit;
+
+use ast::{
+    Assoc, BinOp, Block, Expr, ExprKind, FnDecl, Param, Stmt, StmtKind, StructDecl, UnaryOp,
+    UNARY_PRECEDENCE,
+};
+use error::{ParseError, ParseErrorKind};
+
+#[derive(Debug)]
+pub struct Parser<'p> {
+    lexer: Lexer<'p>,
+    curr: Token<'p>,
+    span: Span<'p>,
+    /// End of the last consumed token, where the current node ends.
+    prev_end: Loc<'p>,
+}
+
+impl<'p> Parser<'p> {
+    pub fn new(lexer: Lexer<'p>) -> Result<'p, Self> {
+        let mut s = Self {
+            lexer,
+            curr: Token::Eof,
+            span: Span::default(),
+            prev_end: Loc::default(),
+        };
+        s.bump()?;
+        Ok(s)
+    }
+
+    /// Parses a single expression that has to span the whole input.
+    pub fn parse_expression(&mut self) -> Result<'p, Expr<'p>> {
+        let expr = self.parse_expr()?;
+        self.expect_eof()?;
+        Ok(expr)
+    }
+
+    pub fn parse_expr(&mut self) -> Result<'p, Expr<'p>> {
+        self.parse_expr_prec(0)
+    }
+
+    /// Parses statements up to the end of input.
+    pub fn parse_program(&mut self) -> Result<'p, Block<'p>> {
+        let start = self.span.clone();
+        let mut program = self.parse_stmts(&Token::Eof)?;
+        program.span = start.to(&self.span);
+        Ok(program)
+    }
+
+    pub fn parse_stmt(&mut self) -> Result<'p, Stmt<'p>> {
+        let start = self.span.clone();
+        let kind = match self.curr {
+            Token::While => {
+                self.bump()?;
+                let cond = self.parse_expr()?;
+                StmtKind::While(cond, self.parse_block()?)
+            }
+            Token::For => self.parse_for()?,
+            Token::Return => {
+                self.bump()?;
+                let value = match self.curr {
+                    Token::SemiColon | Token::RSquirly | Token::Eof => None,
+                    _ => Some(self.parse_expr()?),
+                };
+                StmtKind::Return(value)
+            }
+            Token::Break => {
+                self.bump()?;
+                StmtKind::Break
+            }
+            Token::Continue => {
+                self.bump()?;
+                StmtKind::Continue
+            }
+            // a statement starting with a block ends with it
+            Token::If | Token::LSquirly => StmtKind::Expr(self.parse_primary()?),
+            _ => {
+                let expr = self.parse_expr()?;
+                let assign_op = BinOp::from_assign_token(&self.curr);
+                match expr.kind {
+                    ExprKind::Ident(name)
+                        if matches!(self.curr, Token::DeclAssign | Token::Decl | Token::Colon) =>
+                    {
+                        self.parse_decl(name)?
+                    }
+                    ExprKind::Ident(name) if self.curr == Token::Assign || assign_op.is_some() => {
+                        self.bump()?;
+                        let value = self.parse_expr()?;
+                        StmtKind::Assign {
+                            name,
+                            op: assign_op,
+                            value,
+                        }
+                    }
+                    _ if self.curr == Token::Assign || assign_op.is_some() => {
+                        return Err(Error::parsing_error(ParseError::new(
+                            ParseErrorKind::InvalidAssignTarget,
+                            expr.span,
+                        )))
+                    }
+                    kind => StmtKind::Expr(self.parse_format(Expr::new(kind, expr.span))?),
+                }
+            }
+        };
+        Ok(Stmt::new(kind, self.span_from(&start)))
+    }
+}
+
+impl<'p> Parser<'p> {
+    /// Advances to the next token, returning the one that was current.
+    /// Trivia is skipped, doc comments included.
+    fn bump(&mut self) -> Result<'p, Spanned<'p, Token<'p>>> {
+        let next = loop {
+            match self.lexer.next() {
+                Some(Ok(Spanned { value, .. }))
+                    if value.is_trivia() || matches!(value, Token::DocComment(_)) =>
+                {
+                    continue
+                }
+                Some(tok) => break tok?,
+                // an empty span right after the last token
+                None => {
+                    let end = self.span.end.clone();
+                    break Spanned::new(Token::Eof, Span::new(end.clone(), end));
+                }
+            }
+        };
+        self.prev_end = self.span.end.clone();
+        Ok(Spanned::new(
+            std::mem::replace(&mut self.curr, next.value),
+            std::mem::replace(&mut self.span, next.span),
+        ))
+    }
+
+    /// Span from `start` to the end of the last consumed token.
+    fn span_from(&self, start: &Span<'p>) -> Span<'p> {
+        Span::new(start.start.clone(), self.prev_end.clone())
+    }
+
+    fn error_expected(&self, expected: impl ToString) -> Error<'p> {
+        Error::parsing_error(self.unexpected(expected))
+    }
+
+    fn unexpected(&self, expected: impl ToString) -> ParseError<'p> {
+        match self.curr {
+            Token::Eof => ParseError::unexpected_eof(expected, self.span.clone()),
+            ref tok => ParseError::unexpected_token(tok, expected, self.span.clone()),
+        }
+    }
+
+    fn expect(&mut self, tok: Token<'p>) -> Result<'p, Span<'p>> {
+        if self.curr == tok {
+            Ok(self.bump()?.span)
+        } else {
+            Err(self.error_expected(tok))
+        }
+    }
+
+    fn expect_eof(&self) -> Result<'p, ()> {
+        match self.curr {
+            Token::Eof => Ok(()),
+            _ => Err(self.error_expected(Token::Eof)),
+        }
+    }
+
+    fn expect_ident(&mut self, expected: &str) -> Result<'p, Cow<'p, str>> {
+        match self.curr {
+            Token::Ident(_) => match self.bump()?.value {
+                Token::Ident(name) => Ok(name),
+                _ => unreachable!(),
+            },
+            _ => Err(self.error_expected(expected)),
+        }
+    }
+
+    /// Parses statements until `end`, which is left current.
+    fn parse_stmts(&mut self, end: &Token<'p>) -> Result<'p, Block<'p>> {
+        let start = self.span.clone();
+        let mut stmts = Vec::new();
+        let mut tail = None;
+        while self.curr != *end {
+            if self.curr == Token::SemiColon {
+                self.bump()?;
+                continue;
+            }
+            let stmt = self.parse_stmt()?;
+            if self.curr == Token::SemiColon {
+                self.bump()?;
+            } else if self.curr == *end {
+                if let StmtKind::Expr(expr) = stmt.kind {
+                    tail = Some(expr.boxed());
+                    break;
+                }
+            } else if !stmt.is_block_like() {
+                return Err(self
+                    .misplaced_keyword(&stmt)
+                    .unwrap_or_else(|| self.error_expected("`;`")));
+            }
+            stmts.push(stmt);
+        }
+        Ok(Block {
+            stmts,
+            tail,
+            span: self.span_from(&start),
+        })
+    }
+
+    /// Wraps `expr` in the format following a `->`, if there is one. The
+    /// format is made of identifiers and integers, up to the end of the
+    /// statement.
+    fn parse_format(&mut self, expr: Expr<'p>) -> Result<'p, Expr<'p>> {
+        if self.curr != Token::Arrow {
+            return Ok(expr);
+        }
+        self.bump()?;
+        let start = self.span.clone();
+        let mut words = Vec::new();
+        loop {
+            match &self.curr {
+                Token::Ident(word) => words.push(word.to_string()),
+                Token::Int(digits) => words.push(digits.to_string()),
+                _ => break,
+            }
+            self.bump()?;
+        }
+        if words.is_empty() {
+            return Err(self.error_expected("format"));
+        }
+        let format = words.join(" ").parse().map_err(|word| {
+            let kind = ParseErrorKind::UnknownFormat(word);
+            Error::parsing_error(ParseError::new(kind, self.span_from(&start)))
+        })?;
+        let span = expr.span.to(&self.span_from(&start));
+        Ok(Expr::new(ExprKind::Format(expr.boxed(), format), span))
+    }
+
+    /// A statement like `whiel x < 3 {}` reads as the identifier `whiel`
+    /// followed by garbage, point out the keyword it resembles.
+    fn misplaced_keyword(&self, stmt: &Stmt<'p>) -> Option<Error<'p>> {
+        let StmtKind::Expr(Expr {
+            kind: ExprKind::Ident(name),
+            ..
+        }) = &stmt.kind
+        else {
+            return None;
+        };
+        let keywords = closest_words(name, KEYWORDS);
+        if keywords.is_empty() {
+            return None;
+        }
+        let err = self.unexpected("`;`").with_suggestions(keywords);
+        Some(Error::parsing_error(err))
+    }
+
+    fn parse_block(&mut self) -> Result<'p, Block<'p>> {
+        let start = self.expect(Token::LSquirly)?;
+        let mut block = self.parse_stmts(&Token::RSquirly)?;
+        self.expect(Token::RSquirly)?;
+        block.span = self.span_from(&start);
+        Ok(block)
+    }
+
+    /// Parses a declaration of `name`, the `:=`, `::` or `:` being current.
+    fn parse_decl(&mut self, name: Cow<'p, str>) -> Result<'p, StmtKind<'p>> {
+        let (ty, constant) = match self.curr {
+            Token::DeclAssign => (None, false),
+            Token::Decl => (None, true),
+            _ => {
+                self.bump()?;
+                let ty = self.expect_ident("type name")?;
+                match self.curr {
+                    Token::Assign => (Some(ty), false),
+                    Token::Colon => (Some(ty), true),
+                    _ => return Err(self.error_expected("`=` or `:`")),
+                }
+            }
+        };
+        self.bump()?;
+        if constant && ty.is_none() && self.curr == Token::LParen && self.is_fn_decl() {
+            return self.parse_fn(name);
+        }
+        if constant && ty.is_none() && self.curr == Token::Struct {
+            return self.parse_struct(name);
+        }
+        let value = self.parse_expr()?;
+        let value = self.parse_format(value)?;
+        Ok(StmtKind::Decl {
+            name,
+            ty,
+            value,
+            constant,
+        })
+    }
+
+    /// Whether the `(` under the cursor starts a parameter list rather than
+    /// an expression: names with optional types between the parentheses,
+    /// followed by `{` or by `-> type {`. `c :: (1 + 2) -> hex` is a constant.
+    fn is_fn_decl(&self) -> bool {
+        let mut tokens = self
+            .lexer
+            .clone()
+            .filter_map(|tok| tok.ok().map(|tok| tok.value))
+            .filter(|tok| !tok.is_trivia() && !matches!(tok, Token::DocComment(_)));
+        loop {
+            match tokens.next() {
+                Some(Token::RParen) => break,
+                Some(Token::Ident(_)) => {}
+                _ => return false,
+            }
+            let mut next = tokens.next();
+            if next == Some(Token::Colon) {
+                if !matches!(tokens.next(), Some(Token::Ident(_))) {
+                    return false;
+                }
+                next = tokens.next();
+            }
+            match next {
+                Some(Token::Comma) => {}
+                Some(Token::RParen) => break,
+                _ => return false,
+            }
+        }
+        match tokens.next() {
+            Some(Token::LSquirly) => true,
+            Some(Token::Arrow) => matches!(
+                (tokens.next(), tokens.next()),
+                (Some(Token::Ident(_)), Some(Token::LSquirly))
+            ),
+            _ => false,
+        }
+    }
+
+    fn parse_fn(&mut self, name: Cow<'p, str>) -> Result<'p, StmtKind<'p>> {
+        self.expect(Token::LParen)?;
+        let mut params = Vec::new();
+        while self.curr != Token::RParen {
+            let name = self.expect_ident("parameter name")?;
+            let ty = match self.curr {
+                Token::Colon => {
+                    self.bump()?;
+                    Some(self.expect_ident("type name")?)
+                }
+                _ => None,
+            };
+            params.push(Param { name, ty });
+            if self.curr != Token::Comma {
+                break;
+            }
+            self.bump()?;
+        }
+        self.expect(Token::RParen)?;
+        let ret = match self.curr {
+            Token::Arrow => {
+                self.bump()?;
+                Some(self.expect_ident("return type")?)
+            }
+            _ => None,
+        };
+        let body = self.parse_block()?;
+        Ok(StmtKind::Fn(Box::new(FnDecl {
+            name,
+            params,
+            ret,
+            body,
+        })))
+    }
+
+    /// Parses the fields of a struct, separated by `;` or `,`.
+    fn parse_struct(&mut self, name: Cow<'p, str>) -> Result<'p, StmtKind<'p>> {
+        self.expect(Token::Struct)?;
+        self.expect(Token::LSquirly)?;
+        let mut fields: Vec<Param> = Vec::new();
+        while self.curr != Token::RSquirly {
+            let start = self.span.clone();
+            let field = self.expect_ident("field name")?;
+            if fields.iter().any(|other| other.name == field) {
+                let kind = ParseErrorKind::DuplicateField(field.into_owned());
+                return Err(Error::parsing_error(ParseError::new(kind, start)));
+            }
+            let ty = match self.curr {
+                Token::Colon => {
+                    self.bump()?;
+                    Some(self.expect_ident("type name")?)
+                }
+                _ => None,
+            };
+            fields.push(Param { name: field, ty });
+            match self.curr {
+                Token::SemiColon | Token::Comma => {
+                    self.bump()?;
+                }
+                Token::RSquirly => {}
+                _ => return Err(self.error_expected("`;` or `}`")),
+            }
+        }
+        self.expect(Token::RSquirly)?;
+        Ok(StmtKind::Struct(StructDecl { name, fields }))
+    }
+
+    fn parse_for(&mut self) -> Result<'p, StmtKind<'p>> {
+        self.expect(Token::For)?;
+        let var = self.expect_ident("loop variable")?;
+        match &self.curr {
+            Token::Ident(kw) if kw == "in" => self.bump()?,
+            _ => return Err(self.error_expected("`in`")),
+        };
+        let start = self.parse_expr()?;
+        let inclusive = match self.curr {
+            Token::Range => false,
+            Token::RangeInc => true,
+            _ => return Err(self.error_expected("`..` or `..=`")),
+        };
+        self.bump()?;
+        let end = self.parse_expr()?;
+        let body = self.parse_block()?;
+        Ok(StmtKind::For {
+            var,
+            start: Box::new(start),
+            end: Box::new(end),
+            inclusive,
+            body,
+        })
+    }
+
+    /// Parses `if`, `elif` and `else` branches, `else if` being the same as
+    /// `elif`.
+    fn parse_if(&mut self) -> Result<'p, Expr<'p>> {
+        let start = self.expect(Token::If)?;
+        let mut branches = vec![(self.parse_expr()?, self.parse_block()?)];
+        let mut otherwise = None;
+        loop {
+            match self.curr {
+                Token::Elif => {
+                    self.bump()?;
+                }
+                Token::Else => {
+                    self.bump()?;
+                    if self.curr != Token::If {
+                        otherwise = Some(self.parse_block()?);
+                        break;
+                    }
+                    self.bump()?;
+                }
+                _ => break,
+            }
+            branches.push((self.parse_expr()?, self.parse_block()?));
+        }
+        Ok(Expr::new(
+            ExprKind::If(branches, otherwise),
+            self.span_from(&start),
+        ))
+    }
+
+    fn parse_expr_prec(&mut self, min_prec: u8) -> Result<'p, Expr<'p>> {
+        let mut lhs = self.parse_unary()?;
+        while let Some(op) = BinOp::from_token(&self.curr) {
+            let prec = op.precedence();
+            if prec < min_prec {
+                break;
+            }
+            self.bump()?;
+            let next_prec = match op.assoc() {
+                Assoc::Left => prec + 1,
+                Assoc::Right => prec,
+            };
+            let rhs = self.parse_expr_prec(next_prec)?;
+            let span = lhs.span.to(&rhs.span);
+            lhs = Expr::new(ExprKind::Binary(op, lhs.boxed(), rhs.boxed()), span);
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<'p, Expr<'p>> {
+        match UnaryOp::from_token(&self.curr) {
+            Some(op) => {
+                let start = self.bump()?.span;
+                let expr = self.parse_expr_prec(UNARY_PRECEDENCE)?;
+                let span = start.to(&expr.span);
+                Ok(Expr::new(ExprKind::Unary(op, expr.boxed()), span))
+            }
+            None => self.parse_postfix(),
+        }
+    }
+
+    fn parse_postfix(&mut self) -> Result<'p, Expr<'p>> {
+        let mut expr = self.parse_primary()?;
+        loop {
+            expr = match self.curr {
+                Token::LParen => {
+                    self.bump()?;
+                    let args = self.parse_args()?;
+                    let span = self.span_from(&expr.span);
+                    Expr::new(ExprKind::Call(expr.boxed(), args), span)
+                }
+                Token::Dot => {
+                    self.bump()?;
+                    match self.bump()? {
+                        Spanned {
+                            value: Token::Ident(name),
+                            span,
+                        } => {
+                            let span = expr.span.to(&span);
+                            Expr::new(ExprKind::Field(expr.boxed(), name), span)
+                        }
+                        Spanned { value, span } => {
+                            return Err(Error::parsing_error(ParseError::unexpected_token(
+                                value,
+                                "field name",
+                                span,
+                            )))
+                        }
+                    }
+                }
+                _ => return Ok(expr),
+            };
+        }
+    }
+
+    /// Parses a comma separated argument list, the opening `(` already consumed.
+    fn parse_args(&mut self) -> Result<'p, Vec<Expr<'p>>> {
+        let mut args = Vec::new();
+        while self.curr != Token::RParen {
+            args.push(self.parse_expr()?);
+            if self.curr != Token::Comma {
+                break;
+            }
+            self.bump()?;
+        }
+        self.expect(Token::RParen)?;
+        Ok(args)
+    }
+
+    fn parse_primary(&mut self) -> Result<'p, Expr<'p>> {
+        let kind = match self.curr {
+            Token::Int(i) => ExprKind::Int(i),
+            Token::BigInt(ref i) => ExprKind::BigInt(i.clone()),
+            Token::Float(x) => ExprKind::Float(x),
+            Token::Decimal(x, ref d) => ExprKind::Decimal(x, d.clone()),
+            Token::Imag(x) => ExprKind::Imag(x),
+            Token::True => ExprKind::Bool(true),
+            Token::False => ExprKind::Bool(false),
+            Token::Ident(_) => {
+                let start = self.span.clone();
+                let name = self.expect_ident("identifier")?;
+                return Ok(Expr::new(ExprKind::Ident(name), start));
+            }
+            Token::LSquirly => {
+                let block = self.parse_block()?;
+                let span = block.span.clone();
+                return Ok(Expr::new(ExprKind::Block(block), span));
+            }
+            Token::If => return self.parse_if(),
+            Token::LParen => {
+                let start = self.bump()?.span;
+                let mut expr = self.parse_expr()?;
+                self.expect(Token::RParen)?;
+                expr.span = self.span_from(&start);
+                return Ok(expr);
+            }
+            _ => return Err(self.error_expected("expression")),
+        };
+        let span = self.bump()?.span;
+        let number = matches!(
+            kind,
+            ExprKind::Int(_) | ExprKind::BigInt(_) | ExprKind::Float(_) | ExprKind::Decimal(..)
+        );
+        let expr = Expr::new(kind, span);
+        match self.curr {
+            Token::Ident(ref name) if number && Unit::lookup(name).is_some() => {
+                self.parse_units(expr)
+            }
+            _ => Ok(expr),
+        }
+    }
+
+    /// The units after a number, `9.81 m/s^2`. `*` and `/` only continue the
+    /// units when a unit follows them, `5 km / 2 h` is a division.
+    fn parse_units(&mut self, value: Expr<'p>) -> Result<'p, Expr<'p>> {
+        let mut units = vec![self.parse_unit(1)?];
+        loop {
+            let sign = match self.curr {
+                Token::Mul => 1,
+                Token::Div => -1,
+                _ => break,
+            };
+            match self.peek() {
+                Some(Token::Ident(name)) if Unit::lookup(&name).is_some() => {}
+                _ => break,
+            }
+            self.bump()?;
+            units.push(self.parse_unit(sign)?);
+        }
+        let span = self.span_from(&value.span);
+        Ok(Expr::new(ExprKind::Quantity(value.boxed(), units), span))
+    }
+
+    /// `unit ('^' '-'? int)?`, with the power multiplied by `sign`.
+    fn parse_unit(&mut self, sign: i32) -> Result<'p, (Spanned<'p, Cow<'p, str>>, i32)> {
+        let span = self.span.clone();
+        let name = Spanned::new(self.expect_ident("unit")?, span);
+        if self.curr != Token::Pow {
+            return Ok((name, sign));
+        }
+        self.bump()?;
+        let negative = self.curr == Token::Sub;
+        if negative {
+            self.bump()?;
+        }
+        let power = match self.curr {
+            Token::Int(i) => i32::try_from(i).ok().filter(|i| *i <= i32::from(i8::MAX)),
+            _ => return Err(self.error_expected("integer power")),
+        };
+        let Some(power) = power else {
+            return Err(self.error_expected("smaller power"));
+        };
+        self.bump()?;
+        Ok((
+            name,
+            if negative {
+                -sign * power
+            } else {
+                sign * power
+            },
+        ))
+    }
+
+    /// The token after the current one.
+    fn peek(&self) -> Option<Token<'p>> {
+        self.lexer
+            .clone()
+            .filter_map(|tok| tok.ok().map(|tok| tok.value))
+            .find(|tok| !tok.is_trivia() && !matches!(tok, Token::DocComment(_)))
+    }
+}
@@ -0,0 +1,432 @@
+use super::{
+    ast::{Block, Expr, ExprKind, StmtKind},
+    error::ParseErrorKind,
+    Parser,
+};
+use crate::{error::Error, format::Format, format::Notation, lexer::Lexer};
+
+/// Renders the tree as a fully parenthesized s-expression.
+fn sexpr(expr: &Expr<'_>) -> String {
+    match &expr.kind {
+        ExprKind::Int(i) => i.to_string(),
+        ExprKind::BigInt(i) => i.to_string(),
+        ExprKind::Float(x) | ExprKind::Decimal(x, _) => format!("{x:?}"),
+        ExprKind::Imag(x) => format!("{x:?}i"),
+        ExprKind::Quantity(value, units) => {
+            let units: Vec<_> = units
+                .iter()
+                .map(|(u, e)| format!("{}^{e}", u.value))
+                .collect();
+            format!("(units {} [{}])", sexpr(value), units.join(" "))
+        }
+        ExprKind::Bool(b) => b.to_string(),
+        ExprKind::Ident(i) => i.to_string(),
+        ExprKind::Unary(op, e) => format!("({op} {})", sexpr(e)),
+        ExprKind::Binary(op, l, r) => format!("({op} {} {})", sexpr(l), sexpr(r)),
+        ExprKind::Call(callee, args) => {
+            let args: Vec<_> = args.iter().map(sexpr).collect();
+            format!("(call {} [{}])", sexpr(callee), args.join(" "))
+        }
+        ExprKind::Field(e, name) => format!("(. {} {name})", sexpr(e)),
+        ExprKind::Block(_) | ExprKind::If(..) => expr.to_string(),
+        ExprKind::Format(e, format) => format!("(-> {} {format})", sexpr(e)),
+    }
+}
+
+fn parse(source: &str) -> Expr<'_> {
+    Parser::new(Lexer::new(source))
+        .and_then(|mut p| p.parse_expression())
+        .unwrap_or_else(|err| panic!("{source:?}: {err}"))
+}
+
+fn test_parse(source: &str, expect: &str) {
+    assert_eq!(sexpr(&parse(source)), expect, "source: {source:?}");
+}
+
+fn parse_program(source: &str) -> Block<'_> {
+    Parser::new(Lexer::new(source))
+        .and_then(|mut p| p.parse_program())
+        .unwrap_or_else(|err| panic!("{source:?}: {err}"))
+}
+
+fn parse_err(source: &str) -> ParseErrorKind {
+    match Parser::new(Lexer::new(source)).and_then(|mut p| p.parse_expression()) {
+        Err(Error::ParsingError(err)) => err.kind().clone(),
+        other => panic!("{source:?}: expected parse error, got {other:?}"),
+    }
+}
+
+#[test]
+fn simple() {
+    test_parse("64 * 420", "(* 64 420)");
+    test_parse(
+        "4.20 * ((69 + 69_420) >> 69)",
+        "(* 4.2 (>> (+ 69 69420) 69))",
+    );
+}
+
+#[test]
+fn comments() {
+    test_parse("1 + # one\n/* two */ 2 ## doc", "(+ 1 2)");
+}
+
+#[test]
+fn precedence() {
+    test_parse("1 + 2 * 3", "(+ 1 (* 2 3))");
+    test_parse("1 * 2 + 3", "(+ (* 1 2) 3)");
+    test_parse("1 << 2 + 3", "(<< 1 (+ 2 3))");
+    test_parse("a & b ^^ c | d", "(| (^^ (& a b) c) d)");
+    test_parse("a ~ b & c", "(~ a (& b c))");
+    test_parse("1 < 2 == true", "(== (< 1 2) true)");
+    test_parse("a || b && c == d", "(|| a (&& b (== c d)))");
+    test_parse("7 // 2 % 3", "(% (// 7 2) 3)");
+}
+
+#[test]
+fn associativity() {
+    test_parse("1 - 2 - 3", "(- (- 1 2) 3)");
+    test_parse("8 / 4 / 2", "(/ (/ 8 4) 2)");
+    test_parse("2 ^ 3 ^ 2", "(^ 2 (^ 3 2))");
+    test_parse("a || b || c", "(|| (|| a b) c)");
+}
+
+#[test]
+fn unary() {
+    test_parse("-2 ^ 2", "(- (^ 2 2))");
+    test_parse("2 ^ -1", "(^ 2 (- 1))");
+    test_parse("-a * b", "(* (- a) b)");
+    test_parse("!a && ~b", "(&& (! a) (~ b))");
+    test_parse("- -1", "(- (- 1))");
+    test_parse("+x", "(+ x)");
+}
+
+#[test]
+fn postfix() {
+    test_parse("sqrt(2)", "(call sqrt [2])");
+    test_parse("max(1, 2 + 3,)", "(call max [1 (+ 2 3)])");
+    test_parse("f()", "(call f [])");
+    test_parse("a.b.c", "(. (. a b) c)");
+    test_parse("point.x ^ 2", "(^ (. point x) 2)");
+    test_parse("-f(x)(y)", "(- (call (call f [x]) [y]))");
+}
+
+#[test]
+fn quantities() {
+    test_parse("5 km", "(units 5 [km^1])");
+    test_parse(
+        "9.81 m/s^2 * 70 kg",
+        "(* (units 9.81 [m^1 s^-2]) (units 70 [kg^1]))",
+    );
+    test_parse("5 km / 2 h", "(/ (units 5 [km^1]) (units 2 [h^1]))");
+    test_parse("1 kg*m^2/s^-1", "(units 1 [kg^1 m^2 s^1])");
+    test_parse("-3 m ^ 2", "(- (units 3 [m^2]))");
+    test_parse("2 m * x", "(* (units 2 [m^1]) x)");
+    // only numbers take units
+    test_parse("x * m", "(* x m)");
+    assert!(matches!(
+        parse_err("2 m^x"),
+        ParseErrorKind::UnexpectedToken { .. }
+    ));
+}
+
+#[test]
+fn display_roundtrip() {
+    for source in [
+        "1 + 2 * 3",
+        "(1 + 2) * 3",
+        "1 - (2 - 3)",
+        "(2 ^ 3) ^ 2",
+        "-(2 ^ 2)",
+        "(-2) ^ 2",
+        "!(a && b) || c.d(1, 2.5)",
+        "(a + b).len",
+        "(2 m) ^ 2",
+        "-5 km/h^2 * 3 s",
+    ] {
+        let expr = parse(source);
+        let printed = expr.to_string();
+        assert_eq!(
+            sexpr(&parse(&printed)),
+            sexpr(&expr),
+            "{source} => {printed}"
+        );
+    }
+    assert_eq!(parse("(1 + (2 * 3))").to_string(), "1 + 2 * 3");
+}
+
+#[test]
+fn errors() {
+    assert!(matches!(parse_err("1 +"), ParseErrorKind::UnexpectedEof(_)));
+    assert!(matches!(
+        parse_err("(1 + 2"),
+        ParseErrorKind::UnexpectedEof(_)
+    ));
+    assert!(matches!(
+        parse_err("1 2"),
+        ParseErrorKind::UnexpectedToken { .. }
+    ));
+    assert!(matches!(
+        parse_err("a.1"),
+        ParseErrorKind::UnexpectedToken { .. }
+    ));
+    assert!(matches!(
+        parse_err("* 2"),
+        ParseErrorKind::UnexpectedToken { .. }
+    ));
+}
+
+#[test]
+fn error_loc() {
+    let err = Parser::new(Lexer::new("1 +\n  * 2")).and_then(|mut p| p.parse_expression());
+    let Err(Error::ParsingError(err)) = err else {
+        panic!("expected parse error");
+    };
+    assert_eq!((err.loc().line(), err.loc().col()), (2, 3));
+    assert_eq!(err.span().range(), 6..7);
+}
+
+#[test]
+fn spans() {
+    let source = "max(a, 2) + -(b.c ^ 2)";
+    let expr = parse(source);
+    assert_eq!(&source[expr.span.range()], source);
+    let ExprKind::Binary(_, lhs, rhs) = &expr.kind else {
+        panic!("expected binary expression");
+    };
+    assert_eq!(&source[lhs.span.range()], "max(a, 2)");
+    assert_eq!(&source[rhs.span.range()], "-(b.c ^ 2)");
+    let ExprKind::Unary(_, inner) = &rhs.kind else {
+        panic!("expected unary expression");
+    };
+    let ExprKind::Binary(_, field, _) = &inner.kind else {
+        panic!("expected binary expression");
+    };
+    assert_eq!(&source[field.span.range()], "b.c");
+}
+
+#[test]
+fn statements() {
+    let source = r#"
+## a doc comment
+x := 1;
+limit :i32: 10;
+y :f32= x * 2;
+x += 3; x = x ^ 2;
+while x < limit { x -= 1; if x == 5 { break } }
+for i in 0..=3 { continue; }
+x
+"#;
+    let program = parse_program(source);
+    let kinds: Vec<_> = program
+        .stmts
+        .iter()
+        .map(|stmt| match &stmt.kind {
+            StmtKind::Expr(_) => "expr",
+            StmtKind::Decl { constant: true, .. } => "const",
+            StmtKind::Decl { .. } => "decl",
+            StmtKind::Assign { op: Some(_), .. } => "op-assign",
+            StmtKind::Assign { .. } => "assign",
+            StmtKind::Fn(_) => "fn",
+            StmtKind::While(..) => "while",
+            StmtKind::For { .. } => "for",
+            _ => "other",
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            "decl",
+            "const",
+            "decl",
+            "op-assign",
+            "assign",
+            "while",
+            "for"
+        ]
+    );
+    assert_eq!(
+        program.tail.as_deref().map(|e| e.to_string()).as_deref(),
+        Some("x")
+    );
+    assert_eq!(&source[program.stmts[1].span.range()], "limit :i32: 10");
+
+    // a `;` after the last statement leaves the program without a value
+    assert!(parse_program("x := 1; x;").tail.is_none());
+    assert!(parse_program("").stmts.is_empty());
+}
+
+#[test]
+fn functions() {
+    let program = parse_program(
+        "area :: (w, h :float) -> float { w * h / 2 }\n\
+         half :: (10) / 2;\n\
+         noop :: () {}",
+    );
+    let StmtKind::Fn(area) = &program.stmts[0].kind else {
+        panic!("expected a function");
+    };
+    assert_eq!(area.name, "area");
+    assert_eq!(area.params.len(), 2);
+    assert_eq!(area.params[1].ty.as_deref(), Some("float"));
+    assert_eq!(area.ret.as_deref(), Some("float"));
+    assert!(matches!(
+        &program.stmts[1].kind,
+        StmtKind::Decl { constant: true, .. }
+    ));
+    assert!(matches!(&program.stmts[2].kind, StmtKind::Fn(_)));
+
+    // a format suffix is not a return type
+    let program = parse_program("c :: (1 + 2) -> hex; d :: (x) -> hex; f :: (x,) -> int { x }");
+    for stmt in &program.stmts[..2] {
+        let StmtKind::Decl { value, .. } = &stmt.kind else {
+            panic!("expected a constant, got {stmt}");
+        };
+        assert!(matches!(value.kind, ExprKind::Format(..)));
+    }
+    assert!(matches!(&program.stmts[2].kind, StmtKind::Fn(_)));
+}
+
+#[test]
+fn structs() {
+    let program = parse_program(
+        "Person :: struct {\n\
+             name :string; # no strings yet\n\
+             age :i32;\n\
+         }\n\
+         Empty :: struct {}\n\
+         p := Person(1, 2).age",
+    );
+    let StmtKind::Struct(person) = &program.stmts[0].kind else {
+        panic!("expected a struct");
+    };
+    assert_eq!(person.name, "Person");
+    assert_eq!(person.field("age"), Some(1));
+    assert_eq!(person.fields[0].ty.as_deref(), Some("string"));
+    assert_eq!(
+        person.to_string(),
+        "Person :: struct { name :string; age :i32 }"
+    );
+    let StmtKind::Struct(empty) = &program.stmts[1].kind else {
+        panic!("expected a struct");
+    };
+    assert_eq!(empty.to_string(), "Empty :: struct {}");
+    assert!(matches!(&program.stmts[2].kind, StmtKind::Decl { .. }));
+    assert_eq!(
+        parse_program("P :: struct { x, y :int, } P(1, 2)")
+            .body()
+            .to_string(),
+        "P :: struct { x; y :int }; P(1, 2)"
+    );
+}
+
+#[test]
+fn if_expressions() {
+    test_parse("1 + if a { 1 } else { 2 }", "(+ 1 if a { 1 } else { 2 })");
+    test_parse(
+        "if a { 1 } elif b { 2 } else if c { 3 } else { 4 }",
+        "if a { 1 } elif b { 2 } elif c { 3 } else { 4 }",
+    );
+    test_parse("{ x := 2; x * x }", "{ x := 2; x * x }");
+}
+
+#[test]
+fn program_roundtrip() {
+    for source in [
+        "x := 1; y :: 2; z :int= 3; w :f32: 4.5; x += y; x = (if x > 2 { x } else { 0 }) + 1;",
+        "f :: (a, b :int) -> int { if a > b { return a; }; b } f(1, 2)",
+        "i := 0; while true { i += 1; if i > 3 { break; }; }; for j in 0..i { continue; }; i",
+    ] {
+        let printed = parse_program(source).body().to_string();
+        assert_eq!(
+            parse_program(&printed).body().to_string(),
+            printed,
+            "{source}"
+        );
+    }
+    assert_eq!(
+        parse_program("f :: (a) -> int { a }").body().to_string(),
+        "f :: (a) -> int { a };"
+    );
+}
+
+#[test]
+fn statement_errors() {
+    let err = |source| match Parser::new(Lexer::new(source)).and_then(|mut p| p.parse_program()) {
+        Err(Error::ParsingError(err)) => err.kind().clone(),
+        other => panic!("{source:?}: expected parse error, got {other:?}"),
+    };
+    assert_eq!(err("1 + 2 = 3"), ParseErrorKind::InvalidAssignTarget);
+    assert!(matches!(
+        err("x := 1 y := 2"),
+        ParseErrorKind::UnexpectedToken { .. }
+    ));
+    assert!(matches!(
+        err("f :: (a) {"),
+        ParseErrorKind::UnexpectedEof(_)
+    ));
+    assert!(matches!(
+        err("for i 0..2 {}"),
+        ParseErrorKind::UnexpectedToken { .. }
+    ));
+    assert!(matches!(
+        err("x :int 3"),
+        ParseErrorKind::UnexpectedToken { .. }
+    ));
+    assert_eq!(
+        err("P :: struct { x; y; x }"),
+        ParseErrorKind::DuplicateField("x".to_owned())
+    );
+    assert!(matches!(
+        err("P :: struct { x y }"),
+        ParseErrorKind::UnexpectedToken { .. }
+    ));
+}
+
+#[test]
+fn keyword_suggestions() {
+    let suggestions =
+        |source| match Parser::new(Lexer::new(source)).and_then(|mut p| p.parse_program()) {
+            Err(Error::ParsingError(err)) => err.suggestions().to_vec(),
+            other => panic!("{source:?}: expected parse error, got {other:?}"),
+        };
+    assert_eq!(suggestions("retrun 1"), ["return"]);
+    assert_eq!(suggestions("n := 0; whiel n < 3 { n += 1; }"), ["while"]);
+    assert_eq!(suggestions("f :: () { brek 1 }"), ["break"]);
+    assert!(suggestions("x := 1 y := 2").is_empty());
+}
+
+#[test]
+fn formats() {
+    let program = parse_program("x := 255; x * 2 -> hex _");
+    assert_eq!(
+        program.format(),
+        Some(Format::new(Notation::Hex).group(true))
+    );
+    assert_eq!(program.body().to_string(), "x := 255; x * 2 -> hex _");
+    assert_eq!(
+        parse_program("1 / 3 -> sci 4")
+            .format()
+            .map(|f| f.to_string()),
+        Some("sci 4".into())
+    );
+    assert_eq!(parse_program("1 -> hex; 2").format(), None);
+    assert_eq!(
+        parse_program("x := { 2 -> bin }; x").body().to_string(),
+        "x := { 2 -> bin }; x"
+    );
+
+    let err = |source| match Parser::new(Lexer::new(source)).and_then(|mut p| p.parse_program()) {
+        Err(Error::ParsingError(err)) => err.kind().clone(),
+        other => panic!("{source:?}: expected parse error, got {other:?}"),
+    };
+    assert_eq!(
+        err("1 -> hexa"),
+        ParseErrorKind::UnknownFormat("hexa".into())
+    );
+    assert_eq!(err("1 -> hex 0"), ParseErrorKind::UnknownFormat("0".into()));
+    assert!(matches!(err("1 ->"), ParseErrorKind::UnexpectedEof(_)));
+    assert!(matches!(
+        err("1 -> ;"),
+        ParseErrorKind::UnexpectedToken { .. }
+    ));
+}
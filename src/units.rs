@@ -0,0 +1,246 @@
+//! The registry of physical units: SI base and derived units with their
+//! prefixes, some others like `h` or `bar`, imperial units and the
+//! temperature scales `degC` and `degF`.
+
+use std::fmt;
+
+/// Exponents of the SI base dimensions: length, mass, time, current,
+/// temperature, amount of substance and luminous intensity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Dimension([i8; 7]);
+
+impl Dimension {
+    const fn of(exps: [i8; 7]) -> Self {
+        Self(exps)
+    }
+
+    /// `None` when an exponent leaves the range, see [`Dimension::exponent`].
+    pub(crate) fn pow(self, exp: i32) -> Option<Self> {
+        let mut exps = self.0;
+        for e in &mut exps {
+            *e = Self::exponent(i32::from(*e).checked_mul(exp)?)?;
+        }
+        Some(Self(exps))
+    }
+
+    /// `None` when an exponent leaves the range, see [`Dimension::exponent`].
+    pub(crate) fn mul(self, rhs: Self) -> Option<Self> {
+        let mut exps = self.0;
+        for (e, r) in exps.iter_mut().zip(rhs.0) {
+            *e = Self::exponent(i32::from(*e).checked_add(i32::from(r))?)?;
+        }
+        Some(Self(exps))
+    }
+
+    /// `e` as an exponent, within `±i8::MAX` so that reciprocals have one
+    /// too.
+    pub(crate) fn exponent(e: i32) -> Option<i8> {
+        i8::try_from(e).ok().filter(|e| *e != i8::MIN)
+    }
+
+    /// Whether this is a base dimension, like length.
+    pub(crate) fn is_base(&self) -> bool {
+        self.0.iter().map(|e| i32::from(*e).abs()).sum::<i32>() == 1
+    }
+}
+
+const LENGTH: Dimension = Dimension::of([1, 0, 0, 0, 0, 0, 0]);
+const MASS: Dimension = Dimension::of([0, 1, 0, 0, 0, 0, 0]);
+const TIME: Dimension = Dimension::of([0, 0, 1, 0, 0, 0, 0]);
+const CURRENT: Dimension = Dimension::of([0, 0, 0, 1, 0, 0, 0]);
+const TEMPERATURE: Dimension = Dimension::of([0, 0, 0, 0, 1, 0, 0]);
+const AMOUNT: Dimension = Dimension::of([0, 0, 0, 0, 0, 1, 0]);
+const LUMINOSITY: Dimension = Dimension::of([0, 0, 0, 0, 0, 0, 1]);
+const FREQUENCY: Dimension = Dimension::of([0, 0, -1, 0, 0, 0, 0]);
+const VOLUME: Dimension = Dimension::of([3, 0, 0, 0, 0, 0, 0]);
+const SPEED: Dimension = Dimension::of([1, 0, -1, 0, 0, 0, 0]);
+const FORCE: Dimension = Dimension::of([1, 1, -2, 0, 0, 0, 0]);
+const PRESSURE: Dimension = Dimension::of([-1, 1, -2, 0, 0, 0, 0]);
+const ENERGY: Dimension = Dimension::of([2, 1, -2, 0, 0, 0, 0]);
+const POWER: Dimension = Dimension::of([2, 1, -3, 0, 0, 0, 0]);
+const CHARGE: Dimension = Dimension::of([0, 0, 1, 1, 0, 0, 0]);
+const VOLTAGE: Dimension = Dimension::of([2, 1, -3, -1, 0, 0, 0]);
+const RESISTANCE: Dimension = Dimension::of([2, 1, -3, -2, 0, 0, 0]);
+const CAPACITANCE: Dimension = Dimension::of([-2, -1, 4, 2, 0, 0, 0]);
+const FLUX: Dimension = Dimension::of([2, 1, -2, -1, 0, 0, 0]);
+const FLUX_DENSITY: Dimension = Dimension::of([0, 1, -2, -1, 0, 0, 0]);
+const INDUCTANCE: Dimension = Dimension::of([2, 1, -2, -2, 0, 0, 0]);
+
+/// A unit of the registry, worth `factor` SI units of its dimension.
+/// Temperature scales also have an `offset` from zero kelvin.
+#[derive(Debug, PartialEq)]
+struct Named {
+    symbol: &'static str,
+    factor: f64,
+    offset: f64,
+    dim: Dimension,
+    /// Whether SI prefixes apply, as in `km`.
+    prefixed: bool,
+    /// Whether products of other units simplify to this one.
+    derived: bool,
+}
+
+const fn unit(symbol: &'static str, factor: f64, dim: Dimension) -> Named {
+    Named {
+        symbol,
+        factor,
+        offset: 0.0,
+        dim,
+        prefixed: false,
+        derived: false,
+    }
+}
+
+const fn si(symbol: &'static str, factor: f64, dim: Dimension) -> Named {
+    Named {
+        prefixed: true,
+        ..unit(symbol, factor, dim)
+    }
+}
+
+const fn derived(symbol: &'static str, dim: Dimension) -> Named {
+    Named {
+        derived: true,
+        ..si(symbol, 1.0, dim)
+    }
+}
+
+const fn scale(symbol: &'static str, factor: f64, offset: f64) -> Named {
+    Named {
+        offset,
+        ..unit(symbol, factor, TEMPERATURE)
+    }
+}
+
+const FAHRENHEIT: f64 = 5.0 / 9.0;
+
+/// The registry, exact symbols take precedence over prefixed ones so that
+/// `min` is a minute and not a milli-inch.
+const UNITS: &[Named] = &[
+    si("m", 1.0, LENGTH),
+    si("g", 1e-3, MASS),
+    si("s", 1.0, TIME),
+    si("A", 1.0, CURRENT),
+    si("K", 1.0, TEMPERATURE),
+    si("mol", 1.0, AMOUNT),
+    si("cd", 1.0, LUMINOSITY),
+    si("Hz", 1.0, FREQUENCY),
+    derived("N", FORCE),
+    derived("J", ENERGY),
+    derived("W", POWER),
+    derived("Pa", PRESSURE),
+    derived("C", CHARGE),
+    derived("V", VOLTAGE),
+    derived("Ω", RESISTANCE),
+    si("ohm", 1.0, RESISTANCE),
+    derived("F", CAPACITANCE),
+    derived("Wb", FLUX),
+    derived("T", FLUX_DENSITY),
+    derived("H", INDUCTANCE),
+    si("L", 1e-3, VOLUME),
+    si("l", 1e-3, VOLUME),
+    si("Wh", 3600.0, ENERGY),
+    si("bar", 1e5, PRESSURE),
+    unit("atm", 101_325.0, PRESSURE),
+    unit("min", 60.0, TIME),
+    unit("h", 3600.0, TIME),
+    unit("d", 86_400.0, TIME),
+    unit("day", 86_400.0, TIME),
+    unit("in", 0.0254, LENGTH),
+    unit("ft", 0.3048, LENGTH),
+    unit("yd", 0.9144, LENGTH),
+    unit("mi", 1609.344, LENGTH),
+    unit("lb", 0.453_592_37, MASS),
+    unit("oz", 0.453_592_37 / 16.0, MASS),
+    unit("mph", 0.44704, SPEED),
+    unit("psi", 6_894.757_293_168_361, PRESSURE),
+    scale("degC", 1.0, 273.15),
+    scale("degF", FAHRENHEIT, 459.67 * FAHRENHEIT),
+];
+
+const PREFIXES: &[(&str, f64)] = &[
+    ("Q", 1e30),
+    ("R", 1e27),
+    ("Y", 1e24),
+    ("Z", 1e21),
+    ("E", 1e18),
+    ("P", 1e15),
+    ("T", 1e12),
+    ("G", 1e9),
+    ("M", 1e6),
+    ("k", 1e3),
+    ("h", 1e2),
+    ("da", 1e1),
+    ("d", 1e-1),
+    ("c", 1e-2),
+    ("m", 1e-3),
+    ("µ", 1e-6),
+    ("u", 1e-6),
+    ("n", 1e-9),
+    ("p", 1e-12),
+    ("f", 1e-15),
+    ("a", 1e-18),
+    ("z", 1e-21),
+    ("y", 1e-24),
+    ("r", 1e-27),
+    ("q", 1e-30),
+];
+
+/// A unit of the registry with an optional SI prefix, `km`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Unit {
+    prefix: Option<&'static (&'static str, f64)>,
+    named: &'static Named,
+}
+
+impl Unit {
+    /// Looks a unit symbol up, `None` when there is no such unit.
+    pub fn lookup(symbol: &str) -> Option<Self> {
+        if let Some(named) = UNITS.iter().find(|u| u.symbol == symbol) {
+            return Some(Self {
+                prefix: None,
+                named,
+            });
+        }
+        PREFIXES.iter().find_map(|prefix| {
+            let rest = symbol.strip_prefix(prefix.0)?;
+            let named = UNITS.iter().find(|u| u.prefixed && u.symbol == rest)?;
+            Some(Self {
+                prefix: Some(prefix),
+                named,
+            })
+        })
+    }
+
+    /// The derived SI unit of a dimension, `N` for forces.
+    pub(crate) fn derived(dim: Dimension) -> Option<Self> {
+        let named = UNITS.iter().find(|u| u.derived && u.dim == dim)?;
+        Some(Self {
+            prefix: None,
+            named,
+        })
+    }
+
+    /// How many SI units of its dimension the unit is worth.
+    pub(crate) fn factor(&self) -> f64 {
+        self.named.factor * self.prefix.map_or(1.0, |p| p.1)
+    }
+
+    /// Where zero is on the scale, in kelvin for temperatures.
+    pub(crate) fn offset(&self) -> f64 {
+        self.named.offset
+    }
+
+    pub fn dim(&self) -> Dimension {
+        self.named.dim
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((prefix, _)) = self.prefix {
+            f.write_str(prefix)?;
+        }
+        f.write_str(self.named.symbol)
+    }
+}
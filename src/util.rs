@@ -5,14 +5,85 @@ type DynResult<T> = Result<T, Box<dyn Error + 'static>>;
 
 pub(crate) fn parse_hex_integer(s: impl AsRef<str>) -> DynResult<u64> {
     let s = s.as_ref();
-    match &s[..2] {
-        "0x" | "0Xd" => Ok(u64::from_str_radix(&s[2..], 16)?),
-        s => Err(format!("expected prefix '0x' or '0X', got '{s}' for Hex Integer").into()),
+    match s.get(..2) {
+        Some("0x" | "0X") => Ok(u64::from_str_radix(&s[2..], 16)?),
+        _ => Err(format!("expected prefix '0x' or '0X', got '{s}' for Hex Integer").into()),
     }
 }
 
+/// Parses a C99 hexadecimal float like `0x1.8p3`, the `p` exponent is a
+/// power of two written in decimal.
 pub(crate) fn parse_hex_float(s: impl AsRef<str>) -> DynResult<f64> {
-    Ok(f64::from_bits(parse_hex_integer(s)?))
+    let s = s.as_ref();
+    let body = match s.get(..2) {
+        Some("0x" | "0X") => &s[2..],
+        _ => return Err(format!("expected prefix '0x' or '0X', got '{s}' for Hex Float").into()),
+    };
+    let (mantissa, exp) = body
+        .split_once(['p', 'P'])
+        .ok_or("expected 'p' exponent for Hex Float")?;
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if int.is_empty() && frac.is_empty() {
+        return Err("expected hexadecimal digits for Hex Float".into());
+    }
+    // far enough out of range to overflow or underflow, and to not
+    // overflow when adding the digits
+    let mut exp = exp.parse::<i64>()?.clamp(-1 << 20, 1 << 20);
+
+    // the leading 61 to 64 bits, and whether any digit after them is not 0
+    let mut value: u64 = 0;
+    let mut sticky = false;
+    for (idx, c) in int.bytes().chain(frac.bytes()).enumerate() {
+        let d = from_hex_digit(c).ok_or_else(|| format!("invalid hex digit '{}'", c as char))?;
+        if idx >= int.len() {
+            exp -= 4;
+        }
+        if value >> 60 == 0 {
+            value = value * 16 + d as u64;
+        } else {
+            exp += 4;
+            sticky |= d != 0;
+        }
+    }
+    Ok(round_to_f64(value, exp, sticky))
+}
+
+/// The float nearest to `value * 2^exp`, ties to even. `sticky` tells that
+/// the exact value is a bit more than that.
+fn round_to_f64(value: u64, exp: i64, sticky: bool) -> f64 {
+    if value == 0 {
+        return 0.0;
+    }
+    let len = i64::from(64 - value.leading_zeros());
+    // exponent of the leading bit, and how many bits fit below it
+    let top = exp + len - 1;
+    if top > 1023 {
+        return f64::INFINITY;
+    }
+    let bits = if top >= -1022 { 53 } else { top + 1075 };
+    let shift = len - bits;
+    if shift <= 0 {
+        return scale(value, exp);
+    }
+    if shift > 64 {
+        return 0.0;
+    }
+    let kept = value.checked_shr(shift as u32).unwrap_or(0);
+    let rest = value & (u64::MAX >> (64 - shift));
+    let half = 1 << (shift - 1);
+    let up = rest > half || (rest == half && (sticky || kept & 1 == 1));
+    scale(kept + up as u64, exp + shift)
+}
+
+/// `value * 2^exp` for a product that is a float, in steps that stay exact.
+fn scale(value: u64, mut exp: i64) -> f64 {
+    let mut x = value as f64;
+    while exp.abs() > 1000 && x.is_finite() {
+        let step = exp.signum() * 1000;
+        x *= 2f64.powi(step as i32);
+        exp -= step;
+    }
+    x * 2f64.powi(exp as i32)
 }
 
 #[inline(always)]
@@ -51,11 +122,10 @@ pub(super) const fn is_digit(c: u8) -> bool {
 
 #[inline(always)]
 pub(super) const fn from_hex_digit(c: u8) -> Option<u8> {
-    match from_digit(c) {
-        Some(b'a'..=b'f') => Some(10 + c - b'a'),
-        Some(b'A'..=b'F') => Some(10 + c - b'A'),
-        Some(c) => Some(c),
-        None => None,
+    match c {
+        b'a'..=b'f' => Some(10 + c - b'a'),
+        b'A'..=b'F' => Some(10 + c - b'A'),
+        _ => from_digit(c),
     }
 }
 